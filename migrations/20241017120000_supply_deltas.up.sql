@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS supply_deltas (
+    slot INTEGER NOT NULL PRIMARY KEY REFERENCES beacon_states(slot),
+    timestamp TIMESTAMPTZ NOT NULL,
+    issuance_delta INT8 NOT NULL,
+    burn_delta INT8 NOT NULL
+);
@@ -0,0 +1,6 @@
+DROP INDEX beacon_blocks_block_number_idx;
+
+ALTER TABLE
+	beacon_blocks
+DROP COLUMN
+	block_number;
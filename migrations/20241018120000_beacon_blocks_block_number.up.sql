@@ -0,0 +1,6 @@
+ALTER TABLE
+	beacon_blocks
+ADD COLUMN
+	block_number INT4;
+
+CREATE UNIQUE INDEX beacon_blocks_block_number_idx ON beacon_blocks (block_number);
@@ -0,0 +1,8 @@
+CREATE TABLE beacon_withdrawals (
+    index INT8 NOT NULL PRIMARY KEY,
+    slot INT4 NOT NULL REFERENCES beacon_states(slot),
+    address TEXT NOT NULL,
+    amount INT8 NOT NULL
+);
+
+CREATE INDEX beacon_withdrawals_address_slot_idx ON beacon_withdrawals (address, slot);
@@ -0,0 +1,4 @@
+ALTER TABLE
+	beacon_blocks
+DROP COLUMN
+	deposit_count;
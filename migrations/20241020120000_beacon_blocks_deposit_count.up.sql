@@ -0,0 +1,4 @@
+ALTER TABLE
+	beacon_blocks
+ADD COLUMN
+	deposit_count INT4;
@@ -1,7 +1,7 @@
 use crate::beacon_chain::{
     balances, node::BeaconNode, node::BeaconNodeHttp, slots::Slot,
 };
-use futures::{pin_mut, StreamExt};
+use futures::{future, pin_mut, Stream, StreamExt};
 use pit_wall::Progress;
 use sqlx::PgPool;
 use tracing::{debug, info, warn};
@@ -68,6 +68,33 @@ async fn estimate_work_todo(
     .unwrap()
 }
 
+// the bucket a slot falls in for the given granularity, every bucket gets at most one balance
+fn granularity_bucket(granularity: &Granularity, slot: Slot) -> i64 {
+    match granularity {
+        Granularity::Slot => slot.0.into(),
+        Granularity::Epoch => slot.epoch().into(),
+        Granularity::Hour => slot.date_time().timestamp().div_euclid(3600),
+        Granularity::Day => slot.date_time().timestamp().div_euclid(86400),
+    }
+}
+
+// keep only the first item of every bucket. items have to come sorted by slot, that way
+// all items of a bucket are next to each other and remembering the last bucket is enough
+fn first_per_bucket<'a, T: 'a>(
+    items: impl Stream<Item = T> + 'a,
+    granularity: &'a Granularity,
+    slot_of: impl Fn(&T) -> Slot + 'a,
+) -> impl Stream<Item = T> + 'a {
+    items
+        .scan(None, move |last_bucket, item| {
+            let bucket = granularity_bucket(granularity, slot_of(&item));
+            let is_new_bucket = *last_bucket != Some(bucket);
+            *last_bucket = Some(bucket);
+            future::ready(Some(is_new_bucket.then_some(item)))
+        })
+        .filter_map(future::ready)
+}
+
 // this function is designed and implemented for
 // backfill the records in table beacon_validators_balance
 // first, we use work_estimate calcualte how many slots that in beacon_validators_balance
@@ -85,7 +112,15 @@ pub async fn backfill_balances(
     // create beacon endpoint request client side
     // and configure with correct beacon url request parameters and address suffixes
     let beacon_node = BeaconNodeHttp::new();
+    backfill_balances_with_node(db_pool, &beacon_node, granularity, from).await
+}
 
+async fn backfill_balances_with_node(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    granularity: &Granularity,
+    from: Slot,
+) {
     // invoke estimate_work_todo to get the exactly number of the slots by providing
     // the unit of the garnularity {day, hour, slot, or epoch} and start slot value
     let work_todo = estimate_work_todo(db_pool, granularity, from).await;
@@ -144,14 +179,16 @@ pub async fn backfill_balances(
         }
     });
 
+    // the boundary check alone shouldn't be trusted to yield a single slot per bucket
+    let rows_deduped =
+        first_per_bucket(rows_filtered, granularity, |row| Slot(row.slot));
+
     // here we traver each item in the queried filter map
     // and establish data fetching task (beacon balance backfill) one by one
     // since queried records are sorted in DESC order
-    let tasks = rows_filtered.map(|row| {
-        // each task owns it own beacon query client
-        let beacon_node_clone = beacon_node.clone();
+    let tasks = rows_deduped.map(|row| {
         async move {
-            let validator_balances = beacon_node_clone
+            let validator_balances = beacon_node
                 .get_validator_balances(&row.state_root)
                 .await
                 .unwrap();
@@ -198,3 +235,98 @@ pub async fn backfill_balances(
         info!("{}", progress.get_progress_string());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{MockBeaconNode, ValidatorBalance};
+    use crate::beacon_chain::states::store_state;
+    use crate::db::db::tests::TestDb;
+    use crate::units::GweiNewtype;
+
+    #[test]
+    fn granularity_bucket_test() {
+        // slot 3599 is the first slot of the second day since genesis
+        assert_eq!(
+            granularity_bucket(&Granularity::Day, Slot(3598)) + 1,
+            granularity_bucket(&Granularity::Day, Slot(3599))
+        );
+        assert_eq!(
+            granularity_bucket(&Granularity::Day, Slot(3599)),
+            granularity_bucket(&Granularity::Day, Slot(3600))
+        );
+        assert_eq!(granularity_bucket(&Granularity::Epoch, Slot(63)), 1);
+    }
+
+    #[tokio::test]
+    async fn backfill_one_balance_per_day_test() {
+        let test_db = TestDb::new().await;
+        // far beyond slots other tests store, first_of_day is the first slot of a day
+        let first_of_day = Slot(3599 + ((i32::MAX - 3599) / 7200 - 1) * 7200);
+        assert!(first_of_day.is_first_of_day());
+        let slots = [first_of_day, first_of_day + 1, first_of_day + 2];
+        for slot in slots {
+            store_state(
+                &test_db.pool,
+                &format!("0xbackfill_one_balance_per_day_{}", slot.0),
+                slot,
+            )
+            .await;
+        }
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        backfill_balances_with_node(
+            &test_db.pool,
+            &beacon_node,
+            &Granularity::Day,
+            first_of_day,
+        )
+        .await;
+
+        let balance_count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM beacon_validators_balance
+            WHERE state_root LIKE '0xbackfill_one_balance_per_day_%'
+            "#
+        )
+        .fetch_one(&test_db.pool)
+        .await
+        .unwrap();
+
+        crate::beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            first_of_day,
+            first_of_day + 2,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(balance_count, 1);
+    }
+
+    #[tokio::test]
+    async fn first_per_bucket_test() {
+        // three slots of one day and the last slot of the day before, sorted like the rows
+        // the backfill reads. without the dedup every slot would come through
+        let first_of_day = Slot(3599);
+        let slots = futures::stream::iter([
+            first_of_day + 2,
+            first_of_day + 1,
+            first_of_day,
+            first_of_day - 1,
+        ]);
+
+        let deduped: Vec<Slot> =
+            first_per_bucket(slots, &Granularity::Day, |slot| *slot)
+                .collect()
+                .await;
+
+        assert_eq!(deduped, vec![first_of_day + 2, first_of_day - 1]);
+    }
+}
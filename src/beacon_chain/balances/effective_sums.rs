@@ -2,9 +2,21 @@ use super::BeaconNode;
 use crate::beacon_chain::node::StateRoot;
 use crate::beacon_chain::slots::Slot;
 use crate::units::{GweiImprecise, GweiNewtype};
+use anyhow::anyhow;
 use chrono::{DateTime, Utc};
+use futures::{pin_mut, StreamExt};
+use lazy_static::lazy_static;
+use pit_wall::Progress;
 use serde::{Deserialize, Serialize};
-use sqlx::PgExecutor;
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tracing::{debug, info};
+
+// every state needs the full validator set from the beacon node, which is slow,
+// so we keep this many requests in flight while backfilling
+const GET_VALIDATORS_CONCURRENCY_LIMIT: usize = 16;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct EffectiveBalanceSum {
@@ -43,6 +55,40 @@ pub async fn get_effective_balance_sum(
         .fold(GweiNewtype(0), |sum, item| sum + item.effective_balance())
 }
 
+lazy_static! {
+    // effective balances only change at epoch boundaries, so one sum per epoch is all we need.
+    // entries are never evicted, at 12 bytes per epoch and an epoch every 6.4 minutes a year of
+    // uptime holds around 82k entries, about 1MB with map overhead, against a full validator set
+    // download for every repeated lookup
+    static ref EFFECTIVE_BALANCE_SUM_BY_EPOCH: Mutex<HashMap<i32, GweiNewtype>> =
+        Mutex::new(HashMap::new());
+}
+
+// the effective balance sum of the state at the first slot of the given epoch, every slot in
+// the epoch shares it. the beacon node is only asked once per epoch for the life of the process
+pub async fn get_effective_balance_sum_for_epoch(
+    beacon_node: &impl BeaconNode,
+    epoch: i32,
+) -> anyhow::Result<GweiNewtype> {
+    if let Some(sum) =
+        EFFECTIVE_BALANCE_SUM_BY_EPOCH.lock().unwrap().get(&epoch)
+    {
+        return Ok(*sum);
+    }
+
+    let slot = Slot::first_of_epoch(epoch);
+    let state_root = beacon_node
+        .get_state_root_by_slot(slot)
+        .await?
+        .ok_or_else(|| {
+            anyhow!("no state root for slot {slot}, the first of epoch {epoch}")
+        })?;
+    let sum = get_effective_balance_sum(beacon_node, &state_root).await;
+
+    EFFECTIVE_BALANCE_SUM_BY_EPOCH.lock().unwrap().insert(epoch, sum);
+    Ok(sum)
+}
+
 // store the accumulated sum value of effective_balance to beacon_states table's effective_balance_sum field
 pub async fn store_effective_balance_sum(
     executor: impl PgExecutor<'_>,
@@ -66,12 +112,151 @@ pub async fn store_effective_balance_sum(
     .unwrap();
 }
 
+// the effective balance sum of the most recent state which has one stored
+pub async fn get_last_stored_effective_balance_sum(
+    executor: impl PgExecutor<'_>,
+) -> Option<EffectiveBalanceSum> {
+    sqlx::query!(
+        r#"
+        SELECT
+            slot AS "slot: Slot",
+            effective_balance_sum AS "effective_balance_sum!"
+        FROM
+            beacon_states
+        WHERE
+            effective_balance_sum IS NOT NULL
+        ORDER BY slot DESC
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| {
+        EffectiveBalanceSum::new(row.slot, GweiNewtype(row.effective_balance_sum))
+    })
+}
+
+// effective_balance_sum was not computed for historic states, unlike the total balances in
+// beacon_validators_balance which are handled by backfill::backfill_balances.
+// this function picks up every state from the given slot onwards that has no effective_balance_sum yet,
+// asks the beacon node for the validators of that state and stores the summed effective balance
+pub async fn backfill_effective_balance_sums(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    from: Slot,
+) {
+    backfill_effective_balance_sums_with_limit(
+        db_pool,
+        beacon_node,
+        from,
+        GET_VALIDATORS_CONCURRENCY_LIMIT,
+    )
+    .await
+}
+
+async fn backfill_effective_balance_sums_with_limit(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    from: Slot,
+    concurrency_limit: usize,
+) {
+    backfill_effective_balance_sums_with(
+        db_pool,
+        from,
+        concurrency_limit,
+        |state_root| async move {
+            get_effective_balance_sum(beacon_node, &state_root).await
+        },
+    )
+    .await
+}
+
+// the sum requests run concurrently up to concurrency_limit, buffered keeps the
+// results in slot order so the sums are written sequentially as they come in
+async fn backfill_effective_balance_sums_with<F, Fut>(
+    db_pool: &PgPool,
+    from: Slot,
+    concurrency_limit: usize,
+    get_sum: F,
+) where
+    F: Fn(StateRoot) -> Fut,
+    Fut: Future<Output = GweiNewtype>,
+{
+    let work_todo = sqlx::query!(
+        "
+        SELECT
+            COUNT(*) AS \"count!\"
+        FROM
+            beacon_states
+        WHERE
+            slot >= $1
+        AND
+            effective_balance_sum IS NULL
+        ",
+        from.0
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap()
+    .count;
+
+    let mut progress = Progress::new(
+        "backfill-effective-balance-sums",
+        work_todo.try_into().unwrap(),
+    );
+
+    let rows = sqlx::query!(
+        "
+        SELECT
+            state_root,
+            slot
+        FROM
+            beacon_states
+        WHERE
+            slot >= $1
+        AND
+            effective_balance_sum IS NULL
+        ORDER BY slot ASC
+        ",
+        from.0
+    )
+    .fetch(db_pool);
+
+    let get_sum = &get_sum;
+    let tasks = rows.map(|row| async move {
+        let row = row.unwrap();
+        let sum = get_sum(row.state_root.clone()).await;
+        (row.state_root, row.slot, sum)
+    });
+
+    let buffered_tasks = tasks.buffered(concurrency_limit);
+    pin_mut!(buffered_tasks);
+
+    while let Some((state_root, slot, sum)) = buffered_tasks.next().await {
+        debug!(
+            slot,
+            sum = %sum.display_eth(),
+            "computed effective balance sum"
+        );
+
+        store_effective_balance_sum(db_pool, &state_root, &sum).await;
+
+        progress.inc_work_done();
+        info!("{}", progress.get_progress_string());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::{anyhow, Result};
     use async_trait::async_trait;
     use sqlx::Acquire;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
     use test_context::test_context;
+    use tokio::sync::Barrier;
+    use tokio::time::timeout;
 
     use super::*;
     use crate::beacon_chain::states::store_state;
@@ -91,6 +276,47 @@ mod tests {
 
     const SLOT_0_STATE_ROOT: &str = "0x_mock_slot_state_root";
 
+    #[tokio::test]
+    async fn effective_balance_sum_for_epoch_memoized_test() {
+        // the memo is process wide, an epoch no other test asks for keeps this test independent
+        let epoch = 1_000_850;
+        let mut beacon_node = beacon_chain::MockBeaconNode::new();
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .withf(move |slot| *slot == Slot::first_of_epoch(epoch))
+            .times(1)
+            .returning(|_| Ok(Some("0xepoch_memo_state_root".to_string())));
+        beacon_node
+            .expect_get_validators_by_state()
+            .times(1)
+            .returning(|_| {
+                Ok(vec![
+                    ValidatorEnvelope {
+                        status: "active_ongoing".to_string(),
+                        validator: Validator {
+                            effective_balance: GweiNewtype(32_000_000_000),
+                        },
+                    },
+                    ValidatorEnvelope {
+                        status: "pending_queued".to_string(),
+                        validator: Validator {
+                            effective_balance: GweiNewtype(32_000_000_000),
+                        },
+                    },
+                ])
+            });
+
+        let first = get_effective_balance_sum_for_epoch(&beacon_node, epoch)
+            .await
+            .unwrap();
+        let second = get_effective_balance_sum_for_epoch(&beacon_node, epoch)
+            .await
+            .unwrap();
+
+        assert_eq!(first, GweiNewtype(32_000_000_000));
+        assert_eq!(second, first);
+    }
+
     #[tokio::test]
     async fn test_get_effective_balance_sum() {
         let mock_beacon_node = MockBeaconNode {};
@@ -134,6 +360,89 @@ mod tests {
         assert_eq!(stored_sum, sum.0);
     }
 
+    #[tokio::test]
+    async fn test_backfill_effective_balance_sums() {
+        let test_db = TestDb::new().await;
+        let state_root = "0xtest_backfill_effective_balance_sums";
+        // far beyond any slot other tests store, so the backfill only picks up this state
+        let slot = Slot(i32::MAX - 1);
+
+        store_state(&test_db.pool, state_root, slot).await;
+
+        backfill_effective_balance_sums(&test_db.pool, &MockBeaconNode, slot)
+            .await;
+
+        let stored_sum = sqlx::query!(
+            "
+            SELECT effective_balance_sum
+            FROM beacon_states
+            WHERE state_root = $1
+            ",
+            state_root
+        )
+        .fetch_one(&test_db.pool)
+        .await
+        .unwrap()
+        .effective_balance_sum;
+
+        beacon_chain::states::delete_state(&test_db.pool, slot).await;
+
+        assert_eq!(stored_sum, Some(64_000_000_000_000_000));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_effective_balance_sums_concurrency_limit() {
+        let test_db = TestDb::new().await;
+        let concurrency_limit = 2;
+        let first_slot = Slot(i32::MAX - 100);
+        let slots: Vec<Slot> = (0..6).map(|i| first_slot + i).collect();
+
+        for slot in &slots {
+            store_state(
+                &test_db.pool,
+                &format!("0xtest_backfill_effective_limit_{}", slot.0),
+                *slot,
+            )
+            .await;
+        }
+
+        let mut beacon_node = beacon_chain::MockBeaconNode::new();
+        beacon_node
+            .expect_get_validators_by_state()
+            .times(slots.len())
+            .returning(|_| Ok(vec![]));
+
+        // every request waits until concurrency_limit requests are in flight, the
+        // backfill hangs, and times out, if it does not run them concurrently
+        let barrier = Barrier::new(concurrency_limit);
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let backfill = backfill_effective_balance_sums_with(
+            &test_db.pool,
+            first_slot,
+            concurrency_limit,
+            |state_root| {
+                let (beacon_node, barrier, in_flight, max_in_flight) =
+                    (&beacon_node, &barrier, &in_flight, &max_in_flight);
+                async move {
+                    let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(count, Ordering::SeqCst);
+                    barrier.wait().await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    get_effective_balance_sum(beacon_node, &state_root).await
+                }
+            },
+        );
+        let result = timeout(Duration::from_secs(10), backfill).await;
+
+        for slot in &slots {
+            beacon_chain::states::delete_state(&test_db.pool, *slot).await;
+        }
+
+        result.expect("expect requests to run concurrently");
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), concurrency_limit);
+    }
+
     // create mock beacon node instance that implements all defined functions in trait BeaconNode
 
     struct MockBeaconNode;
@@ -192,6 +501,10 @@ mod tests {
             Err(anyhow!("Not implemented in the MockBeaconNode"))
         }
 
+        async fn get_finalized_slot(&self) -> Result<Slot> {
+            Err(anyhow!("Not implemented in the MockBeaconNode"))
+        }
+
         async fn get_last_finalized_block(&self) -> Result<BeaconBlock> {
             Err(anyhow!("Not implemented in the MockBeaconNode"))
         }
@@ -1,12 +1,19 @@
 pub mod backfill;
 mod effective_sums;
 
+pub use effective_sums::{
+    backfill_effective_balance_sums, get_effective_balance_sum_for_epoch,
+    get_last_stored_effective_balance_sum,
+};
+
 use super::node::{BeaconNode, BeaconNodeHttp, ValidatorBalance};
 use super::{states::get_last_state, GweiInTime, Slot};
 use crate::units::GweiNewtype;
 use chrono::{Duration, DurationRound};
+use futures::{future::BoxFuture, FutureExt, Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgExecutor, PgPool};
+use sqlx::{Connection, PgConnection, PgExecutor, PgPool};
+use thiserror::Error;
 
 // this function will iterate and accumulate all passed in ValidatorBalance#balance field
 // value and return
@@ -78,32 +85,112 @@ pub async fn get_last_effective_balance_sum(
 
 // query gwei field from beacon_validators_balance table
 // each returned record's timestamp should be distinct and timestamp should be located in today's timestamp scope.
-pub async fn get_validator_balances_by_start_of_day(
-    executor: impl PgExecutor<'_>,
+// days are cut at UTC midnight whatever the session's time zone is, each day gets the balance of its earliest slot.
+pub async fn get_validator_balances_by_start_of_day<'a>(
+    executor: impl PgExecutor<'a> + 'a,
 ) -> Vec<GweiInTime> {
+    query_validator_balances_by_start_of_day(executor)
+        .await
+        .unwrap()
+}
+
+async fn query_validator_balances_by_start_of_day<'a>(
+    executor: impl PgExecutor<'a> + 'a,
+) -> sqlx::Result<Vec<GweiInTime>> {
+    stream_validator_balances_by_start_of_day(executor)
+        .try_collect()
+        .await
+}
+
+// same series as get_validator_balances_by_start_of_day, row by row as the db returns them
+pub fn stream_validator_balances_by_start_of_day<'a>(
+    executor: impl PgExecutor<'a> + 'a,
+) -> impl Stream<Item = sqlx::Result<GweiInTime>> + 'a {
     sqlx::query!(
         r#"
         SELECT
-            DISTINCT ON (DATE_TRUNC('day', timestamp)) DATE_TRUNC('day', timestamp) AS "day_timestamp!",
+            DISTINCT ON (DATE_TRUNC('day', timestamp AT TIME ZONE 'UTC'))
+            DATE_TRUNC('day', timestamp AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS "day_timestamp!",
             gwei
         FROM
             beacon_validators_balance
         ORDER BY
-            DATE_TRUNC('day', timestamp)
+            DATE_TRUNC('day', timestamp AT TIME ZONE 'UTC'),
+            timestamp
         "#
     )
-        .fetch_all(executor)
-        .await
-        .map(|rows| {
-            rows.iter()
-                .map(|row| {
-                    GweiInTime {
-                        t: row.day_timestamp.duration_trunc(Duration::days(1)).unwrap().timestamp() as u64,
-                        v: row.gwei,
-                    }
-                })
-                .collect()
-        }).unwrap()
+        .fetch(executor)
+        .map_ok(|row| {
+            GweiInTime {
+                t: row.day_timestamp.duration_trunc(Duration::days(1)).unwrap().timestamp() as u64,
+                v: row.gwei,
+            }
+        })
+}
+
+// sqlstate postgres uses when a statement is cancelled, statement_timeout expiring included
+const QUERY_CANCELED_CODE: &str = "57014";
+
+#[derive(Debug, Error)]
+pub enum DailyAggregationError {
+    #[error("daily aggregation query did not finish within {0:?}")]
+    Timeout(std::time::Duration),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+impl DailyAggregationError {
+    fn from_query_error(
+        error: sqlx::Error,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let is_canceled = error
+            .as_database_error()
+            .and_then(|database_error| database_error.code())
+            .is_some_and(|code| code == QUERY_CANCELED_CODE);
+        if is_canceled {
+            Self::Timeout(timeout)
+        } else {
+            Self::Database(error)
+        }
+    }
+}
+
+// same as get_validator_balances_by_start_of_day, but the DISTINCT ON (DATE_TRUNC(..)) scan
+// gets at most `timeout` to run, on a large table a server request then fails instead of hanging.
+pub async fn get_validator_balances_by_start_of_day_with_timeout(
+    connection: &mut PgConnection,
+    timeout: std::time::Duration,
+) -> Result<Vec<GweiInTime>, DailyAggregationError> {
+    with_statement_timeout(connection, timeout, |transaction| {
+        query_validator_balances_by_start_of_day(transaction).boxed()
+    })
+    .await
+}
+
+// run `query` in a transaction in which statements get at most `timeout` to run. the timeout
+// is set with SET LOCAL so it ends with the transaction and leaves the connection as it was.
+async fn with_statement_timeout<T>(
+    connection: &mut PgConnection,
+    timeout: std::time::Duration,
+    query: impl for<'c> FnOnce(
+        &'c mut PgConnection,
+    ) -> BoxFuture<'c, Result<T, sqlx::Error>>,
+) -> Result<T, DailyAggregationError> {
+    let mut transaction = connection.begin().await?;
+
+    // SET LOCAL takes no bind parameters, set_config(.., true) is its parameterized equivalent
+    sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+        .bind(format!("{}ms", timeout.as_millis()))
+        .execute(&mut *transaction)
+        .await?;
+
+    let result = query(&mut transaction).await.map_err(|error| {
+        DailyAggregationError::from_query_error(error, timeout)
+    })?;
+
+    transaction.commit().await?;
+    Ok(result)
 }
 
 // function deletes multiple records in beacon_validators_balance table
@@ -128,6 +215,28 @@ pub async fn delete_validator_sums(
     .unwrap();
 }
 
+// delete validator sums of all slots in [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_validator_sums_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM beacon_validators_balance
+        WHERE state_root IN (
+            SELECT state_root FROM beacon_states
+            WHERE slot >= $1 AND slot <= $2
+        )
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 // function deletes multiple records in beacon_validators_balance table with the same given slot value
 // however, slot value does not exist in table so we need to first
 // query block_states table by given slot value
@@ -218,6 +327,130 @@ mod tests {
         assert_eq!(datetime, start_of_day_datetime)
     }
 
+    #[tokio::test]
+    async fn daily_aggregation_times_out_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let timeout = std::time::Duration::from_millis(10);
+
+        // sleeping well past the timeout stands in for a slow aggregation
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            with_statement_timeout(&mut connection, timeout, |transaction| {
+                sqlx::query("SELECT pg_sleep(1)")
+                    .execute(transaction)
+                    .map(|result| result.map(|_| ()))
+                    .boxed()
+            }),
+        )
+        .await
+        .expect("aggregation should not block indefinitely");
+
+        assert!(
+            matches!(result, Err(DailyAggregationError::Timeout(t)) if t == timeout)
+        );
+
+        // the timeout was transaction local, the connection can aggregate again
+        let balances = get_validator_balances_by_start_of_day_with_timeout(
+            &mut connection,
+            std::time::Duration::from_secs(5),
+        )
+        .await;
+        assert!(balances.is_ok());
+    }
+
+    #[tokio::test]
+    async fn start_of_day_is_utc_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // day boundaries should not follow the session time zone
+        sqlx::query("SET LOCAL TIME ZONE 'America/New_York'")
+            .execute(&mut *transaction)
+            .await
+            .unwrap();
+
+        // the last slot before and the first slot after a UTC midnight, far ahead of synced slots
+        let midnight = Utc.with_ymd_and_hms(2157, 1, 1, 0, 0, 0).unwrap();
+        let seconds_since_genesis =
+            (midnight - crate::beacon_chain::genesis_timestamp()).num_seconds();
+        let slot_before_midnight = Slot((seconds_since_genesis / 12) as i32);
+        let slot_after_midnight = slot_before_midnight + 1;
+
+        for (state_root, slot, gwei) in [
+            ("0xbefore_utc_midnight", slot_before_midnight, 1),
+            ("0xafter_utc_midnight", slot_after_midnight, 2),
+        ] {
+            store_state(&mut *transaction, state_root, slot).await;
+            store_validators_balance(
+                &mut *transaction,
+                state_root,
+                slot,
+                &GweiNewtype(gwei),
+            )
+            .await;
+        }
+
+        let validator_balances_by_day =
+            get_validator_balances_by_start_of_day(&mut *transaction).await;
+
+        let gwei_on_day = |day: DateTime<Utc>| {
+            validator_balances_by_day
+                .iter()
+                .find(|gwei_in_time| gwei_in_time.t == day.timestamp() as u64)
+                .map(|gwei_in_time| gwei_in_time.v)
+        };
+        assert_eq!(gwei_on_day(midnight - Duration::days(1)), Some(1));
+        assert_eq!(gwei_on_day(midnight), Some(2));
+    }
+
+    #[tokio::test]
+    async fn one_balance_per_day_from_earliest_slot_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        // two slots on either side of the 2150-01-01 UTC midnight
+        let last_day = Utc.with_ymd_and_hms(2149, 12, 31, 0, 0, 0).unwrap();
+        let first_day = Utc.with_ymd_and_hms(2150, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            Slot(339454798).date_time(),
+            Utc.with_ymd_and_hms(2149, 12, 31, 23, 59, 59).unwrap()
+        );
+        assert_eq!(
+            Slot(339454799).date_time(),
+            Utc.with_ymd_and_hms(2150, 1, 1, 0, 0, 11).unwrap()
+        );
+
+        // stored latest first, so insertion order can't pick the earliest slot
+        for (slot, gwei) in [
+            (Slot(339454800), 4),
+            (Slot(339454799), 3),
+            (Slot(339454798), 2),
+            (Slot(339454797), 1),
+        ] {
+            let state_root = format!("0xone_balance_per_day_{}", slot.0);
+            store_state(&mut *transaction, &state_root, slot).await;
+            store_validators_balance(
+                &mut *transaction,
+                &state_root,
+                slot,
+                &GweiNewtype(gwei),
+            )
+            .await;
+        }
+
+        let validator_balances_by_day =
+            get_validator_balances_by_start_of_day(&mut *transaction).await;
+
+        let gwei_on_day = |day: DateTime<Utc>| {
+            validator_balances_by_day
+                .iter()
+                .filter(|gwei_in_time| gwei_in_time.t == day.timestamp() as u64)
+                .map(|gwei_in_time| gwei_in_time.v)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(gwei_on_day(last_day), vec![1]);
+        assert_eq!(gwei_on_day(first_day), vec![3]);
+    }
+
     // #[tokio::test]
     async fn delete_balance_test() {
         let mut connection = db::tests::get_test_db_connection().await;
@@ -19,7 +19,11 @@ pub async fn heal_block_hashes() {
     let job_tracker = JobProgress::new(HEAL_BLOCK_HASHES_KEY, &kv_store);
     let beacon_node = BeaconNodeHttp::new();
     // fetch the first slot value from job tracker, if fetch nothing use FIRST_POST_MERGE_SLOT instead
-    let first_slot = job_tracker.get().await.unwrap_or(FIRST_POST_MERGE_SLOT);
+    let first_slot = job_tracker
+        .get()
+        .await
+        .expect("expect heal block hashes progress to be a valid slot")
+        .unwrap_or(FIRST_POST_MERGE_SLOT);
 
     let work_todo = sqlx::query!(
         r#"
@@ -95,7 +99,7 @@ pub async fn heal_block_hashes() {
 
         if slot % 100 == 0 {
             info!("{}", progress.get_progress_string());
-            job_tracker.set(&slot.into()).await;
+            job_tracker.set(&slot.into()).await.unwrap();
         }
     }
 
@@ -1,10 +1,14 @@
 ///! handles storage and retrieval of beacon blocks in our DB.
 pub mod heal;
+use crate::execution_chain::BlockNumber;
 use crate::units::GweiNewtype;
 use sqlx::{PgExecutor, Row};
 
 use super::{
-    node::{BeaconBlock, BeaconHeader, BeaconHeaderSignedEnvelope, BeaconNode},
+    node::{
+        BeaconBlock, BeaconBlockBody, BeaconHeader, BeaconHeaderSignedEnvelope,
+        BeaconNode, ExecutionPayload,
+    },
     Slot,
 };
 
@@ -92,8 +96,28 @@ pub async fn store_block(
     withdrawal_sum_aggregated: &GweiNewtype,
     header: &BeaconHeaderSignedEnvelope,
 ) {
+    let withdrawals = block.withdrawals().map(Vec::as_slice).unwrap_or_default();
+    let (indices, (addresses, amounts)): (Vec<i64>, (Vec<String>, Vec<i64>)) =
+        withdrawals
+            .iter()
+            .map(|withdrawal| {
+                (
+                    i64::from(withdrawal.index),
+                    (withdrawal.address.clone(), i64::from(withdrawal.amount)),
+                )
+            })
+            .unzip();
+
+    // the withdrawals are stored in the same statement as the block, so a block
+    // never lands without its per-address withdrawal records
     sqlx::query!(
         "
+        WITH stored_withdrawals AS (
+            INSERT INTO beacon_withdrawals (index, slot, address, amount)
+            SELECT index, $10, address, amount
+            FROM UNNEST($11::INT8[], $12::TEXT[], $13::INT8[])
+                AS withdrawals (index, address, amount)
+        )
         INSERT INTO beacon_blocks (
             block_hash,
             block_root,
@@ -102,10 +126,12 @@ pub async fn store_block(
             withdrawal_sum,
             withdrawal_sum_aggregated,
             parent_root,
-            state_root
+            state_root,
+            block_number,
+            deposit_count
         )
         VALUES (
-            $1, $2, $3, $4, $5, $6, $7, $8
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $14
         )
         ",
         block.block_hash(),
@@ -116,6 +142,12 @@ pub async fn store_block(
         i64::from(withdrawal_sum_aggregated.to_owned()),
         header.parent_root(),
         header.state_root(),
+        block.block_number(),
+        block.slot.0,
+        &indices,
+        &addresses,
+        &amounts,
+        block.deposits().len() as i32,
     )
     .execute(executor)
     .await
@@ -147,6 +179,32 @@ pub async fn delete_blocks(
     .unwrap();
 }
 
+// delete the blocks of all slots in [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_blocks_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM beacon_blocks
+        WHERE state_root IN (
+            SELECT
+                state_root
+            FROM
+                beacon_states
+            WHERE beacon_states.slot >= $1
+            AND beacon_states.slot <= $2
+        )
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 // delete single block with state_root locates in the query result
 // that it's query result from query table beacon_states value slot value equal to query parameter
 pub async fn delete_block(executor: impl PgExecutor<'_>, slot: Slot) {
@@ -175,15 +233,43 @@ pub struct DbBlock {
     deposit_sum_aggregated: GweiNewtype,
     parent_root: String,
     pub block_hash: Option<String>,
+    pub block_number: Option<BlockNumber>,
+    pub slot: Slot,
     pub state_root: String,
 }
 
+impl DbBlock {
+    // rebuild the parts of the BeaconBlock we keep in beacon_blocks, so reconciliation jobs
+    // can re-process a stored block. deposits and withdrawals are not stored per block,
+    // callers that need them have to fetch them separately from the beacon node
+    pub fn to_partial_beacon_block(&self) -> BeaconBlock {
+        BeaconBlock {
+            body: BeaconBlockBody {
+                deposits: vec![],
+                // blocks stored before block numbers were kept come back without one
+                execution_payload: self.block_hash.as_ref().map(|block_hash| {
+                    ExecutionPayload {
+                        block_hash: block_hash.clone(),
+                        block_number: self.block_number,
+                        withdrawals: None,
+                    }
+                }),
+            },
+            parent_root: self.parent_root.clone(),
+            slot: self.slot,
+            state_root: self.state_root.clone(),
+        }
+    }
+}
+
 struct BlockDbRow {
     block_root: String,
     deposit_sum: i64,
     deposit_sum_aggregated: i64,
     parent_root: String,
     pub block_hash: Option<String>,
+    pub block_number: Option<i32>,
+    pub slot: i32,
     pub state_root: String,
 }
 
@@ -192,20 +278,23 @@ impl From<BlockDbRow> for DbBlock {
     fn from(value: BlockDbRow) -> Self {
         Self {
             block_hash: value.block_hash,
+            block_number: value.block_number,
             block_root: value.block_root,
             deposit_sum: value.deposit_sum.into(),
             deposit_sum_aggregated: value.deposit_sum_aggregated.into(),
             parent_root: value.parent_root,
+            slot: value.slot.into(),
             state_root: value.state_root,
         }
     }
 }
 
 // get a series of blocks which each slot value <= given query slots value
+// the last stored block before the given slot, None when there is none, e.g. on a fresh db
 pub async fn get_block_before_slot(
     executor: impl PgExecutor<'_>,
     less_than: Slot,
-) -> DbBlock {
+) -> Option<DbBlock> {
     sqlx::query_as!(
         BlockDbRow,
         "
@@ -215,7 +304,9 @@ pub async fn get_block_before_slot(
             parent_root,
             deposit_sum,
             deposit_sum_aggregated,
-            block_hash
+            block_hash,
+            block_number,
+            beacon_states.slot
         FROM
             beacon_blocks
         JOIN
@@ -226,10 +317,10 @@ pub async fn get_block_before_slot(
         ",
         less_than.0
     )
-    .fetch_one(executor)
+    .fetch_optional(executor)
     .await
     .unwrap()
-    .into()
+    .map(Into::into)
 }
 
 // update field of block_hash value in table beacon_blocks
@@ -266,7 +357,9 @@ pub async fn get_block_by_slot(
             parent_root,
             deposit_sum,
             deposit_sum_aggregated,
-            block_hash
+            block_hash,
+            block_number,
+            beacon_states.slot
         FROM
             beacon_blocks
         JOIN beacon_states ON
@@ -282,6 +375,55 @@ pub async fn get_block_by_slot(
     .map(|row| row.into())
 }
 
+// the slot of the beacon block whose execution payload has the given block number
+pub async fn get_slot_by_block_number(
+    executor: impl PgExecutor<'_>,
+    block_number: BlockNumber,
+) -> Option<Slot> {
+    sqlx::query!(
+        r#"
+        SELECT
+            beacon_states.slot AS "slot: Slot"
+        FROM
+            beacon_blocks
+        JOIN beacon_states ON
+            beacon_blocks.state_root = beacon_states.state_root
+        WHERE
+            block_number = $1
+        "#,
+        block_number
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.slot)
+}
+
+// the execution block number of the beacon block at the given slot, None for slots without a
+// block, blocks from before the merge and blocks stored before block numbers were kept
+pub async fn get_block_number_by_slot(
+    executor: impl PgExecutor<'_>,
+    slot: Slot,
+) -> Option<BlockNumber> {
+    sqlx::query!(
+        "
+        SELECT
+            block_number
+        FROM
+            beacon_blocks
+        JOIN beacon_states ON
+            beacon_blocks.state_root = beacon_states.state_root
+        WHERE
+            slot = $1
+        ",
+        slot.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .and_then(|row| row.block_number)
+}
+
 #[cfg(test)]
 mod tests {
     use db::db::tests;
@@ -292,6 +434,7 @@ mod tests {
     use crate::beacon_chain::tests::store_test_block;
     use crate::{
         beacon_chain::node::{
+            mock_block::{BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder},
             BeaconBlockBody, BeaconHeader, BeaconHeaderEnvelope, BeaconNode,
             ExecutionPayload,
         },
@@ -435,12 +578,31 @@ mod tests {
     async fn get_block_before_slot_test() {
         let mut connection = tests::get_test_db_connection().await;
         let mut transaction = connection.begin().await.unwrap();
-        let test_id_before = "last_block_before_slot_before";
+        let slot_before = Slot(i32::MAX - 500);
+        let slot = Slot(i32::MAX - 499);
+
+        store_test_block(
+            &mut transaction,
+            "last_block_before_slot_before",
+            slot_before,
+        )
+        .await;
+        store_test_block(&mut transaction, "last_block_before_slot", slot)
+            .await;
+
+        let block = get_block_before_slot(&mut *transaction, slot)
+            .await
+            .unwrap();
+        assert_eq!(block.slot, slot_before);
     }
 
     #[tokio::test]
     async fn get_block_before_missing_slot_test() {
-        assert!(true)
+        let mut connection = tests::get_test_db_connection().await;
+
+        // no block can be stored before the lowest slot, whatever other tests store
+        let block = get_block_before_slot(&mut connection, Slot(i32::MIN)).await;
+        assert!(block.is_none());
     }
 
     #[tokio::test]
@@ -452,4 +614,137 @@ mod tests {
     async fn get_block_by_slot_test() {
         assert!(true)
     }
+
+    #[tokio::test]
+    async fn to_partial_beacon_block_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let state_root = "0xpartial_block_test_state_root".to_string();
+        let slot = Slot(77778);
+        let block = BeaconBlock {
+            body: BeaconBlockBody {
+                deposits: vec![],
+                execution_payload: Some(ExecutionPayload {
+                    block_hash: "0xpartial_block_test_block_hash".to_string(),
+                    block_number: Some(77778),
+                    withdrawals: None,
+                }),
+            },
+            parent_root: "0xpartial_block_test_parent_root".to_string(),
+            slot,
+            state_root: state_root.clone(),
+        };
+        store_state(&mut *transaction, &state_root, slot).await;
+        store_block(
+            &mut *transaction,
+            &block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &BeaconHeaderSignedEnvelope {
+                root: "0xpartial_block_test_block_root".to_string(),
+                header: BeaconHeaderEnvelope {
+                    message: BeaconHeader {
+                        slot,
+                        parent_root: block.parent_root.clone(),
+                        state_root: state_root.clone(),
+                    },
+                },
+            },
+        )
+        .await;
+
+        let db_block = get_block_by_slot(&mut *transaction, slot).await.unwrap();
+        let partial_block = db_block.to_partial_beacon_block();
+
+        assert_eq!(partial_block.parent_root, block.parent_root);
+        assert_eq!(partial_block.state_root, block.state_root);
+        assert_eq!(partial_block.slot, slot);
+        assert_eq!(partial_block.block_hash(), block.block_hash());
+        assert_eq!(partial_block.block_number(), block.block_number());
+    }
+
+    #[tokio::test]
+    async fn to_partial_beacon_block_without_block_number_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let slot = Slot(77780);
+        let header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "partial_block_without_block_number",
+            slot,
+        )
+        .build();
+        let block = BeaconBlockBuilder::from(&header)
+            .block_hash("0xpartial_block_without_block_number_block_hash")
+            .build();
+        store_state(&mut *transaction, &header.state_root(), slot).await;
+        store_block(
+            &mut *transaction,
+            &block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &header,
+        )
+        .await;
+        // the way rows stored before the block_number column was added look
+        sqlx::query!(
+            "
+            UPDATE beacon_blocks SET block_number = NULL WHERE state_root = $1
+            ",
+            header.state_root()
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        let db_block = get_block_by_slot(&mut *transaction, slot).await.unwrap();
+        let partial_block = db_block.to_partial_beacon_block();
+
+        assert_eq!(partial_block.block_hash(), block.block_hash());
+        assert_eq!(partial_block.block_number(), None);
+    }
+
+    #[tokio::test]
+    async fn slot_block_number_mapping_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let slot = Slot(77779);
+        let block_number = 17_034_870;
+        let header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "slot_block_number_mapping",
+            slot,
+        )
+        .build();
+        let block = BeaconBlockBuilder::from(&header)
+            .block_hash("0xslot_block_number_mapping_block_hash")
+            .block_number(block_number)
+            .build();
+        store_state(&mut *transaction, &header.state_root(), slot).await;
+        store_block(
+            &mut *transaction,
+            &block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &header,
+        )
+        .await;
+
+        assert_eq!(
+            get_slot_by_block_number(&mut *transaction, block_number).await,
+            Some(slot)
+        );
+        assert_eq!(
+            get_block_number_by_slot(&mut *transaction, slot).await,
+            Some(block_number)
+        );
+        assert_eq!(
+            get_slot_by_block_number(&mut *transaction, block_number + 1).await,
+            None
+        );
+    }
 }
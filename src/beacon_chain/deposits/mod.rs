@@ -14,6 +14,15 @@ pub fn get_deposit_sum_from_block(block: &BeaconBlock) -> GweiNewtype {
         .fold(GweiNewtype(0), |sum, deposit| sum + deposit.amount)
 }
 
+// the amount of each deposit in the block, in the order they were included
+pub fn get_deposits_from_block(block: &BeaconBlock) -> Vec<GweiNewtype> {
+    block
+        .deposits()
+        .iter()
+        .map(|deposit| deposit.amount)
+        .collect()
+}
+
 /// Computes the aggregated sum of deposit amounts for a given beacon block.
 ///
 /// - If the block is the genesis block, returns `0` since it has no associated deposits.
@@ -30,6 +39,7 @@ pub async fn get_deposit_sum_aggregated(
     block: &BeaconBlock,
 ) -> GweiNewtype {
     let parent_deposit_sum_aggregated = if block.slot == Slot::GENESIS {
+        // genesis has no parent to query, its own deposits are still added below
         GweiNewtype(0)
     } else {
         blocks::get_deposit_sum_from_block_root(executor, &block.parent_root)
@@ -68,6 +78,31 @@ pub async fn get_deposits_sum_by_state_root(
     Ok(deposit_sum_aggregated)
 }
 
+// number of deposits included in the block at the given slot, zero when the slot has no block.
+// blocks stored before deposit counts were tracked also report zero.
+pub async fn get_deposit_count_by_slot(
+    executor: impl PgExecutor<'_>,
+    slot: Slot,
+) -> i64 {
+    sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(beacon_blocks.deposit_count), 0)::INT8 AS "deposit_count!"
+        FROM
+            beacon_blocks
+        JOIN beacon_states ON
+            beacon_states.state_root = beacon_blocks.state_root
+        WHERE
+            beacon_states.slot = $1
+        "#,
+        slot.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .deposit_count
+}
+
 #[cfg(test)]
 mod tests {
     use sqlx::Acquire;
@@ -121,4 +156,60 @@ mod tests {
 
         assert_eq!(GweiNewtype(1), deposits_sum)
     }
+
+    #[tokio::test]
+    async fn get_deposit_count_by_slot_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let test_id = "get_deposit_count_by_slot";
+        let test_header =
+            BeaconHeaderSignedEnvelopeBuilder::new(test_id, Slot(223)).build();
+        let test_block = Into::<BeaconBlockBuilder>::into(&test_header)
+            .deposits(vec![GweiNewtype(1), GweiNewtype(2), GweiNewtype(3)])
+            .build();
+        assert_eq!(
+            get_deposits_from_block(&test_block),
+            vec![GweiNewtype(1), GweiNewtype(2), GweiNewtype(3)]
+        );
+
+        store_state(
+            &mut *transaction,
+            &test_header.state_root(),
+            test_header.slot(),
+        )
+        .await;
+        store_block(
+            &mut *transaction,
+            &test_block,
+            &get_deposit_sum_from_block(&test_block),
+            &get_deposit_sum_from_block(&test_block),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &test_header,
+        )
+        .await;
+
+        let deposit_count =
+            get_deposit_count_by_slot(&mut *transaction, test_header.slot())
+                .await;
+        assert_eq!(deposit_count, 3);
+    }
+
+    #[tokio::test]
+    async fn get_deposit_sum_aggregated_genesis_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let test_header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "deposit_sum_aggregated_genesis",
+            Slot::GENESIS,
+        )
+        .build();
+        let test_block = Into::<BeaconBlockBuilder>::into(&test_header)
+            .deposits(vec![GweiNewtype(32), GweiNewtype(16)])
+            .build();
+
+        let deposit_sum_aggregated =
+            get_deposit_sum_aggregated(&mut *transaction, &test_block).await;
+        assert_eq!(deposit_sum_aggregated, GweiNewtype(48));
+    }
 }
@@ -2,10 +2,12 @@ use crate::beacon_chain::node::Withdrawal;
 use crate::beacon_chain::slots;
 use crate::beacon_chain::slots::Slot;
 use crate::beacon_chain::states::get_last_state;
+use crate::caching::{self, CacheKey};
 use crate::{db::db, units::GweiNewtype};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use futures::join;
+use mockall::automock;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::types::PgInterval, PgExecutor, PgPool};
 use thiserror::Error;
@@ -35,15 +37,78 @@ pub async fn store_issuance(
     .unwrap();
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum IssuanceArithmeticError {
+    #[error(
+        "balances {} plus withdrawals {} overflow",
+        .balances.display_eth(),
+        .withdrawals.display_eth()
+    )]
+    Overflow {
+        balances: GweiNewtype,
+        withdrawals: GweiNewtype,
+    },
+    #[error(
+        "balances plus withdrawals {} are less than deposits {}",
+        .balances_and_withdrawals.display_eth(),
+        .deposits.display_eth()
+    )]
+    Negative {
+        balances_and_withdrawals: GweiNewtype,
+        deposits: GweiNewtype,
+    },
+}
+
 // calculate the value of issuance
 // issuance = validator_balances_sum_gwei + withdrawal_sum_aggregated - deposit_sum_aggregated
+// inconsistent sums could push the result out of range or below zero, in that case we return an
+// error instead of panicking or storing a negative issuance
 pub fn calc_issuance(
     validator_balances_sum_gwei: &GweiNewtype,
     withdrawal_sum_aggregated: &GweiNewtype,
     deposit_sum_aggregated: &GweiNewtype,
-) -> GweiNewtype {
-    (*validator_balances_sum_gwei + *withdrawal_sum_aggregated)
-        - *deposit_sum_aggregated
+) -> Result<GweiNewtype, IssuanceArithmeticError> {
+    let balances_and_withdrawals = validator_balances_sum_gwei
+        .checked_add(*withdrawal_sum_aggregated)
+        .ok_or(IssuanceArithmeticError::Overflow {
+            balances: *validator_balances_sum_gwei,
+            withdrawals: *withdrawal_sum_aggregated,
+        })?;
+
+    balances_and_withdrawals
+        .checked_sub(*deposit_sum_aggregated)
+        .filter(|issuance| issuance.0 >= 0)
+        .ok_or(IssuanceArithmeticError::Negative {
+            balances_and_withdrawals,
+            deposits: *deposit_sum_aggregated,
+        })
+}
+
+// get the issuance of the closest slot before the given one that has an issuance stored
+pub async fn get_issuance_before_slot(
+    executor: impl PgExecutor<'_>,
+    slot: Slot,
+) -> Option<GweiNewtype> {
+    sqlx::query!(
+        "
+            SELECT
+                beacon_issuance.gwei
+            FROM
+                beacon_issuance
+            JOIN beacon_states ON
+                beacon_issuance.state_root = beacon_states.state_root
+            WHERE
+                beacon_states.slot < $1
+            ORDER BY
+                beacon_states.slot DESC
+            LIMIT 1
+        ",
+        slot.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| GweiNewtype(row.gwei))
 }
 
 // get the latest(freshest) issuance gwei value from table beacon_issuance
@@ -92,6 +157,28 @@ pub async fn delete_issuances(
     .unwrap();
 }
 
+// delete issuance records of all slots in [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_issuances_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+            DELETE FROM beacon_issuance
+            WHERE state_root IN (
+                SELECT state_root FROM beacon_states
+                WHERE slot >= $1 AND slot <= $2
+            )
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 // delete records in beacon_issuance table by match with only one slot value
 pub async fn delete_issuance(executor: impl PgExecutor<'_>, slot: Slot) {
     sqlx::query!(
@@ -138,7 +225,7 @@ pub async fn get_n_days_ago_issuance(
             LIMIT 1
         ",
         PgInterval {
-            days: 0,
+            days: n,
             microseconds: 0,
             months: 0,
         }
@@ -156,6 +243,7 @@ pub enum IssuanceUnavailableError {
 }
 
 // here we define a series of beacon_issuances table operations
+#[automock]
 #[async_trait]
 pub trait IssuanceStore {
     async fn current_issuance(&self) -> GweiNewtype;
@@ -171,6 +259,14 @@ pub trait IssuanceStore {
         // time_frame: &TimeFrame,
     ) -> Result<GweiNewtype, IssuanceUnavailableError>;
     async fn weekly_issuance(&self) -> GweiNewtype;
+
+    // issuance over the last `days` days
+    async fn issuance_over_days(&self, days: i32) -> GweiNewtype {
+        let (n_days_ago_issuance, current_issuance) =
+            join!(self.n_days_ago_issuance(days), self.current_issuance());
+
+        current_issuance - n_days_ago_issuance
+    }
 }
 
 pub struct IssuanceStoragePostgres {
@@ -225,10 +321,7 @@ impl IssuanceStore for IssuanceStoragePostgres {
 
     /// weekly issuance in Gwei
     async fn weekly_issuance(&self) -> GweiNewtype {
-        let (d14_issuance, now_issuance) =
-            join!(self.n_days_ago_issuance(14), self.current_issuance());
-
-        GweiNewtype((now_issuance - d14_issuance).0 / 2)
+        self.issuance_over_days(7).await
     }
 }
 
@@ -242,7 +335,7 @@ const SLOTS_PER_WEEK: f64 =
         as f64;
 
 #[derive(Debug, Serialize)]
-struct IssuanceEstimate {
+pub struct IssuanceEstimate {
     slot: Slot,
     timestamp: DateTime<Utc>,
     issuance_per_slot_gwei: f64,
@@ -258,41 +351,287 @@ async fn get_issuance_per_slot_estimate(
     last_week_issuance.0 as f64 / SLOTS_PER_WEEK
 }
 
-// this is also the main entry point of issuance estimate service
-// and this main entry function will be invoked in update-issuance-estimate.ts (not implemented yet)
-// also the calculated final result will be updated to the project cache store(not implemented yet)
-pub async fn update_issuance_estimate() {
-    info!("updating issuance estimate");
-    // create db connection pool instance with max connection = 3, and pool name as 'update-issuance-estimate'
-    let db_pool = db::get_db_pool("update-issuance-estimate", 3).await;
-    let issuance_store = IssuanceStoragePostgres::new(db_pool.clone());
-
+// compute the issuance estimate for the last stored slot without publishing it anywhere
+pub async fn compute_issuance_estimate(
+    executor: impl PgExecutor<'_>,
+    issuance_store: &impl IssuanceStore,
+) -> IssuanceEstimate {
     // get how many issuances in gwei per slot
     let issuance_per_slot_gwei =
-        get_issuance_per_slot_estimate(&issuance_store).await;
+        get_issuance_per_slot_estimate(issuance_store).await;
     debug!("issuance per slots estimate: {}", issuance_per_slot_gwei);
 
     // here we get the freshest/latest state_root from the beacon_states table
-    let slot = get_last_state(&db_pool)
+    let slot = get_last_state(executor)
         .await
         .expect(
             "expect last state to exist in order to update issuance estimate",
         )
         .slot;
 
-    // get the timestamp value of the beacon_states' latest record
-    let timestamp = slot.date_time();
-
     // create instance of struct IssuanceEstimate value by passing the values of
     // slot value, latest beacon_state's ts, and the estimateed issuance per slot value in the unit of Gwei
-    let issuance_estimate = IssuanceEstimate {
+    IssuanceEstimate {
         slot,
-        timestamp,
+        timestamp: slot.date_time(),
         issuance_per_slot_gwei,
-    };
+    }
+}
+
+// this is also the main entry point of issuance estimate service
+// and this main entry function will be invoked in update-issuance-estimate.ts (not implemented yet)
+// the estimate is published to the cache, failing when it can't be serialized
+pub async fn update_issuance_estimate() -> Result<(), serde_json::Error> {
+    info!("updating issuance estimate");
+    // create db connection pool instance with max connection = 3, and pool name as 'update-issuance-estimate'
+    let db_pool = db::get_db_pool("update-issuance-estimate", 3).await;
+    let issuance_store = IssuanceStoragePostgres::new(db_pool.clone());
+
+    let issuance_estimate =
+        compute_issuance_estimate(&db_pool, &issuance_store).await;
+    debug!(?issuance_estimate, "computed issuance estimate");
 
     // finally publish the aggregated value struct instance to cache to let frontend request to fetch
-    // but cache we haven't implment yet , just add a todo!() and print the value for now is ok
-    todo!("publish the calculated issuance estimate value to the cache");
-    info!("updated issuance estimate")
+    caching::update_and_publish(
+        &db_pool,
+        &CacheKey::IssuanceEstimate,
+        issuance_estimate,
+    )
+    .await?;
+    info!("updated issuance estimate");
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct IssuanceBreakdown {
+    pub slot: Slot,
+    pub timestamp: DateTime<Utc>,
+    pub issuance_gwei: GweiNewtype,
+    pub withdrawals_gwei: GweiNewtype,
+    pub deposits_gwei: GweiNewtype,
+}
+
+// break the issuance of the latest block that has one stored down into what went into it,
+// the withdrawals and deposits are the block's aggregates since genesis.
+// returns None while no block with an issuance has been synced
+pub async fn compute_issuance_breakdown(
+    executor: impl PgExecutor<'_>,
+) -> Option<IssuanceBreakdown> {
+    sqlx::query!(
+        "
+            SELECT
+                beacon_states.slot,
+                beacon_issuance.gwei AS issuance_gwei,
+                beacon_blocks.deposit_sum_aggregated,
+                beacon_blocks.withdrawal_sum_aggregated
+            FROM
+                beacon_blocks
+            JOIN beacon_states ON
+                beacon_blocks.state_root = beacon_states.state_root
+            JOIN beacon_issuance ON
+                beacon_blocks.state_root = beacon_issuance.state_root
+            ORDER BY
+                beacon_states.slot DESC
+            LIMIT 1
+        "
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| {
+        let slot = Slot(row.slot);
+        IssuanceBreakdown {
+            slot,
+            timestamp: slot.date_time(),
+            issuance_gwei: GweiNewtype(row.issuance_gwei),
+            // blocks from before withdrawals were enabled have none stored
+            withdrawals_gwei: GweiNewtype(
+                row.withdrawal_sum_aggregated.unwrap_or(0),
+            ),
+            deposits_gwei: GweiNewtype(row.deposit_sum_aggregated),
+        }
+    })
+}
+
+pub async fn update_issuance_breakdown(
+    db_pool: &PgPool,
+) -> Result<(), serde_json::Error> {
+    match compute_issuance_breakdown(db_pool).await {
+        Some(issuance_breakdown) => {
+            debug!(?issuance_breakdown, "publishing issuance breakdown");
+            caching::update_and_publish(
+                db_pool,
+                &CacheKey::IssuanceBreakdown,
+                issuance_breakdown,
+            )
+            .await?;
+        }
+        None => {
+            debug!("no block with an issuance stored yet, skipping breakdown")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::states;
+    use crate::db::db::tests::TestDb;
+
+    #[test]
+    fn calc_issuance_test() {
+        let issuance = calc_issuance(
+            &GweiNewtype(100),
+            &GweiNewtype(20),
+            &GweiNewtype(50),
+        );
+        assert_eq!(issuance, Ok(GweiNewtype(70)));
+    }
+
+    #[test]
+    fn calc_issuance_negative_test() {
+        let issuance = calc_issuance(
+            &GweiNewtype(10),
+            &GweiNewtype(5),
+            &GweiNewtype(20),
+        );
+        assert_eq!(
+            issuance,
+            Err(IssuanceArithmeticError::Negative {
+                balances_and_withdrawals: GweiNewtype(15),
+                deposits: GweiNewtype(20),
+            })
+        );
+    }
+
+    #[test]
+    fn calc_issuance_overflow_test() {
+        let issuance = calc_issuance(
+            &GweiNewtype(i64::MAX),
+            &GweiNewtype(1),
+            &GweiNewtype(0),
+        );
+        assert!(matches!(
+            issuance,
+            Err(IssuanceArithmeticError::Overflow { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn compute_issuance_estimate_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        let slot = Slot(i32::MAX - 400);
+        states::store_state(&mut *transaction, "0xissuance_estimate_test", slot)
+            .await;
+        // the estimate only needs the weekly issuance
+        let mut issuance_store = MockIssuanceStore::new();
+        issuance_store
+            .expect_weekly_issuance()
+            .returning(|| GweiNewtype(10_000_000_000_000));
+
+        let issuance_estimate =
+            compute_issuance_estimate(&mut *transaction, &issuance_store).await;
+
+        assert_eq!(issuance_estimate.slot, slot);
+        assert!(issuance_estimate.issuance_per_slot_gwei > 0.0);
+    }
+
+    #[tokio::test]
+    async fn compute_issuance_breakdown_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        let slot = Slot(i32::MAX - 1700);
+        let state_root = "0xissuance_breakdown_test";
+        states::store_state(&mut *transaction, state_root, slot).await;
+        sqlx::query!(
+            "
+            INSERT INTO beacon_blocks (
+                block_root,
+                state_root,
+                parent_root,
+                deposit_sum,
+                deposit_sum_aggregated,
+                withdrawal_sum,
+                withdrawal_sum_aggregated
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ",
+            "0xissuance_breakdown_block_root",
+            state_root,
+            "0xissuance_breakdown_parent_root",
+            0,
+            64_000_000_000,
+            0,
+            12_000_000_000
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+        store_issuance(
+            &mut *transaction,
+            state_root,
+            slot,
+            &GweiNewtype(5_000_000_000),
+        )
+        .await;
+
+        let issuance_breakdown =
+            compute_issuance_breakdown(&mut *transaction).await.unwrap();
+
+        assert_eq!(
+            issuance_breakdown,
+            IssuanceBreakdown {
+                slot,
+                timestamp: slot.date_time(),
+                issuance_gwei: GweiNewtype(5_000_000_000),
+                withdrawals_gwei: GweiNewtype(12_000_000_000),
+                deposits_gwei: GweiNewtype(64_000_000_000),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn issuance_over_days_test() {
+        let test_db = TestDb::new().await;
+        let now = Utc::now();
+        let seeded_issuances = [(0, 1_000), (1, 900), (7, 300)];
+        for (days_ago, gwei) in seeded_issuances {
+            let slot = Slot::from_date_time_rounded_down(
+                &(now - chrono::Duration::days(days_ago)),
+            );
+            let state_root = format!("0xissuance_over_days_{days_ago}");
+            states::store_state(&test_db.pool, &state_root, slot).await;
+            store_issuance(&test_db.pool, &state_root, slot, &GweiNewtype(gwei))
+                .await;
+        }
+        let issuance_store = IssuanceStoragePostgres::new(test_db.pool.clone());
+
+        let one_day_issuance = issuance_store.issuance_over_days(1).await;
+        let weekly_issuance = issuance_store.weekly_issuance().await;
+
+        for (days_ago, _) in seeded_issuances {
+            let state_root = format!("0xissuance_over_days_{days_ago}");
+            sqlx::query!(
+                "DELETE FROM beacon_issuance WHERE state_root = $1",
+                state_root
+            )
+            .execute(&test_db.pool)
+            .await
+            .unwrap();
+            sqlx::query!(
+                "DELETE FROM beacon_states WHERE state_root = $1",
+                state_root
+            )
+            .execute(&test_db.pool)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(one_day_issuance, GweiNewtype(100));
+        assert_eq!(weekly_issuance, GweiNewtype(700));
+    }
 }
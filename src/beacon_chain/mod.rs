@@ -9,33 +9,84 @@ mod syncer;
 mod withdrawals;
 
 
+pub use balances::get_effective_balance_sum_for_epoch;
 pub use balances::backfill;
+pub use balances::backfill_effective_balance_sums;
+pub use balances::{
+    get_validator_balances_by_start_of_day_with_timeout,
+    stream_validator_balances_by_start_of_day, DailyAggregationError,
+};
+pub use blocks::{get_block_number_by_slot, get_slot_by_block_number};
+pub use deposits::{get_deposit_count_by_slot, get_deposits_from_block};
+pub use issuance::{compute_issuance_estimate, IssuanceStoragePostgres};
 pub use states::heal_beacon_states;
+pub use states::{get_state_roots_in_range, store_state};
+pub use syncer::{bench_sync, SyncBenchReport};
 pub use syncer::sync_beacon_states;
 pub use syncer::sync_beacon_states_to_local;
+pub use syncer::rollback_slot_range;
+pub use syncer::sync_historic_range;
+pub use syncer::update_deferrable_analysis;
+pub use syncer::{slots_only, stream_slots_from_last, StreamItem};
 
 
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub use node::mock_block::{
     BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder,
 };
 
-pub use node::mock_beacon_node::MockBeaconHttpNode;
-pub use slots::{slot_from_string, Slot, };
+pub use node::mock_beacon_node::{validate_fixtures, FixtureError, MockBeaconHttpNode};
+pub use node::{BeaconNode, BeaconNodeHttp, MockBeaconNode, StateRoot};
+pub use slots::{slot_from_string, Slot, SlotRange};
+pub use withdrawals::get_withdrawals_sum_by_address;
 
 lazy_static! {
-    pub static ref GENESIS_TIMESTAMP: DateTime<Utc> =
-        "2020-12-01T12:00:23Z".parse().unwrap();
     pub static ref SHAPELLA_SLOT: Slot = Slot(6209536);
 }
 
+// the genesis timestamp slots are counted from
+#[cfg(not(test))]
+pub fn genesis_timestamp() -> DateTime<Utc> {
+    crate::env::ENV_CONFIG.genesis_timestamp
+}
+
+#[cfg(test)]
+pub use genesis_override::{genesis_timestamp, set_genesis_timestamp_override};
+
+// tests can swap the genesis timestamp for the current thread with
+// `set_genesis_timestamp_override`, without affecting tests running in parallel
+#[cfg(test)]
+mod genesis_override {
+    use chrono::{DateTime, Utc};
+    use std::cell::Cell;
+
+    use crate::env::ENV_CONFIG;
+
+    thread_local! {
+        static GENESIS_TIMESTAMP_OVERRIDE: Cell<Option<DateTime<Utc>>> =
+            const { Cell::new(None) };
+    }
+
+    pub fn genesis_timestamp() -> DateTime<Utc> {
+        GENESIS_TIMESTAMP_OVERRIDE
+            .get()
+            .unwrap_or(ENV_CONFIG.genesis_timestamp)
+    }
+
+    pub fn set_genesis_timestamp_override(
+        genesis_timestamp: Option<DateTime<Utc>>,
+    ) {
+        GENESIS_TIMESTAMP_OVERRIDE.set(genesis_timestamp);
+    }
+}
+
 pub const FIRST_POST_MERGE_SLOT: Slot = Slot(4700013);
 pub const FIRST_POST_LONDON_SLOT: Slot = Slot(1778566);
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct GweiInTime {
     pub t: u64,
     pub v: i64,
@@ -52,7 +103,10 @@ impl From<(DateTime<Utc>, i64)> for GweiInTime {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::beacon_chain::blocks::store_block;
+    use crate::beacon_chain::blocks::{
+        get_deposit_sum_from_block_root, get_withdrawal_sum_from_block_root,
+        store_block,
+    };
     use crate::beacon_chain::node::{
         mock_block::{BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder},
         BeaconBlock, BeaconHeaderSignedEnvelope,
@@ -73,10 +127,44 @@ pub mod tests {
         store_custom_test_block(executor, &header, &block).await
     }
 
+    // the deposit and withdrawal sums stored alongside a test block, all zero by default
+    pub struct TestBlockSums {
+        pub deposit_sum: GweiNewtype,
+        pub deposit_sum_aggregated: GweiNewtype,
+        pub withdrawal_sum: GweiNewtype,
+        pub withdrawal_sum_aggregated: GweiNewtype,
+    }
+
+    impl Default for TestBlockSums {
+        fn default() -> Self {
+            Self {
+                deposit_sum: GweiNewtype(0),
+                deposit_sum_aggregated: GweiNewtype(0),
+                withdrawal_sum: GweiNewtype(0),
+                withdrawal_sum_aggregated: GweiNewtype(0),
+            }
+        }
+    }
+
     pub async fn store_custom_test_block(
         executor: &mut PgConnection,
         header: &BeaconHeaderSignedEnvelope,
         block: &BeaconBlock,
+    ) {
+        store_custom_test_block_with_sums(
+            executor,
+            header,
+            block,
+            &TestBlockSums::default(),
+        )
+        .await
+    }
+
+    pub async fn store_custom_test_block_with_sums(
+        executor: &mut PgConnection,
+        header: &BeaconHeaderSignedEnvelope,
+        block: &BeaconBlock,
+        sums: &TestBlockSums,
     ) {
         store_state(
             executor.acquire().await.unwrap(),
@@ -88,12 +176,46 @@ pub mod tests {
         store_block(
             executor,
             block,
-            &GweiNewtype(0),
-            &GweiNewtype(0),
-            &GweiNewtype(0),
-            &GweiNewtype(0),
+            &sums.deposit_sum,
+            &sums.deposit_sum_aggregated,
+            &sums.withdrawal_sum,
+            &sums.withdrawal_sum_aggregated,
             header,
         )
         .await
     }
+
+    #[tokio::test]
+    async fn store_custom_test_block_with_sums_test() {
+        let mut connection = crate::db::db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "store_custom_test_block_with_sums",
+            Slot(224),
+        )
+        .build();
+        let block = Into::<BeaconBlockBuilder>::into(&header).build();
+
+        store_custom_test_block_with_sums(
+            &mut transaction,
+            &header,
+            &block,
+            &TestBlockSums {
+                deposit_sum: GweiNewtype(1),
+                deposit_sum_aggregated: GweiNewtype(10),
+                withdrawal_sum: GweiNewtype(2),
+                withdrawal_sum_aggregated: GweiNewtype(20),
+            },
+        )
+        .await;
+
+        let deposit_sum_aggregated =
+            get_deposit_sum_from_block_root(&mut *transaction, &header.root)
+                .await;
+        let withdrawal_sum_aggregated =
+            get_withdrawal_sum_from_block_root(&mut *transaction, &header.root)
+                .await;
+        assert_eq!(deposit_sum_aggregated, GweiNewtype(10));
+        assert_eq!(withdrawal_sum_aggregated, GweiNewtype(20));
+    }
 }
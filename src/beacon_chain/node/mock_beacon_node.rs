@@ -13,6 +13,7 @@ use serde_json::Deserializer;
 use std::fs;
 use std::fs::File;
 use std::io::BufReader;
+use thiserror::Error;
 
 pub struct MockBeaconHttpNode {
     pub state_root: StateRoot,
@@ -152,71 +153,80 @@ pub fn load_finality_checkpoints_from_file(
     Ok(finality_checkpoint)
 }
 
-impl MockBeaconHttpNode {
-    pub fn new() -> MockBeaconHttpNode {
-        Self {
-            state_root: Self::load_beacon_state_root(),
-            headers: Self::load_beacon_headers(),
-            validator_balances: Self::load_validator_balances(),
-            validators: Self::load_validators(),
-            block: Self::load_block(),
-            finalityCheckpoints: Self::load_finality_checkpoints(),
-        }
-    }
+#[derive(Debug, Error)]
+pub enum FixtureError {
+    #[error("failed to load fixture {path}: {error:#}")]
+    Load { path: String, error: anyhow::Error },
+}
 
-    fn load_validators() -> ValidatorsEnvelope {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_validators_file =
-            format!("{project_root}/datasets/beaconchain/validators.json")
-                .to_string();
-        load_validators_from_file(&beacon_validators_file, 30).unwrap()
-    }
+fn fixture_path(file_name: &str) -> String {
+    let project_root = env!("CARGO_MANIFEST_DIR");
+    format!("{project_root}/datasets/beaconchain/{file_name}")
+}
 
-    fn load_validator_balances() -> ValidatorBalancesEnvelope {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_validator_balances_file = format!(
-            "{project_root}/datasets/beaconchain/validator_balances.json"
-        )
-        .to_string();
-        load_validator_balances_from_file(&beacon_validator_balances_file, 30)
-            .unwrap()
-    }
+fn load_fixture<T>(
+    file_name: &str,
+    load: impl FnOnce(&String) -> Result<T>,
+) -> Result<T, FixtureError> {
+    let path = fixture_path(file_name);
+    load(&path).map_err(|error| FixtureError::Load { path, error })
+}
 
-    fn load_beacon_state_root() -> StateRoot {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_state_root_file =
-            format!("{project_root}/datasets/beaconchain/root.json")
-                .to_string();
-        load_beacon_state_root_from_file(&beacon_state_root_file)
-            .unwrap()
-            .root as StateRoot
-    }
+fn load_validators() -> Result<ValidatorsEnvelope, FixtureError> {
+    load_fixture("validators.json", |path| load_validators_from_file(path, 30))
+}
 
-    fn load_beacon_headers() -> BeaconHeaderSignedEnvelope {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_header_file =
-            format!("{project_root}/datasets/beaconchain/block_header.json")
-                .to_string();
-        load_beacon_header_from_file(&beacon_header_file).unwrap()
-    }
+fn load_validator_balances() -> Result<ValidatorBalancesEnvelope, FixtureError> {
+    load_fixture("validator_balances.json", |path| {
+        load_validator_balances_from_file(path, 30)
+    })
+}
 
-    fn load_block() -> BeaconBlock {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_block_detail_file =
-            format!("{project_root}/datasets/beaconchain/block_details.json")
-                .to_string();
-        load_beacon_block_details_from_file(beacon_block_detail_file.as_str())
-            .unwrap()
-    }
+fn load_beacon_state_root() -> Result<StateRoot, FixtureError> {
+    load_fixture("root.json", |path| {
+        load_beacon_state_root_from_file(path).map(|state_root| state_root.root)
+    })
+}
 
-    fn load_finality_checkpoints() -> FinalityCheckpoints {
-        let project_root = env!("CARGO_MANIFEST_DIR");
-        let beacon_finality_checkpoint_file = format!(
-            "{project_root}/datasets/beaconchain/finality_checkpoints.json"
-        )
-        .to_string();
-        load_finality_checkpoints_from_file(&beacon_finality_checkpoint_file)
-            .unwrap()
+fn load_beacon_headers() -> Result<BeaconHeaderSignedEnvelope, FixtureError> {
+    load_fixture("block_header.json", |path| load_beacon_header_from_file(path))
+}
+
+fn load_block() -> Result<BeaconBlock, FixtureError> {
+    load_fixture("block_details.json", |path| {
+        load_beacon_block_details_from_file(path)
+    })
+}
+
+fn load_finality_checkpoints() -> Result<FinalityCheckpoints, FixtureError> {
+    load_fixture("finality_checkpoints.json", load_finality_checkpoints_from_file)
+}
+
+// loads every fixture MockBeaconHttpNode is built from, so a fixture which no longer matches
+// the response shapes shows up as an error naming the file instead of a panic in new
+pub fn validate_fixtures() -> Result<(), FixtureError> {
+    load_beacon_state_root()?;
+    load_beacon_headers()?;
+    load_validator_balances()?;
+    load_validators()?;
+    load_block()?;
+    load_finality_checkpoints().map(|_| ())
+}
+
+fn expect_fixture<T>(fixture: Result<T, FixtureError>) -> T {
+    fixture.unwrap_or_else(|error| panic!("{error}"))
+}
+
+impl MockBeaconHttpNode {
+    pub fn new() -> MockBeaconHttpNode {
+        Self {
+            state_root: expect_fixture(load_beacon_state_root()),
+            headers: expect_fixture(load_beacon_headers()),
+            validator_balances: expect_fixture(load_validator_balances()),
+            validators: expect_fixture(load_validators()),
+            block: expect_fixture(load_block()),
+            finalityCheckpoints: expect_fixture(load_finality_checkpoints()),
+        }
     }
 }
 #[async_trait]
@@ -274,6 +284,10 @@ impl BeaconNode for MockBeaconHttpNode {
         Ok(self.finalityCheckpoints.finalized.clone())
     }
 
+    async fn get_finalized_slot(&self) -> anyhow::Result<Slot> {
+        Ok(self.finalityCheckpoints.finalized.slot())
+    }
+
     async fn get_last_finalized_block(&self) -> anyhow::Result<BeaconBlock> {
         Ok(self.block.clone())
     }
@@ -325,6 +339,20 @@ pub mod tests {
 
     /// --- test cases for Mocked Beacon Node ---
 
+    #[test]
+    fn validate_fixtures_test() {
+        validate_fixtures().unwrap();
+    }
+
+    #[test]
+    fn missing_fixture_error_names_file_test() {
+        let error = load_fixture("missing.json", |path| {
+            load_beacon_header_from_file(path)
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("datasets/beaconchain/missing.json"));
+    }
+
     #[tokio::test]
     async fn test_get_block_by_block_root() -> Result<()> {
         let node = MockBeaconHttpNode::new();
@@ -3,9 +3,9 @@
 
 use super::*;
 use crate::beacon_chain::blocks::GENESIS_PARENT_ROOT;
-use crate::beacon_chain::GENESIS_TIMESTAMP;
 pub struct BeaconBlockBuilder {
     block_hash: Option<BlockHash>,
+    block_number: Option<BlockNumber>,
     deposits: Vec<GweiNewtype>,
     parent_root: BlockRoot,
     slot: Slot,
@@ -21,6 +21,7 @@ impl Default for BeaconBlockBuilder {
             slot: Slot(0),
             state_root: StateRoot::default(),
             block_hash: None,
+            block_number: None,
             withdrawals: None,
         }
     }
@@ -32,6 +33,16 @@ impl BeaconBlockBuilder {
         self
     }
 
+    pub fn block_number(mut self, block_number: BlockNumber) -> Self {
+        self.block_number = Some(block_number);
+        self
+    }
+
+    pub fn deposits(mut self, deposits: Vec<GweiNewtype>) -> Self {
+        self.deposits = deposits;
+        self
+    }
+
     pub fn withdrawals(mut self, withdrawals: Vec<Withdrawal>) -> Self {
         self.withdrawals = Some(withdrawals);
         self
@@ -50,9 +61,12 @@ impl BeaconBlockBuilder {
                 data: DepositData { amount: deposit },
             })
             .collect();
+        // block numbers are unique like slots, so unless one is given the slot stands in for it
+        let block_number = Some(self.block_number.unwrap_or(self.slot.0));
         let execution_payload =
             self.block_hash.map(|block_hash| ExecutionPayload {
                 block_hash,
+                block_number,
                 withdrawals: self.withdrawals,
             });
 
@@ -72,6 +86,7 @@ impl From<&BeaconHeaderSignedEnvelope> for BeaconBlockBuilder {
     fn from(header: &BeaconHeaderSignedEnvelope) -> Self {
         Self {
             block_hash: None,
+            block_number: None,
             deposits: vec![],
             parent_root: header.parent_root(),
             slot: header.slot(),
@@ -6,7 +6,9 @@ pub mod mock_beacon_node;
 
 use super::{slots::slot_from_string, slots::Slot};
 use crate::{
-    env::ENV_CONFIG, execution_chain::BlockHash, json_codecs::i32_from_string,
+    env::ENV_CONFIG,
+    execution_chain::{BlockHash, BlockNumber},
+    json_codecs::{i32_from_string, opt_i32_from_string},
     performance::TimedExt, units::GweiNewtype,
 };
 use anyhow::{anyhow, Result};
@@ -16,6 +18,19 @@ use mockall::automock;
 use reqwest::StatusCode;
 use serde::Deserialize;
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use thiserror::Error;
+
+// errors worth telling apart from any other failed beacon node request, a timeout
+// means the node is slow or stalled, so the request can be retried. a 404 is not an error,
+// what was asked for does not exist (anymore, e.g. after a reorg) and callers get None
+#[derive(Debug, Error)]
+pub enum BeaconNodeError {
+    #[error("beacon node request timed out")]
+    Timeout(#[source] reqwest::Error),
+    #[error("beacon node responded with unexpected status {status}, url: {url}")]
+    UnexpectedStatus { status: StatusCode, url: String },
+}
 
 #[derive(Debug, Deserialize)]
 pub enum BlockId {
@@ -61,7 +76,6 @@ pub struct Deposit {
 pub struct Withdrawal {
     #[serde(deserialize_with = "i32_from_string")]
     pub index: i32,
-    #[allow(dead_code)]
     pub address: String,
     pub amount: GweiNewtype,
 }
@@ -69,6 +83,9 @@ pub struct Withdrawal {
 #[derive(Debug, Deserialize, Clone, PartialEq,Eq)]
 pub struct ExecutionPayload {
     pub block_hash: BlockHash,
+    // None for payloads rebuilt from blocks stored before block numbers were kept
+    #[serde(default, deserialize_with = "opt_i32_from_string")]
+    pub block_number: Option<BlockNumber>,
     pub withdrawals: Option<Vec<Withdrawal>>,
 }
 
@@ -97,6 +114,14 @@ impl BeaconBlock {
             .map(|payload| &payload.block_hash)
     }
 
+    // the execution block number, only blocks after the merge carry an execution payload
+    pub fn block_number(&self) -> Option<BlockNumber> {
+        self.body
+            .execution_payload
+            .as_ref()
+            .and_then(|payload| payload.block_number)
+    }
+
     // extract BeaconBlock's body deposit's
     // inner deposit's data values and collect them return in array
     pub fn deposits(&self) -> Vec<&DepositData> {
@@ -129,7 +154,7 @@ struct BeaconBlockVersionedEnvelope {
     data: BeaconBlockSignedEnvelope,
 }
 
-fn make_blocks_url(block_id: &BlockId) -> String {
+fn make_blocks_url(beacon_url: &str, block_id: &BlockId) -> String {
     let block_id_text = match block_id {
         BlockId::BlockRoot(str) => str.to_owned(),
         BlockId::Finalized => "finalized".to_string(),
@@ -138,10 +163,6 @@ fn make_blocks_url(block_id: &BlockId) -> String {
         BlockId::Slot(slot) => slot.to_string(),
     };
 
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch blocks");
     format!("{beacon_url}/eth/v2/beacon/blocks/{}", block_id_text)
 }
 
@@ -157,11 +178,7 @@ struct StateRootFirstEnvelope {
     data: StateRootSecondEnvelope,
 }
 
-fn make_state_root_url(slot: Slot) -> String {
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch blocks");
+fn make_state_root_url(beacon_url: &str, slot: Slot) -> String {
     format!("{beacon_url}/eth/v1/beacon/states/{}/root", slot)
 }
 
@@ -175,11 +192,10 @@ struct ValidatorBalancesEnvelope {
     data: Vec<ValidatorBalance>,
 }
 
-fn make_validator_balances_by_state_url(state_root: &str) -> String {
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch validator balances");
+fn make_validator_balances_by_state_url(
+    beacon_url: &str,
+    state_root: &str,
+) -> String {
     format!(
         "{beacon_url}/eth/v1/beacon/states/{}/validator_balances",
         state_root
@@ -227,7 +243,10 @@ struct HeaderEnvelope {
     data: BeaconHeaderSignedEnvelope,
 }
 
-fn make_header_by_block_id_url(block_id: &BlockId) -> String {
+fn make_header_by_block_id_url(
+    beacon_url: &str,
+    block_id: &BlockId,
+) -> String {
     let block_id_text = match block_id {
         BlockId::BlockRoot(str) => str.to_owned(),
         BlockId::Finalized => "finalized".to_string(),
@@ -236,18 +255,13 @@ fn make_header_by_block_id_url(block_id: &BlockId) -> String {
         BlockId::Slot(slot) => slot.to_string(),
     };
 
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch headers");
     format!("{beacon_url}/eth/v1/beacon/headers/{}", block_id_text)
 }
 
-fn make_validators_by_state_url(state_root: &str) -> String {
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch validator balances");
+fn make_validators_by_state_url(
+    beacon_url: &str,
+    state_root: &str,
+) -> String {
     format!(
         "{beacon_url}/eth/v1/beacon/states/{}/validators",
         state_root
@@ -266,10 +280,19 @@ pub struct ValidatorEnvelope {
 }
 
 impl ValidatorEnvelope {
+    // active_ongoing, active_exiting and active_slashed validators all still count
+    // their effective balance toward the active set
     pub fn is_active(&self) -> bool {
-        self.status == "active_ongoing"
-            || self.status == "active_exiting"
-            || self.status == "active_slashed"
+        self.status.starts_with("active_")
+    }
+
+    // covers both active_slashed and exited_slashed
+    pub fn is_slashed(&self) -> bool {
+        self.status.ends_with("_slashed")
+    }
+
+    pub fn is_exiting(&self) -> bool {
+        self.status == "active_exiting"
     }
 
     pub fn effective_balance(&self) -> GweiNewtype {
@@ -282,23 +305,25 @@ struct ValidatorsEnvelope {
     data: Vec<ValidatorEnvelope>,
 }
 
-fn make_finality_checkpoint_url() -> String {
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required in env to fetch validator balances");
+fn make_finality_checkpoint_url(beacon_url: &str) -> String {
     format!("{beacon_url}/eth/v1/beacon/states/head/finality_checkpoints",)
 }
 
 #[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
 pub struct FinalityCheckpoint {
-    #[allow(dead_code)]
     #[serde(deserialize_with = "i32_from_string")]
     epoch: i32,
     #[allow(dead_code)]
     root: String,
 }
 
+impl FinalityCheckpoint {
+    // checkpoints sit at the first slot of their epoch
+    pub fn slot(&self) -> Slot {
+        Slot(self.epoch * 32)
+    }
+}
+
 #[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
 struct FinalityCheckpoints {
     finalized: FinalityCheckpoint,
@@ -311,6 +336,8 @@ struct CheckpointEnvelope {
 
 #[derive(Clone, Debug)]
 pub struct BeaconNodeHttp {
+    // only required once a request is made, so building a node without BEACON_URL is fine
+    beacon_url: Option<String>,
     client: reqwest::Client,
 }
 
@@ -332,6 +359,7 @@ pub trait BeaconNode {
         block_id: &BlockId,
     ) -> Result<Option<BeaconHeaderSignedEnvelope>>;
 
+    // None when the node does not know the block_root, e.g. because it was reorged out
     async fn get_header_by_block_root(
         &self,
         block_root: &str,
@@ -351,6 +379,10 @@ pub trait BeaconNode {
     async fn get_last_block(&self) -> Result<BeaconBlock>;
 
     async fn get_last_finality_checkpoint(&self) -> Result<FinalityCheckpoint>;
+
+    // the first slot of the last finalized epoch, stored slots up to here can't be reorged
+    async fn get_finalized_slot(&self) -> Result<Slot>;
+
     async fn get_last_finalized_block(&self) -> Result<BeaconBlock>;
     async fn get_last_header(&self) -> Result<BeaconHeaderSignedEnvelope>;
 
@@ -370,19 +402,67 @@ pub trait BeaconNode {
     ) -> Result<Vec<ValidatorEnvelope>>;
 }
 
+impl Default for BeaconNodeHttp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BeaconNodeHttp {
     pub fn new() -> Self {
+        Self::new_with_timeout(Duration::from_secs(
+            ENV_CONFIG.beacon_timeout_seconds,
+        ))
+    }
+
+    // every request, including reading the response body, has to finish within `timeout`,
+    // a stalled beacon node would otherwise hang the sync indefinitely
+    pub fn new_with_timeout(timeout: Duration) -> Self {
+        Self::new_with_url_and_timeout(ENV_CONFIG.beacon_url.clone(), timeout)
+    }
+
+    pub fn new_with_url(beacon_url: &str) -> Self {
+        Self::new_with_url_and_timeout(
+            Some(beacon_url.to_string()),
+            Duration::from_secs(ENV_CONFIG.beacon_timeout_seconds),
+        )
+    }
+
+    fn new_with_url_and_timeout(
+        beacon_url: Option<String>,
+        timeout: Duration,
+    ) -> Self {
         BeaconNodeHttp {
-            client: reqwest::Client::new(),
+            beacon_url,
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("expect beacon node http client to build"),
         }
     }
 
+    fn beacon_url(&self) -> &str {
+        self.beacon_url
+            .as_deref()
+            .expect("BEACON_URL is required in env to fetch from the beacon node")
+    }
+
+    async fn send_get(&self, url: &str) -> Result<reqwest::Response> {
+        self.client.get(url).send().await.map_err(|err| {
+            if err.is_timeout() {
+                BeaconNodeError::Timeout(err).into()
+            } else {
+                err.into()
+            }
+        })
+    }
+
     async fn get_block(
         &self,
         block_id: &BlockId,
     ) -> Result<Option<BeaconBlock>> {
-        let url = make_blocks_url(block_id);
-        let res = self.client.get(&url).send().await?;
+        let url = make_blocks_url(self.beacon_url(), block_id);
+        let res = self.send_get(&url).await?;
         match res.status() {
             StatusCode::NOT_FOUND => Ok(None),
             StatusCode::OK => {
@@ -391,12 +471,11 @@ impl BeaconNodeHttp {
                     .map(|envelope| envelope.data.message)?;
                 Ok(Some(block))
             }
-            status => Err(anyhow!(
-                "failed to fetch block by block_id. block_id = {} status = {} url = {}",
-                block_id,
+            status => Err(BeaconNodeError::UnexpectedStatus {
                 status,
-                res.url()
-            ))
+                url: res.url().to_string(),
+            }
+            .into()),
         }
     }
 }
@@ -442,8 +521,8 @@ impl BeaconNode for BeaconNodeHttp {
         &self,
         slot: Slot,
     ) -> Result<Option<String>> {
-        let url = make_state_root_url(slot);
-        let res = self.client.get(&url).send().await?;
+        let url = make_state_root_url(self.beacon_url(), slot);
+        let res = self.send_get(&url).await?;
 
         match res.status() {
             StatusCode::NOT_FOUND => Ok(None),
@@ -454,12 +533,11 @@ impl BeaconNode for BeaconNodeHttp {
                     .map(|envelope| envelope.data.root)?;
                 Ok(Some(state_root))
             }
-            status => {
-                Err(anyhow!(
-                "failed to fetch state_root by slots. slots={} status={} url={}",
-                slot, status, res.url()
-            ))
+            status => Err(BeaconNodeError::UnexpectedStatus {
+                status,
+                url: res.url().to_string(),
             }
+            .into()),
         }
     }
 
@@ -467,12 +545,11 @@ impl BeaconNode for BeaconNodeHttp {
         &self,
         state_root: &str,
     ) -> Result<Option<Vec<ValidatorBalance>>> {
-        let url = make_validator_balances_by_state_url(state_root);
+        let url =
+            make_validator_balances_by_state_url(self.beacon_url(), state_root);
 
         let res = self
-            .client
-            .get(&url)
-            .send()
+            .send_get(&url)
             .timed("get_validator_balances")
             .await?;
 
@@ -483,32 +560,30 @@ impl BeaconNode for BeaconNodeHttp {
                     res.json::<ValidatorBalancesEnvelope>().await?;
                 Ok(Some(envelope.data))
             }
-            status => Err(anyhow!(
-                "failed to fetch validator balances by state_root. state_root = {} status = {} url = {}",
-                state_root,
+            status => Err(BeaconNodeError::UnexpectedStatus {
                 status,
-                res.url()
-            )),
+                url: res.url().to_string(),
+            }
+            .into()),
         }
     }
     async fn get_header(
         &self,
         block_id: &BlockId,
     ) -> Result<Option<BeaconHeaderSignedEnvelope>> {
-        let url = make_header_by_block_id_url(block_id);
-        let res = self.client.get(&url).send().await?;
+        let url = make_header_by_block_id_url(self.beacon_url(), block_id);
+        let res = self.send_get(&url).await?;
         match res.status() {
             StatusCode::NOT_FOUND => Ok(None),
             StatusCode::OK => {
                 let envelope = res.json::<HeaderEnvelope>().await?;
                 Ok(Some(envelope.data))
             }
-
-            status => Err(anyhow!(
-                "failed to fetch header by block id. status = {} url = {}",
+            status => Err(BeaconNodeError::UnexpectedStatus {
                 status,
-                res.url()
-            )),
+                url: res.url().to_string(),
+            }
+            .into()),
         }
     }
 
@@ -569,10 +644,8 @@ impl BeaconNode for BeaconNodeHttp {
 
     #[allow(dead_code)]
     async fn get_last_finality_checkpoint(&self) -> Result<FinalityCheckpoint> {
-        let url = make_finality_checkpoint_url();
-        self.client
-            .get(&url)
-            .send()
+        let url = make_finality_checkpoint_url(self.beacon_url());
+        self.send_get(&url)
             .await?
             .error_for_status()?
             .json::<CheckpointEnvelope>()
@@ -581,14 +654,18 @@ impl BeaconNode for BeaconNodeHttp {
             .map_err(Into::into)
     }
 
+    async fn get_finalized_slot(&self) -> Result<Slot> {
+        self.get_last_finality_checkpoint()
+            .await
+            .map(|checkpoint| checkpoint.slot())
+    }
+
     async fn get_validators_by_state(
         &self,
         state_root: &str,
     ) -> Result<Vec<ValidatorEnvelope>> {
-        let url = make_validators_by_state_url(state_root);
-        self.client
-            .get(&url)
-            .send()
+        let url = make_validators_by_state_url(self.beacon_url(), state_root);
+        self.send_get(&url)
             .await?
             .error_for_status()?
             .json::<ValidatorsEnvelope>()
@@ -793,3 +870,106 @@ impl BeaconNode for BeaconNodeHttp {
 //         assert_eq!(withdrawals.len(), 16);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio::task;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn header_by_unknown_block_root_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let mock = server
+            .mock("GET", "/eth/v1/beacon/headers/0xreorged_block_root")
+            .with_status(404)
+            .with_body(r#"{"code":404,"message":"NOT_FOUND: beacon block"}"#)
+            .create();
+        let beacon_node = BeaconNodeHttp::new_with_url(&server.url());
+
+        let header = beacon_node
+            .get_header_by_block_root("0xreorged_block_root")
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(header, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn header_by_block_root_server_error_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        server
+            .mock("GET", "/eth/v1/beacon/headers/0xsome_block_root")
+            .with_status(500)
+            .create();
+        let beacon_node = BeaconNodeHttp::new_with_url(&server.url());
+
+        let err = beacon_node
+            .get_header_by_block_root("0xsome_block_root")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<BeaconNodeError>(),
+            Some(BeaconNodeError::UnexpectedStatus { status, .. })
+                if *status == StatusCode::INTERNAL_SERVER_ERROR
+        ));
+    }
+
+    #[tokio::test]
+    async fn request_timeout_test() {
+        // accepts connections but never answers, like a stalled beacon node
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/eth/v1/beacon/headers/head",
+            listener.local_addr().unwrap()
+        );
+        let stalled_node = tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        let beacon_node =
+            BeaconNodeHttp::new_with_timeout(Duration::from_millis(100));
+
+        let err = beacon_node.send_get(&url).await.unwrap_err();
+        stalled_node.abort();
+
+        assert!(matches!(
+            err.downcast_ref::<BeaconNodeError>(),
+            Some(BeaconNodeError::Timeout(_))
+        ));
+    }
+
+    fn validator_with_status(status: &str) -> ValidatorEnvelope {
+        ValidatorEnvelope {
+            status: status.to_string(),
+            validator: Validator {
+                effective_balance: GweiNewtype(32_000_000_000),
+            },
+        }
+    }
+
+    #[test]
+    fn validator_status_test() {
+        let statuses = [
+            // (status, is_active, is_slashed, is_exiting)
+            ("active_ongoing", true, false, false),
+            ("active_exiting", true, false, true),
+            ("active_slashed", true, true, false),
+            ("pending_queued", false, false, false),
+            ("exited_unslashed", false, false, false),
+        ];
+
+        for (status, is_active, is_slashed, is_exiting) in statuses {
+            let validator = validator_with_status(status);
+            assert_eq!(validator.is_active(), is_active, "{status}");
+            assert_eq!(validator.is_slashed(), is_slashed, "{status}");
+            assert_eq!(validator.is_exiting(), is_exiting, "{status}");
+        }
+    }
+}
@@ -25,13 +25,32 @@ pub struct SlotRange {
 
 impl SlotRange {
     pub fn new(greater_than_or_equal: Slot, less_than_or_equal: Slot) -> Self {
-        if greater_than_or_equal > less_than_or_equal {
+        Self::try_new(greater_than_or_equal, less_than_or_equal).unwrap_or_else(|| {
             panic!("invalid input value {greater_than_or_equal} should always be <= {less_than_or_equal}")
+        })
+    }
+
+    // None when the bounds are inverted, for ranges computed from slots which can cross,
+    // like a local slot that got ahead of the chain
+    pub fn try_new(
+        greater_than_or_equal: Slot,
+        less_than_or_equal: Slot,
+    ) -> Option<Self> {
+        if greater_than_or_equal > less_than_or_equal {
+            return None;
         }
-        Self {
+        Some(Self {
             greater_than_or_equal,
             less_than_or_equal,
-        }
+        })
+    }
+
+    pub fn greater_than_or_equal(&self) -> Slot {
+        self.greater_than_or_equal
+    }
+
+    pub fn less_than_or_equal(&self) -> Slot {
+        self.less_than_or_equal
     }
 }
 
@@ -107,6 +126,18 @@ mod tests {
         assert_eq!(range, vec![Slot(1), Slot(2), Slot(3), Slot(4)]);
     }
 
+    #[test]
+    fn slot_range_try_new_inverted_test() {
+        assert!(SlotRange::try_new(Slot(5), Slot(4)).is_none());
+        assert_eq!(
+            SlotRange::try_new(Slot(4), Slot(4))
+                .into_iter()
+                .flatten()
+                .collect::<Vec<Slot>>(),
+            vec![Slot(4)]
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn stream_slots_from_test() {
         let slots_stream = stream_slots_from(Slot(759000)).await;
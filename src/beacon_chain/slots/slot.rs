@@ -1,9 +1,12 @@
-use crate::beacon_chain::GENESIS_TIMESTAMP;
+use crate::beacon_chain::genesis_timestamp;
 use anyhow::Result;
 use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::{
-    fmt::Display,
+    fmt::{self, Display},
     ops::{Add, Mul, Rem, Sub},
     str::FromStr,
 };
@@ -12,7 +15,6 @@ use std::{
     Clone,
     Copy,
     Debug,
-    Deserialize,
     Eq,
     Ord,
     PartialOrd,
@@ -36,7 +38,7 @@ impl Slot {
 
     pub fn from_date_time(date_time: &DateTime<Utc>) -> Option<Self> {
         let seconds_since_genesis =
-            date_time.timestamp() - GENESIS_TIMESTAMP.timestamp();
+            date_time.timestamp() - genesis_timestamp().timestamp();
         if seconds_since_genesis % Self::SECONDS_PER_SLOT as i64 != 0 {
             None
         } else {
@@ -48,7 +50,7 @@ impl Slot {
 
     /// Returns the most recent slot before the given date_time
     pub fn from_date_time_rounded_down(date_time: &DateTime<Utc>) -> Self {
-        let diff_seconds = *date_time - *GENESIS_TIMESTAMP;
+        let diff_seconds = *date_time - genesis_timestamp();
         let slot = diff_seconds.num_seconds() / Slot::SECONDS_PER_SLOT as i64;
         Self(slot as i32)
     }
@@ -57,41 +59,42 @@ impl Slot {
         self.0 % 32 == 0
     }
 
-    pub fn is_first_of_day(&self) -> bool {
+    // Beacon chain time is UTC only, slots map to a DateTime<Utc> which has no DST.
+    // Day, hour and minute boundaries are therefore always found by comparing the
+    // UTC components of a slot and its predecessor, never those of a local time.
+    fn is_first_of_utc_component(
+        &self,
+        component: impl Fn(&DateTime<Utc>) -> u32,
+    ) -> bool {
         if self.0 == 0 {
             return true;
         }
 
-        let day_previous_slot = Self(self.0 - 1).date_time().day();
-        let day = Self(self.0).date_time().day();
+        let previous_date_time: DateTime<Utc> = Self(self.0 - 1).date_time();
+        let date_time: DateTime<Utc> = self.date_time();
 
-        day_previous_slot != day
+        component(&previous_date_time) != component(&date_time)
     }
 
-    pub fn is_first_of_hour(&self) -> bool {
-        if self.0 == 0 {
-            return true;
-        }
+    pub fn is_first_of_day(&self) -> bool {
+        self.is_first_of_utc_component(|date_time| date_time.day())
+    }
 
-        let hour_previous_slot = Self(self.0 - 1).date_time().hour();
-        let hour = Self(self.0).date_time().hour();
-        hour_previous_slot != hour
+    pub fn is_first_of_hour(&self) -> bool {
+        self.is_first_of_utc_component(|date_time| date_time.hour())
     }
 
     pub fn is_first_of_minute(&self) -> bool {
-        if self.0 == 0 {
-            return true;
-        }
-
-        let minute_previous_slot = Self(self.0 - 1).date_time().minute();
-        let minute = Self(self.0).date_time().minute();
-
-        minute_previous_slot != minute
+        self.is_first_of_utc_component(|date_time| date_time.minute())
     }
 
     pub fn epoch(&self) -> i32 {
         self.0 / 32
     }
+
+    pub fn first_of_epoch(epoch: i32) -> Self {
+        Self(epoch * 32)
+    }
 }
 
 impl Display for Slot {
@@ -133,7 +136,7 @@ impl Rem<i32> for Slot {
 impl From<Slot> for DateTime<Utc> {
     fn from(slot: Slot) -> Self {
         let seconds = slot.0 as i64 * Slot::SECONDS_PER_SLOT as i64;
-        *GENESIS_TIMESTAMP + Duration::seconds(seconds)
+        genesis_timestamp() + Duration::seconds(seconds)
     }
 }
 impl From<Slot> for i32 {
@@ -174,13 +177,44 @@ impl From<&i32> for Slot {
     }
 }
 
+struct SlotVisitor;
+
+impl<'de> Visitor<'de> for SlotVisitor {
+    type Value = Slot;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a slot as an i32 number or a numeric string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Slot, E> {
+        i32::try_from(v).map(Slot).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Slot, E> {
+        i32::try_from(v).map(Slot).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Slot, E> {
+        v.parse().map(Slot).map_err(E::custom)
+    }
+}
+
+// the beacon API encodes slots as strings while our own JSON uses numbers, accept both
+impl<'de> Deserialize<'de> for Slot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(SlotVisitor)
+    }
+}
+
+// kept for the existing `deserialize_with` attributes, Slot itself accepts strings now
 pub fn slot_from_string<'de, D>(deserializer: D) -> Result<Slot, D::Error>
 where
     D: Deserializer<'de>,
 {
-    String::deserialize(deserializer)
-        .map(|slot_text| slot_text.parse().expect("expect slots to be i32"))
-        .map(Slot)
+    Slot::deserialize(deserializer)
 }
 
 mod tests {
@@ -205,6 +239,34 @@ mod tests {
         assert!(!Slot(3600).is_first_of_day());
     }
 
+    #[test]
+    fn first_of_day_month_boundary_test() {
+        // 2021-01-31T23:59:59Z -> 2021-02-01T00:00:11Z
+        let last_of_month = Slot(442798);
+        let first_of_month = Slot(442799);
+        assert_eq!(
+            first_of_month.date_time(),
+            "2021-02-01T00:00:11Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert!(!last_of_month.is_first_of_day());
+        assert!(first_of_month.is_first_of_day());
+        assert!(!Slot(442800).is_first_of_day());
+    }
+
+    #[test]
+    fn first_of_day_year_boundary_test() {
+        // 2020-12-31T23:59:59Z -> 2021-01-01T00:00:11Z
+        let last_of_year = Slot(219598);
+        let first_of_year = Slot(219599);
+        assert_eq!(
+            first_of_year.date_time(),
+            "2021-01-01T00:00:11Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert!(!last_of_year.is_first_of_day());
+        assert!(first_of_year.is_first_of_day());
+        assert!(!Slot(219600).is_first_of_day());
+    }
+
     #[test]
     fn get_timestamp_test() {
         assert_eq!(
@@ -225,6 +287,27 @@ mod tests {
         assert!(!Slot(300).is_first_of_hour());
     }
 
+    // genesis is at 12:00:23 UTC, so slot 299 at 13:00:11 UTC is the first slot of the hour
+    #[test]
+    fn first_of_hour_boundary_test() {
+        assert_eq!(
+            Slot(299).date_time(),
+            "2020-12-01T13:00:11Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(Slot(299).date_time().hour(), 13);
+        assert_eq!(Slot(298).date_time().hour(), 12);
+        assert!(Slot(299).is_first_of_hour());
+    }
+
+    // an hour is exactly 300 slots, slots at a multiple of 300 sit 12 seconds past a boundary
+    #[test]
+    fn first_of_hour_multiple_of_300_test() {
+        for hours in 1..=48 {
+            assert!(!Slot(hours * 300).is_first_of_hour());
+            assert!(Slot(hours * 300 - 1).is_first_of_hour());
+        }
+    }
+
     #[test]
     fn first_of_minute_genesis_test() {
         assert!(Slot(0).is_first_of_minute())
@@ -258,4 +341,32 @@ mod tests {
         let slot7 = Slot::GENESIS;
         assert!(slot7.is_first_of_epoch());
     }
+
+    #[test]
+    fn date_time_genesis_override_test() {
+        let sepolia_genesis: DateTime<Utc> =
+            "2022-06-20T22:00:00Z".parse().unwrap();
+        crate::beacon_chain::set_genesis_timestamp_override(Some(
+            sepolia_genesis,
+        ));
+        let date_time = Slot(0).date_time();
+        crate::beacon_chain::set_genesis_timestamp_override(None);
+
+        assert_eq!(date_time, sepolia_genesis);
+    }
+
+    #[test]
+    fn deserialize_number_and_string_test() {
+        let from_number: Slot = serde_json::from_str("5").unwrap();
+        let from_string: Slot = serde_json::from_str("\"5\"").unwrap();
+        assert_eq!(from_number, Slot(5));
+        assert_eq!(from_string, Slot(5));
+        assert_eq!(serde_json::to_string(&from_string).unwrap(), "5");
+    }
+
+    #[test]
+    fn deserialize_invalid_slot_test() {
+        assert!(serde_json::from_str::<Slot>("\"five\"").is_err());
+        assert!(serde_json::from_str::<Slot>("2147483648").is_err());
+    }
 }
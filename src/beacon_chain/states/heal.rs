@@ -1,21 +1,28 @@
-use crate::{beacon_chain::node::BeaconNode, db, kv_store};
+use crate::data_integrity::{self, StateRootComparison};
+use crate::{db, kv_store};
 use crate::{
-    beacon_chain::{self, node::BeaconNodeHttp, Slot},
+    beacon_chain::{self, node::BeaconNodeHttp, BeaconNode, Slot, SlotRange},
     job::job_progress::JobProgress,
-    kv_store::KVStorePostgres,
 };
+use futures::{pin_mut, StreamExt};
 use pit_wall::Progress;
-use std::collections::HashMap;
-use tracing::{debug, info, warn};
+use sqlx::PgPool;
+use tracing::{info, warn};
 
 // The first slot we have stored
 const FIRST_SHARED_ETH_SUPPLY_SLOT: Slot = Slot(0);
 
 const HEAL_BEACON_STATES_KEY: &str = "heal-beacon-states";
 
+// how many slots to ask the beacon node about at once
+const HEAL_CONCURRENCY: u32 = 8;
+
+// how often, in slots, the progress is saved and logged
+const PROGRESS_INTERVAL: i32 = 10000;
+
 pub async fn heal_beacon_states() {
     info!("healing reorged states");
-    let db_pool = db::get_db_pool("heal-beacon-states", 1).await;
+    let db_pool = db::get_db_pool("heal-beacon-states", HEAL_CONCURRENCY).await;
     let kv_store = kv_store::KVStorePostgres::new(db_pool.clone());
     let job_tracer: JobProgress<'_, Slot> =
         JobProgress::new(HEAL_BEACON_STATES_KEY, &kv_store);
@@ -25,75 +32,131 @@ pub async fn heal_beacon_states() {
         .expect("a beacon state should be stored before trying to heal any")
         .slot
         .0;
-    let last_checked = job_tracer.get().await;
+    let last_checked = job_tracer
+        .get()
+        .await
+        .expect("expect heal beacon states progress to be a valid slot");
     let starting_slot = last_checked.unwrap_or(FIRST_SHARED_ETH_SUPPLY_SLOT).0;
 
     let work_todo: u64 = (last_slot - starting_slot) as u64;
     let mut progress = Progress::new("heal-beacon-states", work_todo);
-    let slots = (starting_slot..=last_slot).collect::<Vec<i32>>();
-
-    // here we take the first and last range with step length = 1000
-    // query the beacon_states table
-    // converted the values into the hash map with
-    // key = slot value
-    // value = state_root  -- beacon block hash value
-    for chunk in slots.chunks(10000) {
-        let first = chunk.first().unwrap();
-        let last = chunk.last().unwrap();
-        let stored_states = sqlx::query!(
-            "
-            SELECT
-                slot,
-                state_root
-            FROM
-                beacon_states
-            WHERE
-                slot >= $1
-            AND
-                slot <= $2
-            ORDER BY
-                slot ASC
-            ",
-            *first,
-            *last
-        )
-        .fetch_all(&db_pool)
-        .await
-        .unwrap()
-        .into_iter()
-        .map(|row| (row.slot, row.state_root))
-        .collect::<HashMap<i32, String>>();
-
-        // traverse each slot value in the slots range [first, last]
-        for slot in *first..=*last {
-            let stored_state_root = stored_states.get(&slot).unwrap();
-
-            // query complete state_root the beacon block hash value by sending request to beacon chain API endpoint
-            let state_root = beacon_node
-                .get_state_root_by_slot(slot.into())
+    let slot_range = SlotRange::new(Slot(starting_slot), Slot(last_slot));
+
+    let comparisons = data_integrity::compare_state_roots_stream(
+        &db_pool,
+        &beacon_node,
+        slot_range,
+        HEAL_CONCURRENCY as usize,
+    );
+    pin_mut!(comparisons);
+
+    while let Some(comparison) = comparisons.next().await {
+        let StateRootComparison {
+            slot,
+            stored_state_root,
+            on_chain_state_root,
+        } = comparison.unwrap();
+        let stored_state_root = stored_state_root
+            .expect("expect a stored state_root for every slot being healed");
+        let on_chain_state_root = on_chain_state_root
+            .expect("expect state_root to exist for historic slots");
+
+        // what we expect is the fresh state_root value matches the stored one
+        if stored_state_root != on_chain_state_root {
+            warn!(
+                %slot,
+                "state root mismatch, rolling back stored and re-syncing"
+            );
+            heal_slot(&db_pool, &beacon_node, slot)
                 .await
-                .unwrap()
-                .expect("expect state_root to exist for historic slots");
-
-            // what we expect is the queried fresh stata_root value match with the map's value
-            // if not match we do two things:
-            // --> delete the original blocks from table beacon_states, beacon_issuance, and beacon_validators_balance
-            //    all tables' associated data records are deleted in one transaction
-            // --> re-synchronized the data from beacon chain side to local database tables beacon_states, beacon_issuance, beacon_validators_balance
-            if *stored_state_root != state_root {
-                warn!(
-                    "state root mismatch, rolling back stored and re-syncing"
-                );
-                todo!("add sync rollback and sync slot here ");
-                info!(%slot, "healed state at slots");
-            }
-
-            progress.inc_work_done();
+                .expect("expect to heal a slot with a mismatched state root");
+            info!(%slot, "healed state at slot");
         }
 
-        job_tracer.set(&last.into()).await;
-        info!("{}", progress.get_progress_string());
+        progress.inc_work_done();
+
+        if slot.0 % PROGRESS_INTERVAL == 0 || slot.0 == last_slot {
+            job_tracer.set(&slot).await.unwrap();
+            info!("{}", progress.get_progress_string());
+        }
     }
 
     info!("done healing beacon states")
 }
+
+// replaces everything stored for the slot with what is on chain now:
+// --> delete the slot's records from all beacon tables, in one transaction
+// --> re-sync the slot from the beacon node, it is historic so no analysis is refreshed
+async fn heal_slot(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    slot: Slot,
+) -> anyhow::Result<()> {
+    let mut connection = db_pool.acquire().await?;
+    beacon_chain::rollback_slot_range(&mut connection, slot, slot).await?;
+    beacon_chain::sync_historic_range(
+        db_pool,
+        beacon_node,
+        SlotRange::new(slot, slot),
+        1,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{
+        mock_block::BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode,
+        ValidatorBalance,
+    };
+    use crate::beacon_chain::{balances, states};
+    use crate::db::db::tests::TestDb;
+    use crate::units::GweiNewtype;
+
+    #[tokio::test]
+    async fn heal_slot_test() {
+        let test_db = TestDb::new().await;
+        let slot = Slot(i32::MAX - 2800);
+        beacon_chain::store_state(&test_db.pool, "0xheal_slot_stale", slot)
+            .await;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node.expect_get_last_header().returning(move || {
+            Ok(BeaconHeaderSignedEnvelopeBuilder::new("heal_slot_head", slot)
+                .build())
+        });
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(|_| Ok(Some("0xheal_slot_on_chain".to_string())));
+        beacon_node
+            .expect_get_header_by_slot()
+            .returning(|_| Ok(None));
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        let result = heal_slot(&test_db.pool, &beacon_node, slot).await;
+
+        let state_root =
+            states::get_state_root_by_slot(&test_db.pool, slot).await;
+        let balance = balances::get_balances_by_state_root(
+            &test_db.pool,
+            "0xheal_slot_on_chain",
+        )
+        .await;
+        beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            slot,
+            slot,
+        )
+        .await
+        .unwrap();
+
+        result.unwrap();
+        assert_eq!(state_root.as_deref(), Some("0xheal_slot_on_chain"));
+        assert_eq!(balance, Some(GweiNewtype(32_000_000_000)));
+    }
+}
@@ -3,6 +3,7 @@ pub mod heal;
 use super::slots::Slot;
 pub use heal::heal_beacon_states;
 use sqlx::PgExecutor;
+use std::collections::HashMap;
 
 #[derive(PartialEq, Debug)]
 pub struct BeaconState {
@@ -73,6 +74,36 @@ pub async fn get_state_root_by_slot(
     .map(|row| row.state_root)
 }
 
+// the stored state roots of every slot in [greater_than_or_equal, less_than_or_equal] in
+// one query, keyed by slot. slots with no stored state are missing from the map
+pub async fn get_state_roots_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) -> HashMap<i32, String> {
+    sqlx::query!(
+        "
+        SELECT
+            slot,
+            state_root
+        FROM
+            beacon_states
+        WHERE
+            slot >= $1
+        AND
+            slot <= $2
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| (row.slot, row.state_root))
+    .collect()
+}
+
 pub async fn delete_states(
     executor: impl PgExecutor<'_>,
     greater_than_or_equal: Slot,
@@ -89,6 +120,25 @@ pub async fn delete_states(
     .unwrap();
 }
 
+pub async fn delete_states_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM beacon_states
+        WHERE beacon_states.slot >= $1
+        AND beacon_states.slot <= $2
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 pub async fn delete_state(executor: impl PgExecutor<'_>, slot: Slot) {
     sqlx::query!(
         "
@@ -170,4 +220,28 @@ mod tests {
                 .unwrap();
         assert_eq!(state_root, "0xtest");
     }
+
+    #[tokio::test]
+    async fn get_state_roots_in_range_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        store_state(&mut *transaction, "0xin_range_0", Slot(777770)).await;
+        store_state(&mut *transaction, "0xin_range_2", Slot(777772)).await;
+        store_state(&mut *transaction, "0xout_of_range", Slot(777773)).await;
+
+        let state_roots = get_state_roots_in_range(
+            &mut *transaction,
+            Slot(777770),
+            Slot(777772),
+        )
+        .await;
+
+        assert_eq!(
+            state_roots,
+            HashMap::from([
+                (777770, "0xin_range_0".to_string()),
+                (777772, "0xin_range_2".to_string()),
+            ])
+        );
+    }
 }
@@ -0,0 +1,163 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::beacon_chain::node::{BeaconNode, BeaconNodeHttp};
+use crate::beacon_chain::slots::SlotRange;
+use crate::beacon_chain::{states, Slot};
+use crate::db::db;
+use crate::performance::TimedExt;
+
+use super::state_sync::sync_slot_by_state_root;
+
+#[derive(Debug)]
+pub struct SyncBenchReport {
+    pub slots: usize,
+    pub total: Duration,
+    // time spent syncing each slot, excluding looking up its state_root
+    pub slot_latencies: Vec<Duration>,
+}
+
+impl SyncBenchReport {
+    pub fn slots_per_second(&self) -> f64 {
+        self.slots as f64 / self.total.as_secs_f64()
+    }
+
+    pub fn average_slot_latency(&self) -> Duration {
+        match self.slot_latencies.len() {
+            0 => Duration::ZERO,
+            count => {
+                self.slot_latencies.iter().sum::<Duration>() / count as u32
+            }
+        }
+    }
+}
+
+impl Display for SyncBenchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "synced {} slots in {:.2?}, {:.2} slots/s, {:.2?} average per slot",
+            self.slots,
+            self.total,
+            self.slots_per_second(),
+            self.average_slot_latency()
+        )
+    }
+}
+
+// sync every slot in the range one at a time, like the syncer does following the chain
+async fn time_slot_syncs(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    slot_range: SlotRange,
+) -> Result<SyncBenchReport> {
+    let start = Instant::now();
+    let mut slots = 0;
+    let mut slot_latencies = vec![];
+
+    for slot in slot_range {
+        slots += 1;
+        let state_root = match beacon_node.get_state_root_by_slot(slot).await? {
+            Some(state_root) => state_root,
+            None => continue,
+        };
+
+        let slot_start = Instant::now();
+        sync_slot_by_state_root(db_pool, beacon_node, &state_root, slot)
+            .timed("bench_sync_slot")
+            .await?;
+        slot_latencies.push(slot_start.elapsed());
+    }
+
+    Ok(SyncBenchReport {
+        slots,
+        total: start.elapsed(),
+        slot_latencies,
+    })
+}
+
+// sync the next `slot_count` slots after the last stored one against the configured beacon
+// node, timing how long it takes. the slots stay stored, the syncer continues after them
+pub async fn bench_sync(slot_count: i32) -> Result<SyncBenchReport> {
+    let db_pool = db::get_db_pool("bench-sync", 3).await;
+    let beacon_node = BeaconNodeHttp::new();
+    let first_slot = states::get_last_state(&db_pool)
+        .await
+        .map_or(Slot(0), |state| state.slot + 1);
+    let slot_range =
+        SlotRange::try_new(first_slot, first_slot + slot_count - 1)
+            .context("expect a positive number of slots to benchmark")?;
+
+    info!(%first_slot, slot_count, "benchmarking sync");
+    time_slot_syncs(&db_pool, &beacon_node, slot_range).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{
+        mock_block::BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode,
+        ValidatorBalance,
+    };
+    use crate::beacon_chain::{balances, rollback_slot_range};
+    use crate::db::db::tests::TestDb;
+    use crate::units::GweiNewtype;
+
+    #[tokio::test]
+    async fn time_slot_syncs_test() {
+        let test_db = TestDb::new().await;
+        let first_slot = Slot(i32::MAX - 2100);
+        let last_slot = first_slot + 4;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node.expect_get_last_header().returning(move || {
+            Ok(BeaconHeaderSignedEnvelopeBuilder::new(
+                "bench_sync_head",
+                last_slot,
+            )
+            .build())
+        });
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(|slot| Ok(Some(format!("0xbench_sync_{slot}"))));
+        beacon_node
+            .expect_get_header_by_slot()
+            .returning(|_| Ok(None));
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        let report = time_slot_syncs(
+            &test_db.pool,
+            &beacon_node,
+            SlotRange::new(first_slot, last_slot),
+        )
+        .await;
+
+        let balance = balances::get_balances_by_state_root(
+            &test_db.pool,
+            &format!("0xbench_sync_{last_slot}"),
+        )
+        .await;
+        rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            first_slot,
+            last_slot,
+        )
+        .await
+        .unwrap();
+
+        let report = report.unwrap();
+        assert_eq!(report.slots, 5);
+        assert_eq!(report.slot_latencies.len(), 5);
+        assert!(report.slots_per_second() > 0.0);
+        assert!(report.average_slot_latency() > Duration::ZERO);
+        assert_eq!(balance, Some(GweiNewtype(32_000_000_000)));
+    }
+}
@@ -1,6 +1,156 @@
+use std::time::Duration;
+
 use sqlx::PgPool;
+use tracing::debug;
+
+use crate::beacon_chain::balances::{
+    get_last_stored_effective_balance_sum,
+    get_validator_balances_by_start_of_day_with_timeout,
+};
+use crate::beacon_chain::issuance::update_issuance_breakdown;
+use crate::caching::{self, CacheKey};
+use crate::flippening::update_flippening_data;
+use crate::series::Series;
+use crate::supply::update_supply_parts;
 
+// the daily aggregation scans all of beacon_validators_balance, give up rather than hold
+// a connection forever when the table grows too large for it
+const VALIDATOR_BALANCES_BY_DAY_TIMEOUT: Duration = Duration::from_secs(60);
+
+// analyses too slow to compute per slot, the server serves their last published value
 pub async fn update_deferrable_analysis(db_pool: &PgPool) -> anyhow::Result<()> {
-    // todo : refresh update cache, but now we haven't implement this yet
+    let mut connection = db_pool.acquire().await?;
+    let validator_balances_by_day =
+        get_validator_balances_by_start_of_day_with_timeout(
+            &mut connection,
+            VALIDATOR_BALANCES_BY_DAY_TIMEOUT,
+        )
+        .await?;
+    debug!(
+        days = validator_balances_by_day.len(),
+        "publishing validator balances by day"
+    );
+    caching::update_and_publish(
+        db_pool,
+        &CacheKey::ValidatorBalancesByDay,
+        Series::new(validator_balances_by_day, None),
+    )
+    .await?;
+
+    match get_last_stored_effective_balance_sum(db_pool).await {
+        Some(effective_balance_sum) => {
+            debug!(
+                slot = %effective_balance_sum.slot,
+                "publishing effective balance sum"
+            );
+            caching::update_and_publish(
+                db_pool,
+                &CacheKey::EffectiveBalanceSum,
+                effective_balance_sum,
+            )
+            .await?;
+        }
+        None => debug!("no effective balance sum stored yet, skipping"),
+    }
+
+    update_issuance_breakdown(db_pool).await?;
+    update_supply_parts(db_pool).await?;
+    update_flippening_data(db_pool).await?;
+
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        beacon_chain::{
+            balances::store_validators_balance, states::store_state, Slot,
+        },
+        db::db::tests::TestDb,
+        kv_store::{KVStorePostgres, KvStore},
+        units::GweiNewtype,
+    };
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn update_deferrable_analysis_test() {
+        let test_db = TestDb::new().await;
+        let state_root = "0xupdate_deferrable_analysis_state_root";
+        // far ahead of synced slots, but early enough for chrono to truncate its timestamp
+        let slot = Slot(500_000_000);
+        store_state(&test_db.pool, state_root, slot).await;
+        store_validators_balance(
+            &test_db.pool,
+            state_root,
+            slot,
+            &GweiNewtype(100),
+        )
+        .await;
+
+        let result = update_deferrable_analysis(&test_db.pool).await;
+
+        // the series is published before the rows are gone again
+        sqlx::query!(
+            "DELETE FROM beacon_validators_balance WHERE state_root = $1",
+            state_root
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "DELETE FROM beacon_states WHERE state_root = $1",
+            state_root
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        assert!(result.is_ok());
+        let published: Value = KVStorePostgres::new(test_db.pool.clone())
+            .get(CacheKey::ValidatorBalancesByDay.to_db_key())
+            .await
+            .unwrap();
+        let last_day = published["data"].as_array().unwrap().last().unwrap();
+        assert_eq!(
+            last_day["t"],
+            slot.date_time().timestamp() / 86400 * 86400
+        );
+        assert_eq!(last_day["v"], 100);
+        assert_eq!(published["meta"]["to"], last_day["t"]);
+        assert_eq!(published["meta"]["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn update_deferrable_analysis_effective_balance_sum_test() {
+        let test_db = TestDb::new().await;
+        let state_root = "0xupdate_deferrable_analysis_effective_balance_sum";
+        let slot = Slot(i32::MAX - 1500);
+        store_state(&test_db.pool, state_root, slot).await;
+        sqlx::query!(
+            "UPDATE beacon_states SET effective_balance_sum = $1 WHERE state_root = $2",
+            32_000_000_000_i64,
+            state_root
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        let result = update_deferrable_analysis(&test_db.pool).await;
+
+        sqlx::query!(
+            "DELETE FROM beacon_states WHERE state_root = $1",
+            state_root
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        assert!(result.is_ok());
+        let published: Value = KVStorePostgres::new(test_db.pool.clone())
+            .get(CacheKey::EffectiveBalanceSum.to_db_key())
+            .await
+            .unwrap();
+        assert_eq!(published["slot"], slot.0);
+        assert_eq!(published["sum"], 32_000_000_000.0);
+    }
+}
@@ -1,3 +1,4 @@
+mod bench;
 mod cache_refresh;
 mod slot_rollback;
 mod slot_stream;
@@ -5,10 +6,15 @@ mod slot_sync;
 mod state_sync;
 mod sync_tracker;
 
+pub use bench::{bench_sync, SyncBenchReport};
+pub use cache_refresh::update_deferrable_analysis;
+pub use slot_rollback::rollback_slot_range;
+pub use slot_stream::{slots_only, stream_slots_from_last, StreamItem};
+pub use state_sync::sync_historic_range;
+
 use crate::beacon_chain::deposits;
 use crate::beacon_chain::slots::SlotRange;
-use crate::beacon_chain::syncer::slot_rollback::rollback_slots;
-use crate::beacon_chain::syncer::slot_stream::stream_slots_from_last;
+use crate::beacon_chain::syncer::slot_rollback::{rollback_reorg, rollback_slots};
 use crate::beacon_chain::syncer::slot_sync::find_last_matching_slot;
 use crate::beacon_chain::syncer::state_sync::sync_slot_by_state_root;
 use crate::env::ENV_CONFIG;
@@ -19,13 +25,14 @@ use crate::{
     },
     beacon_chain::{balances, issuance, slot_from_string, withdrawals, Slot},
     beacon_chain::{blocks, states},
+    data_integrity,
     db::db,
     json_codecs::i32_from_string,
     performance::TimedExt,
 };
 use anyhow::{anyhow, Result};
 use chrono::Duration;
-use futures::{stream, SinkExt, Stream, StreamExt};
+use futures::{pin_mut, stream, SinkExt, Stream, StreamExt};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use sqlx::{Acquire, PgConnection, PgExecutor, PgPool};
@@ -44,8 +51,25 @@ pub async fn sync_beacon_states_to_local() -> Result<()> {
     let mut slots_stream = stream_slots_from_last(&db_pool).await;
     let mut slots_queue = VecDeque::<Slot>::new();
 
-    while let Some(slot) = slots_stream.next().await {
-        slots_queue.push_back(slot);
+    while let Some(item) = slots_stream.next().await {
+        // the slot lag gauge costs a beacon node request, an epoch apart is often enough
+        if matches!(item, StreamItem::Slot(slot) if slot.is_first_of_epoch()) {
+            sync_tracker::update_sync_slot_lag(&db_pool, &beacon_node).await;
+        }
+
+        match item {
+            StreamItem::Slot(slot) => slots_queue.push_back(slot),
+            StreamItem::Reorg {
+                depth,
+                new_head_slot,
+            } => {
+                let reorged_slots =
+                    rollback_reorg(&db_pool, depth, new_head_slot).await?;
+                for reorged_slot in reorged_slots.into_iter().rev() {
+                    slots_queue.push_front(reorged_slot);
+                }
+            }
+        }
 
         while let Some(slot) = slots_queue.pop_front() {
             let on_chain_state_root =
@@ -67,10 +91,13 @@ pub async fn sync_beacon_states_to_local() -> Result<()> {
                         .await?;
                 let first_invalid_slot = last_matching_slot + 1;
 
-                rollback_slots(
-                    &mut *db_pool.acquire().await?,
-                    first_invalid_slot,
-                )
+                db::with_retry_on_serialization_failure(|| async {
+                    rollback_slots(
+                        &mut *db_pool.acquire().await?,
+                        first_invalid_slot,
+                    )
+                    .await
+                })
                 .await?;
 
                 for invalid_slot in (first_invalid_slot.0..=slot.0).rev() {
@@ -92,6 +119,195 @@ pub async fn sync_beacon_states_to_local() -> Result<()> {
     Ok(())
 }
 
+// whether the state_root stored for slot - 1 is the one the chain has for slot - 1, only then
+// can the given slot be synced on top of what is stored
+async fn last_slot_matches(
+    executor: impl PgExecutor<'_>,
+    beacon_node: &impl BeaconNode,
+    slot: Slot,
+) -> Result<bool> {
+    if slot.0 == 0 {
+        return Ok(true);
+    }
+
+    let last_stored_state_root =
+        states::get_state_root_by_slot(executor, slot - 1).await;
+    match last_stored_state_root {
+        None => Ok(false),
+        // slot - 1 can vanish from the chain in a deep reorg, then what we stored for it
+        // no longer matches either and has to be rolled back
+        Some(last_stored_state_root) => Ok(beacon_node
+            .get_state_root_by_slot(slot - 1)
+            .await?
+            .is_some_and(|previous_on_chain_state_root| {
+                last_stored_state_root == previous_on_chain_state_root
+            })),
+    }
+}
+
+// sync every slot on the queue, a rollback puts the rolled back slots back in front of it
+async fn sync_queued_slots(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    slots_queues: &mut VecDeque<Slot>,
+) -> Result<()> {
+    // get front slot from queue and handling slot's grained sync job
+    while let Some(slot) = slots_queues.pop_front() {
+        debug!(%slot, "analyzing next slot on the queue");
+
+        // get current slot's on the chain state_root value,
+        // during a deep reorg a queued slot can disappear from the chain, skip it rather
+        // than stop syncing, the slots after it are requeued if it turns out to be needed
+        let on_chain_state_root =
+            match beacon_node.get_state_root_by_slot(slot).await? {
+                Some(on_chain_state_root) => on_chain_state_root,
+                None => {
+                    warn!(%slot, "no state_root on chain for queued slot, skipping");
+                    continue;
+                }
+            };
+
+        // get current slot's off chain db stored state_root value
+        let current_slot_stored_state_root =
+            states::get_state_root_by_slot(db_pool, slot).await;
+
+        // Check if the previous slot's state_root matches the previous slot's on-chain state_root value.
+        // 1. If the current slot is the initial slot(Slot 0), return true as no it has no previous state_root needs to be checked.
+        // 2. Otherwise, retrieve the state_root of slot - 1 from the off-chain database.
+        // -  If no state_root exists in the database for slot-1, return false (mismatch)
+        // - If it exists, compare it with the on-chain state_root for slot-1.
+        //       - If slot-1's on-chain and off-chain state-root match, it means that the data for slot-1 is correctly synced to db, no rollback is needed.
+        //       - If they don't match, a rollback is required to ensure data consistency.
+        // Rollback Process:
+        // - Identify the first slot associated with the mismatched state_root (slot-1), one state_root contains multiple slots, we need to find the last slot from them.
+        // - Remove all data linked to that stata_root (blocks, issuance, deposits, withdrawals) from the database.
+        // - After rollback, reinsert the affected slots into the processing queue for resynchronization.
+        let last_matches = last_slot_matches(db_pool, beacon_node, slot).await?;
+
+        if current_slot_stored_state_root.is_none() && last_matches {
+            // current slot is empty and last state_root matches.
+            debug!("no state stored for current slot and last slots state_root matches chain");
+            // begin sync from current state and current slot
+            state_sync::sync_slot_by_state_root(
+                db_pool,
+                beacon_node,
+                &on_chain_state_root,
+                slot,
+            )
+            .timed_metric("sync_slot_by_state_root")
+            .await?;
+        } else {
+            // we need to roll back all records associated with the current state_root because it is sync not correctly
+            // and then re-insert the roll-back slots to the queue to re-sync the slot's associated state_root's data(blocks, issuance ...) from beacon chain
+            debug!(
+                ?current_slot_stored_state_root,
+                last_matches,
+                "current slot should be empty, last stored slot state_root should match previous on-chain state_root");
+            let last_matching_slot = slot_sync::find_last_matching_slot(
+                db_pool,
+                beacon_node,
+                slot - 1,
+            )
+            .await?;
+            let first_invalid_slot = last_matching_slot + 1;
+            let reorg_depth =
+                sync_tracker::record_reorg(first_invalid_slot, slot);
+            warn!(
+                slot = last_matching_slot.0,
+                reorg_depth, "rolling back to slot"
+            );
+            // all records associated with slot values that locate in the range of [first_invalid_slot, ...) will be removed from db tables
+            db::with_retry_on_serialization_failure(|| async {
+                rollback_slots(
+                    &mut *db_pool.acquire().await?,
+                    first_invalid_slot,
+                )
+                .await
+            })
+            .await?;
+
+            // traverse all roll-back slots and re-insert them back to the queue
+            // each slot item in the queue will be converted into sync sub-tasks to fetch remote data and store them to  db tables
+            for invalid_slot in (first_invalid_slot.0..=slot.0).rev() {
+                slots_queues.push_front(invalid_slot.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// how many slots since finality to ask the beacon node about at once
+const RECONCILE_CONCURRENCY: usize = 8;
+
+// once a checkpoint is finalized the chain up to it can no longer change. compare what is
+// stored from the finalized slot up to the head with the chain, roll back from the first
+// slot that diverged and sync the rolled back slots again. returns the re-synced slots
+pub async fn reconcile_to_finality(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+) -> Result<Vec<Slot>> {
+    let finalized_slot = beacon_node.get_last_finality_checkpoint().await?.slot();
+    let head_slot = beacon_node.get_last_header().await?.slot();
+    let since_finality = match SlotRange::try_new(finalized_slot, head_slot) {
+        Some(since_finality) => since_finality,
+        None => return Ok(vec![]),
+    };
+
+    let comparisons = data_integrity::compare_state_roots_stream(
+        db_pool,
+        beacon_node,
+        since_finality,
+        RECONCILE_CONCURRENCY,
+    );
+    pin_mut!(comparisons);
+    let mut first_divergent_slot = None;
+    while let Some(comparison) = comparisons.next().await {
+        let comparison = comparison?;
+        // slots we have nothing stored for have nothing to roll back
+        if comparison.stored_state_root.is_some() && !comparison.is_match() {
+            first_divergent_slot = Some(comparison.slot);
+            break;
+        }
+    }
+
+    let first_divergent_slot = match first_divergent_slot {
+        Some(first_divergent_slot) => first_divergent_slot,
+        None => {
+            debug!(%finalized_slot, "stored slots match the chain since finality");
+            return Ok(vec![]);
+        }
+    };
+    // a stored slot diverged, so there is a last stored state
+    let last_stored_slot = states::get_last_state(db_pool)
+        .await
+        .expect("expect a stored state after a divergent slot")
+        .slot
+        .min(head_slot);
+
+    warn!(
+        %finalized_slot,
+        slot = %first_divergent_slot,
+        "stored state diverges from the chain past finality, rolling back"
+    );
+    db::with_retry_on_serialization_failure(|| async {
+        rollback_slots(&mut *db_pool.acquire().await?, first_divergent_slot)
+            .await
+    })
+    .await?;
+
+    // only what was stored before is synced again, newer slots are left to the slot stream
+    let resynced_slots: Vec<Slot> =
+        SlotRange::try_new(first_divergent_slot, last_stored_slot)
+            .into_iter()
+            .flatten()
+            .collect();
+    let mut slots_queue = resynced_slots.iter().copied().collect();
+    sync_queued_slots(db_pool, beacon_node, &mut slots_queue).await?;
+
+    Ok(resynced_slots)
+}
+
 // todo: modify this from streaming into queue operation to debug
 pub async fn sync_beacon_states() -> Result<()> {
     info!("syncing beacon states");
@@ -107,107 +323,261 @@ pub async fn sync_beacon_states() -> Result<()> {
     // sync operations are divided amd execute as unit of slots cached in slots_queues
     // sync complete recorder to record the complete progress of the complete synchronize progress
     let mut progress =
-        sync_tracker::sync_progress_tracker(&db_pool, &beacon_node).await;
+        sync_tracker::sync_progress_tracker(&db_pool, &beacon_node).await?;
 
-    while let Some(slot_from_stream) = slots_stream.next().await {
-        // every 100 slots print the sync progress complete message
-        if slot_from_stream.0 % 100 == 0 {
-            info!("sync in progress, {}", progress.get_progress_string());
-        }
+    while let Some(item_from_stream) = slots_stream.next().await {
+        // finality advances an epoch at a time, so that is how often it is worth checking.
+        // the slot lag gauge is refreshed as often, it costs a beacon node request
+        let is_new_epoch = matches!(
+            item_from_stream,
+            StreamItem::Slot(slot) if slot.is_first_of_epoch()
+        );
 
-        // append current slot item to queue
-        slots_queues.push_back(slot_from_stream);
-
-        // inner while loop && get front slot from queue and handling slot's grained sync job
-        while let Some(slot) = slots_queues.pop_front() {
-            debug!(%slot, "analyzing next slot on the queue");
-
-            // get current slot's on the chain state_root value
-            // and expect this response body should always be able to fetch the corresponding on chain state_root value
-            // from beacon chain api endpoint, otherwise, give a panic
-            let on_chain_state_root = beacon_node
-                .get_state_root_by_slot(slot)
-                .await?
-                .unwrap_or_else(|| {
-                    panic!("expect state_root to exist for slot {slot} to sync from queue")
-                });
-
-            // get current slot's off chain db stored state_root value
-            let current_slot_stored_state_root =
-                states::get_state_root_by_slot(&db_pool, slot).await;
-
-            // Check if the previous slot's state_root matches the previous slot's on-chain state_root value.
-            // 1. If the current slot is the initial slot(Slot 0), return true as no it has no previous state_root needs to be checked.
-            // 2. Otherwise, retrieve the state_root of slot - 1 from the off-chain database.
-            // -  If no state_root exists in the database for slot-1, return false (mismatch)
-            // - If it exists, compare it with the on-chain state_root for slot-1.
-            //       - If slot-1's on-chain and off-chain state-root match, it means that the data for slot-1 is correctly synced to db, no rollback is needed.
-            //       - If they don't match, a rollback is required to ensure data consistency.
-            // Rollback Process:
-            // - Identify the first slot associated with the mismatched state_root (slot-1), one state_root contains multiple slots, we need to find the last slot from them.
-            // - Remove all data linked to that stata_root (blocks, issuance, deposits, withdrawals) from the database.
-            // - After rollback, reinsert the affected slots into the processing queue for resynchronization.
-            let last_matches = if slot.0 == 0 {
-                true
-            } else {
-                let last_stored_state_root =
-                    states::get_state_root_by_slot(&db_pool, slot).await;
-                match last_stored_state_root {
-                    None => false,
-                    Some(last_stored_state_root) => {
-                        let previous_on_chain_state_root = beacon_node
-                            .get_state_root_by_slot(slot - 1)
-                            .await?
-                            .expect("expect state slot before current head to exist");
-                        last_stored_state_root == previous_on_chain_state_root
-                    }
+        match item_from_stream {
+            StreamItem::Slot(slot_from_stream) => {
+                // every 100 slots print the sync progress complete message
+                if slot_from_stream.0 % 100 == 0 {
+                    info!(
+                        "sync in progress, {}",
+                        progress.get_progress_string()
+                    );
                 }
-            };
-
-            if current_slot_stored_state_root.is_none() && last_matches {
-                // current slot is empty and last state_root matches.
-                debug!("no state stored for current slot and last slots state_root matches chain");
-                // begin sync from current state and current slot
-                state_sync::sync_slot_by_state_root(
-                    &db_pool,
-                    &beacon_node,
-                    &on_chain_state_root,
-                    slot,
-                )
-                .timed("sync_slot_by_state_root")
-                .await?;
-            } else {
-                // we need to roll back all records associated with the current state_root because it is sync not correctly
-                // and then re-insert the roll-back slots to the queue to re-sync the slot's associated state_root's data(blocks, issuance ...) from beacon chain
-                debug!(
-                    ?current_slot_stored_state_root,
-                    last_matches,
-                    "current slot should be empty, last stored slot state_root should match previous on-chain state_root");
-                let last_matching_slot = slot_sync::find_last_matching_slot(
-                    &db_pool,
-                    &beacon_node,
-                    slot - 1,
-                )
-                .await?;
-                let first_invalid_slot = last_matching_slot + 1;
-                warn!(slot = last_matching_slot.0, "rolling back to slot");
-                // all records associated with slot values that locate in the range of [first_invalid_slot, ...) will be removed from db tables
-                rollback_slots(
-                    &mut *db_pool.acquire().await?,
-                    first_invalid_slot,
-                )
-                .await?;
 
-                // traverse all roll-back slots and re-insert them back to the queue
-                // each slot item in the queue will be converted into sync sub-tasks to fetch remote data and store them to  db tables
-                for invalid_slot in (first_invalid_slot.0..=slot.0).rev() {
-                    slots_queues.push_front(invalid_slot.into());
+                // append current slot item to queue
+                slots_queues.push_back(slot_from_stream);
+            }
+            // the beacon node told us the chain reorged, roll back the stored slots which
+            // are no longer canonical right away and put them in front of the queue to re-sync
+            StreamItem::Reorg {
+                depth,
+                new_head_slot,
+            } => {
+                let reorged_slots =
+                    rollback_reorg(&db_pool, depth, new_head_slot).await?;
+                for reorged_slot in reorged_slots.into_iter().rev() {
+                    slots_queues.push_front(reorged_slot);
                 }
             }
         }
 
+        sync_queued_slots(&db_pool, &beacon_node, &mut slots_queues).await?;
+
+        if is_new_epoch {
+            reconcile_to_finality(&db_pool, &beacon_node).await?;
+            sync_tracker::update_sync_slot_lag(&db_pool, &beacon_node).await;
+        }
+
         progress.inc_work_done();
     } // outer while loop
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{
+        mock_block::BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode,
+    };
+    use crate::beacon_chain::states::store_state;
+    use crate::db::db::tests::{get_test_db_connection, TestDb};
+    use crate::units::GweiNewtype;
+    use mockall::predicate::eq;
+
+    #[tokio::test]
+    async fn last_slot_matches_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        let slot = Slot(i32::MAX - 1100);
+        store_state(&mut *transaction, "0xlast_slot_matches", slot - 1).await;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .with(eq(slot - 1))
+            .returning(|_| Ok(Some("0xlast_slot_matches".to_string())));
+
+        // nothing is stored for the slot itself yet, which is the normal case at the head
+        assert!(last_slot_matches(&mut *transaction, &beacon_node, slot)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn last_slot_missing_on_chain_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        let slot = Slot(i32::MAX - 1110);
+        store_state(&mut *transaction, "0xlast_slot_missing", slot - 1).await;
+
+        let mut beacon_node = MockBeaconNode::new();
+        // slot - 1 vanished from the chain in a reorg
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .with(eq(slot - 1))
+            .returning(|_| Ok(None));
+
+        assert!(!last_slot_matches(&mut *transaction, &beacon_node, slot)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn last_slot_mismatch_triggers_rollback_test() {
+        // the rollback deletes every slot from the first mismatch on
+        let test_db = TestDb::new_isolated().await;
+        let slot = Slot(i32::MAX - 2700);
+        let on_chain_test_id = |slot: Slot| format!("last_slot_mismatch_{slot}");
+        let on_chain_state_root =
+            move |slot: Slot| format!("0x{}_state_root", on_chain_test_id(slot));
+        store_state(&test_db.pool, &on_chain_state_root(slot - 2), slot - 2)
+            .await;
+        // the chain reorged slot - 1 after we stored it
+        store_state(&test_db.pool, "0xlast_slot_mismatch_stored", slot - 1)
+            .await;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(move |slot| Ok(Some(on_chain_state_root(slot))));
+        beacon_node
+            .expect_get_finalized_slot()
+            .returning(move || Ok(slot - 64));
+        beacon_node.expect_get_header_by_slot().returning(move |slot| {
+            Ok(Some(
+                BeaconHeaderSignedEnvelopeBuilder::new(
+                    &on_chain_test_id(slot),
+                    slot,
+                )
+                .build(),
+            ))
+        });
+        // stops the sync of the requeued slots right after the rollback
+        beacon_node
+            .expect_get_last_header()
+            .returning(|| Err(anyhow!("stop after the rollback")));
+        let mut slots_queue = VecDeque::from([slot]);
+
+        let result =
+            sync_queued_slots(&test_db.pool, &beacon_node, &mut slots_queue)
+                .await;
+        let stored_state_roots = (
+            states::get_state_root_by_slot(&test_db.pool, slot - 2).await,
+            states::get_state_root_by_slot(&test_db.pool, slot - 1).await,
+        );
+
+        crate::beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            slot - 2,
+            slot,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.unwrap_err().to_string(), "stop after the rollback");
+        // the reorged slot is rolled back, the last matching one is kept
+        assert_eq!(
+            stored_state_roots,
+            (Some(on_chain_state_root(slot - 2)), None)
+        );
+        // both rolled back slots were requeued, slot - 1 failed to sync
+        assert_eq!(slots_queue, VecDeque::from([slot]));
+    }
+
+    #[tokio::test]
+    async fn sync_queued_slots_skips_missing_state_root_test() {
+        let test_db = TestDb::new().await;
+        let mut beacon_node = MockBeaconNode::new();
+        // both slots vanished in a reorg, the second is still asked for after the first
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .times(2)
+            .returning(|_| Ok(None));
+        let mut slots_queue =
+            VecDeque::from([Slot(i32::MAX - 1200), Slot(i32::MAX - 1199)]);
+
+        let result =
+            sync_queued_slots(&test_db.pool, &beacon_node, &mut slots_queue)
+                .await;
+
+        assert!(result.is_ok());
+        assert!(slots_queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconcile_to_finality_test() {
+        // the rollback deletes every slot from the first mismatch on
+        let test_db = TestDb::new_isolated().await;
+        let finalized_slot = Slot((i32::MAX - 1900) / 32 * 32);
+        let head_slot = finalized_slot + 3;
+        let on_chain_state_root =
+            |slot: Slot| format!("0xreconcile_to_finality_{slot}");
+        for slot in SlotRange::new(finalized_slot - 1, finalized_slot + 1) {
+            store_state(&test_db.pool, &on_chain_state_root(slot), slot).await;
+        }
+        // a fork we synced before the chain finalized past it
+        for slot in SlotRange::new(finalized_slot + 2, head_slot) {
+            store_state(
+                &test_db.pool,
+                &format!("0xreconcile_to_finality_fork_{slot}"),
+                slot,
+            )
+            .await;
+        }
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_last_finality_checkpoint()
+            .returning(move || {
+                Ok(serde_json::from_value(serde_json::json!({
+                    "epoch": finalized_slot.epoch().to_string(),
+                    "root": "0xreconcile_to_finality_checkpoint",
+                }))
+                .unwrap())
+            });
+        beacon_node.expect_get_last_header().returning(move || {
+            Ok(BeaconHeaderSignedEnvelopeBuilder::new(
+                "reconcile_to_finality_head",
+                head_slot,
+            )
+            .build())
+        });
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(move |slot| Ok(Some(on_chain_state_root(slot))));
+        beacon_node
+            .expect_get_header_by_slot()
+            .returning(|_| Ok(None));
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        let result = reconcile_to_finality(&test_db.pool, &beacon_node).await;
+
+        let mut stored_state_roots = vec![];
+        for slot in SlotRange::new(finalized_slot - 1, head_slot) {
+            stored_state_roots
+                .push(states::get_state_root_by_slot(&test_db.pool, slot).await);
+        }
+        rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            finalized_slot - 1,
+            head_slot,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.unwrap(), vec![finalized_slot + 2, head_slot]);
+        assert_eq!(
+            stored_state_roots,
+            SlotRange::new(finalized_slot - 1, head_slot)
+                .into_iter()
+                .map(|slot| Some(on_chain_state_root(slot)))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,6 +1,71 @@
-use sqlx::{Acquire, PgConnection};
-use tracing::debug;
-use crate::beacon_chain::{balances, blocks, issuance, states, Slot};
+use sqlx::{Acquire, PgConnection, PgExecutor, PgPool};
+use tracing::{debug, enabled, warn, Level};
+use crate::beacon_chain::slots::SlotRange;
+use crate::beacon_chain::{balances, blocks, issuance, states, withdrawals, Slot};
+use crate::db;
+use crate::supply;
+
+// how many rows a rollback of the slots in [greater_than_or_equal, ...) would delete per table
+#[derive(Debug, PartialEq)]
+pub struct RollbackPlan {
+    pub blocks: i64,
+    pub issuance: i64,
+    pub balances: i64,
+    pub eth_supply: i64,
+    pub supply_deltas: i64,
+    pub withdrawals: i64,
+    pub states: i64,
+}
+
+// count the rows rollback_slots would delete without deleting anything, useful to inspect a
+// suspected reorg before touching the db
+pub async fn rollback_slots_dry_run(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+) -> RollbackPlan {
+    sqlx::query_as!(
+        RollbackPlan,
+        r#"
+        WITH rolled_back_states AS (
+            SELECT state_root FROM beacon_states
+            WHERE slot >= $1
+        )
+        SELECT
+            (
+                SELECT COUNT(*) FROM beacon_blocks
+                WHERE state_root IN (SELECT state_root FROM rolled_back_states)
+            ) AS "blocks!",
+            (
+                SELECT COUNT(*) FROM beacon_issuance
+                WHERE state_root IN (SELECT state_root FROM rolled_back_states)
+            ) AS "issuance!",
+            (
+                SELECT COUNT(*) FROM beacon_validators_balance
+                WHERE state_root IN (SELECT state_root FROM rolled_back_states)
+            ) AS "balances!",
+            (
+                SELECT COUNT(*) FROM eth_supply
+                WHERE deposits_slot >= $1
+                OR balances_slot >= $1
+            ) AS "eth_supply!",
+            (
+                SELECT COUNT(*) FROM supply_deltas
+                WHERE slot >= $1
+            ) AS "supply_deltas!",
+            (
+                SELECT COUNT(*) FROM beacon_withdrawals
+                WHERE slot >= $1
+            ) AS "withdrawals!",
+            (
+                SELECT COUNT(*) FROM rolled_back_states
+            ) AS "states!"
+        "#,
+        greater_than_or_equal.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
 
 // this function will delete multiple records from beacon tables,
 // that the records locates by the given slot range [given_slot, ...)
@@ -10,16 +75,85 @@ pub async fn rollback_slots(
 ) -> anyhow::Result<()> {
     debug!("rolling back data based on slots locates in range of [{greater_than_or_equal}, ...]");
     let mut transaction = executor.begin().await?;
-    // todo: update table eth_supply but we haven't implement this table's associated function yet, leave a todo here
+    // counting costs a query per table, only do it when someone is going to read the plan
+    if enabled!(Level::DEBUG) {
+        let plan =
+            rollback_slots_dry_run(&mut *transaction, greater_than_or_equal)
+                .await;
+        debug!(?plan, "rollback plan");
+    }
+    // eth_supply and supply_deltas reference beacon_states, so their records go first
+    supply::delete_supply_by_state_root(&mut *transaction, greater_than_or_equal)
+        .await;
+    supply::delete_supply_deltas(&mut *transaction, greater_than_or_equal)
+        .await;
     blocks::delete_blocks(&mut *transaction, greater_than_or_equal).await;
     issuance::delete_issuances(&mut *transaction, greater_than_or_equal).await;
     balances::delete_validator_sums(&mut *transaction, greater_than_or_equal)
         .await;
+    withdrawals::delete_withdrawals(&mut *transaction, greater_than_or_equal)
+        .await;
     states::delete_states(&mut *transaction, greater_than_or_equal).await;
     transaction.commit().await?;
     Ok(())
 }
 
+// like rollback_slots, but only for the slots in [greater_than_or_equal, less_than_or_equal],
+// so a targeted heal of a few slots leaves the newer, good data alone
+pub async fn rollback_slot_range(
+    executor: &mut PgConnection,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) -> anyhow::Result<()> {
+    debug!("rolling back data based on slots locates in range of [{greater_than_or_equal}, {less_than_or_equal}]");
+    let mut transaction = executor.begin().await?;
+    // eth_supply and supply_deltas reference beacon_states, so their records go first
+    supply::delete_supply_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    supply::delete_supply_deltas_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    blocks::delete_blocks_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    issuance::delete_issuances_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    balances::delete_validator_sums_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    withdrawals::delete_withdrawals_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    states::delete_states_in_range(
+        &mut *transaction,
+        greater_than_or_equal,
+        less_than_or_equal,
+    )
+    .await;
+    transaction.commit().await?;
+    Ok(())
+}
+
 // this function will delete records from multiple beacon tables
 // that the records in the beacon tables share the same slot value provided by the parameter
 pub async fn rollback_slot(
@@ -28,7 +162,10 @@ pub async fn rollback_slot(
 ) -> anyhow::Result<()> {
     debug!("rolling back data from db tables based on the given slot {slot}");
     let mut transaction = executor.begin().await?;
-    // todo: update table eth_supply but we haven't implement this table's associated function yet, leave a todo here
+    // eth_supply and supply_deltas reference beacon_states, remove the slot's supply before anything else
+    supply::delete_supply_by_slot(&mut *transaction, slot).await;
+    supply::delete_supply_delta(&mut *transaction, slot).await;
+
     // first - delete block record in beacon_blocks table that the block locates in the given slot period(12 s) on beacon chain
     blocks::delete_block(&mut *transaction, slot).await;
 
@@ -38,6 +175,9 @@ pub async fn rollback_slot(
     // third - delete validator sum from beacon_validators_balance tabel
     balances::delete_validator_sum(&mut *transaction, slot).await;
 
+    withdrawals::delete_withdrawals_in_range(&mut *transaction, slot, slot)
+        .await;
+
     // last -- delete record from table beacon_states -- this should be the last delete, because the above table deletion all refers to
     // record in beacon_states
     states::delete_state(&mut *transaction, slot).await;
@@ -45,3 +185,194 @@ pub async fn rollback_slot(
     Ok(())
 }
 
+
+// the first slot a reorg replaced. the beacon node reports the slot of the new head and
+// the depth of the reorg, the number of slots between the new head and the common ancestor
+// of the old and new chain. every slot after the common ancestor is no longer canonical
+fn first_reorged_slot(new_head_slot: Slot, depth: i32) -> Slot {
+    let common_ancestor_slot = new_head_slot - depth.max(1);
+    common_ancestor_slot + 1
+}
+
+// handle a reorg reported by the beacon node with the slot of the new head and the reorg depth.
+// all stored slots after the common ancestor of both chains are rolled back,
+// and returned in ascending order so the caller can queue them again for re-sync
+pub async fn rollback_reorg(
+    db_pool: &PgPool,
+    depth: i32,
+    new_head_slot: Slot,
+) -> anyhow::Result<Vec<Slot>> {
+    let first_invalid_slot = first_reorged_slot(new_head_slot, depth);
+    let last_stored_slot = match states::get_last_state(db_pool).await {
+        Some(state) if state.slot >= first_invalid_slot => state.slot,
+        _ => {
+            debug!(%first_invalid_slot, "reorg did not touch any stored slots");
+            return Ok(vec![]);
+        }
+    };
+
+    warn!(
+        depth,
+        %new_head_slot,
+        "chain reorged, rolling back to slot {}",
+        first_invalid_slot - 1
+    );
+    db::with_retry_on_serialization_failure(|| async {
+        rollback_slots(&mut *db_pool.acquire().await?, first_invalid_slot).await
+    })
+    .await?;
+
+    Ok(SlotRange::try_new(first_invalid_slot, last_stored_slot)
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::tests::store_test_block;
+    use crate::db::db::tests::get_test_db_connection;
+    use crate::units::GweiNewtype;
+
+    #[test]
+    fn first_reorged_slot_test() {
+        // the new head at 105 built on 102, the old chain's 103 and 104 are gone
+        assert_eq!(first_reorged_slot(Slot(105), 3), Slot(103));
+        // a sibling block replaced the head at the same slot
+        assert_eq!(first_reorged_slot(Slot(105), 1), Slot(105));
+    }
+
+    #[tokio::test]
+    async fn rollback_slots_removes_supply_deltas_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        // far beyond slots other tests store so the rollback leaves their data alone
+        let slots = [
+            Slot(i32::MAX - 302),
+            Slot(i32::MAX - 301),
+            Slot(i32::MAX - 300),
+        ];
+
+        for slot in slots {
+            states::store_state(
+                &mut *transaction,
+                &format!("0xrollback_supply_delta_{}", slot.0),
+                slot,
+            )
+            .await;
+            supply::store_supply_delta(
+                &mut *transaction,
+                slot,
+                &GweiNewtype(10),
+                &GweiNewtype(0),
+            )
+            .await;
+        }
+
+        rollback_slots(&mut transaction, slots[1]).await.unwrap();
+
+        let remaining_slots: Vec<Slot> = supply::get_supply_over_time(
+            &mut *transaction,
+            slots[0],
+            Slot(i32::MAX),
+        )
+        .await
+        .into_iter()
+        .map(|supply_at_slot| supply_at_slot.slot)
+        .collect();
+        assert_eq!(remaining_slots, vec![slots[0]]);
+    }
+
+    #[tokio::test]
+    async fn rollback_slots_dry_run_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        // far beyond slots other tests store so only this test's rows are counted
+        let slot = Slot(i32::MAX - 600);
+
+        store_test_block(&mut transaction, "rollback_dry_run", slot).await;
+        sqlx::query!(
+            "
+            INSERT INTO eth_supply (timestamp, block_number, deposits_slot, balances_slot, supply)
+            VALUES ($1, $2, $2, $2, 0)
+            ",
+            slot.date_time(),
+            slot.0
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "
+            INSERT INTO beacon_withdrawals (index, slot, address, amount)
+            VALUES ($1, $2, '0xrollback_dry_run', 0)
+            ",
+            i64::MAX - 600,
+            slot.0
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        let plan = rollback_slots_dry_run(&mut *transaction, slot).await;
+        assert_eq!(
+            plan,
+            RollbackPlan {
+                blocks: 1,
+                issuance: 0,
+                balances: 0,
+                eth_supply: 1,
+                supply_deltas: 0,
+                withdrawals: 1,
+                states: 1,
+            }
+        );
+
+        // nothing was deleted
+        let plan = rollback_slots_dry_run(&mut *transaction, slot).await;
+        assert_eq!(plan.blocks, 1);
+        assert_eq!(plan.states, 1);
+    }
+
+    #[tokio::test]
+    async fn rollback_slot_range_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction =
+            sqlx::Connection::begin(&mut connection).await.unwrap();
+        // slots 5, 6 and 7 moved far beyond slots other tests store
+        let base = i32::MAX - 900;
+        let slots = [Slot(base + 5), Slot(base + 6), Slot(base + 7)];
+
+        for slot in slots {
+            store_test_block(
+                &mut transaction,
+                &format!("rollback_slot_range_{}", slot.0 - base),
+                slot,
+            )
+            .await;
+        }
+
+        rollback_slot_range(&mut transaction, slots[1], slots[1])
+            .await
+            .unwrap();
+
+        let plan = rollback_slots_dry_run(&mut *transaction, slots[0]).await;
+        assert_eq!(plan.blocks, 2);
+        assert_eq!(plan.states, 2);
+        let remaining_slots: Vec<i32> = sqlx::query_scalar!(
+            "
+            SELECT slot FROM beacon_states
+            WHERE slot >= $1
+            ORDER BY slot
+            ",
+            slots[0].0
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .unwrap();
+        assert_eq!(remaining_slots, vec![slots[0].0, slots[2].0]);
+    }
+}
@@ -4,11 +4,16 @@ use crate::beacon_chain::node::{
 use crate::beacon_chain::slots::SlotRange;
 use crate::beacon_chain::{states, Slot, slot_from_string, FIRST_POST_LONDON_SLOT};
 use crate::env::ENV_CONFIG;
-use futures::{stream, SinkExt, Stream, StreamExt};
+use crate::json_codecs::i32_from_string;
+use futures::{future, stream, SinkExt, Stream, StreamExt};
 use serde::Deserialize;
 use sqlx::PgPool;
 use tracing::{debug, warn};
 
+// head slots buffered for the syncer, a new head arrives every 12s so this is plenty
+// while following the chain, and bounds memory when the syncer falls behind
+const SLOT_STREAM_CAPACITY: usize = 64;
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 struct HeadEvent {
     #[serde(deserialize_with = "slot_from_string")]
@@ -29,72 +34,191 @@ impl From<BeaconHeaderSignedEnvelope> for HeadEvent {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct ChainReorgEvent {
+    // slot of the new head, the chain that replaced the old head
+    #[serde(deserialize_with = "slot_from_string")]
+    slot: Slot,
+    // how many slots back from the new head the old and new chain share an ancestor
+    #[serde(deserialize_with = "i32_from_string")]
+    depth: i32,
+}
+
+// items produced by the slot stream, besides plain slots to sync the beacon node
+// also tells us when a reorg happened so the syncer can roll back right away
+// instead of discovering the mismatching state_roots lazily
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamItem {
+    Slot(Slot),
+    Reorg { depth: i32, new_head_slot: Slot },
+}
+
+impl From<ChainReorgEvent> for StreamItem {
+    fn from(event: ChainReorgEvent) -> Self {
+        StreamItem::Reorg {
+            depth: event.depth,
+            new_head_slot: event.slot,
+        }
+    }
+}
+
+// turn a head event into the items to stream
+// Events received from the client might not arrive in strict sequential order, and gaps between slot values may occur.
+// To handle this, we detect gaps between the received head.slot and the last sent slot, and fill in the missing slots accordingly.
+// last_sent is the high-water mark of the slots streamed so far, a slot at or below it is never streamed again,
+// so a head seen twice (e.g. after a reconnect) or arriving late doesn't get its slot synced twice.
+fn items_from_head_event(
+    head: &HeadEvent,
+    last_head: Option<&HeadEvent>,
+    last_sent: &mut Slot,
+) -> Vec<StreamItem> {
+    match last_head {
+        // a second head event for the slot we just followed, the slot is already on its way.
+        // if the head moved to a sibling block the stored data of this slot is invalid and
+        // needs to be synced again
+        Some(last_head) if last_head.slot == head.slot => {
+            if last_head.block != head.block || last_head.state != head.state {
+                warn!(
+                    slot = head.slot.0,
+                    block = head.block,
+                    "head moved to a sibling block at the same slot"
+                );
+                vec![StreamItem::Reorg {
+                    depth: 1,
+                    new_head_slot: head.slot,
+                }]
+            } else {
+                debug!(
+                    slot = head.slot.0,
+                    "received duplicate head event, discard it!"
+                );
+                vec![]
+            }
+        }
+        _ if head.slot <= *last_sent => {
+            debug!(
+                slot = head.slot.0,
+                last_sent = last_sent.0,
+                "received head event for an already streamed slot, discard it!"
+            );
+            vec![]
+        }
+        _ => {
+            let mut items = vec![];
+            // header event's beacon latest slot value -> head.slot
+            // highest slot streamed so far -> last_sent, starting at slot_to_follow
+            // take this if expression to check there exists gap between two slots: head.slot and last_sent
+            if head.slot != *last_sent + 1 {
+                for missing_slot in (*last_sent + 1).0..head.slot.0 {
+                    debug!(missing_slot, "add missing slot to slots stream");
+                    // appending missing slot that located between [last_sent, head.slot]
+                    items.push(StreamItem::Slot(Slot(missing_slot)));
+                }
+            }
+            // update last_sent value, and continue process next event's header slot value
+            *last_sent = head.slot;
+            items.push(StreamItem::Slot(head.slot));
+            items
+        }
+    }
+}
+
 /**
 This function takes a starting slot (`start_slot`) as input and streams all slot numbers within the range [start_slot, end_slot],
 where the `end_slot` is dynamically determined by the latest `head.slot` received from the Beacon API's event stream.
-The function subscribes to the Beacon API's `head` event stream, which provides the latest slot numbers as they are confirmed.
+The function subscribes to the Beacon API's `head` and `chain_reorg` event streams, the former provides the latest slot numbers
+as they are confirmed, the latter tells us how deep the chain got reorganized.
 
 It checks for any gaps between the received slots and fills them in accordingly.
 
-The valid slot numbers are then sent concurrently into a buffer using the `tx` (write) channel, allowing for multiple threads
-to perform this operation.
+The valid slot numbers and reorgs are then sent concurrently into a buffer using the `tx` (write) channel, allowing for multiple threads
+to perform this operation. The buffer holds at most `capacity` items, once the caller falls behind sending waits for it to catch up,
+instead of buffering every new head slot in memory.
 
 Finally, the `tx` channel is released, and the `rx` (read) channel is returned to the caller.
-The caller can then iterate over the buffer via the `rx` handler to access the slot numbers as they are processed.
+The caller can then iterate over the buffer via the `rx` handler to access the items as they are processed.
 */
-async fn stream_slots(slot_to_follow: Slot) -> impl Stream<Item = Slot> {
-    let beacon_url = ENV_CONFIG
-        .beacon_url
-        .as_ref()
-        .expect("BEACON_URL is required for env to stream beacon updates");
-    let url_string = format!("{beacon_url}/eth/v1/events/?topics=head");
+async fn stream_slots_from_beacon_url(
+    beacon_url: &str,
+    slot_to_follow: Slot,
+    capacity: usize,
+) -> impl Stream<Item = StreamItem> {
+    let url_string =
+        format!("{beacon_url}/eth/v1/events/?topics=head&topics=chain_reorg");
     let url = reqwest::Url::parse(&url_string).unwrap();
 
     // client created for subscribe event stream from beacon API endpoint
     let client = eventsource::reqwest::Client::new(url);
 
     // create a buffer space with buffer write channel as tx and read channel as rx
-    let (mut tx, rx) = futures::channel::mpsc::unbounded();
+    let (mut tx, rx) = futures::channel::mpsc::channel(capacity);
 
     tokio::spawn(async move {
-        let mut last_slot = slot_to_follow;
+        let mut last_sent = slot_to_follow;
+        let mut last_head: Option<HeadEvent> = None;
 
-        // Events received from the client might not arrive in strict sequential order, and gaps between slot values may occur.
-        // To handle this, we detect gaps between the received head.slot and the last known local slot, and fill in the missing slots accordingly.
+        // the client re-establishes the subscription by itself on the next iteration after a
+        // failed request or a dropped connection, last_sent and last_head live outside of
+        // it, so following the chain resumes without emitting a slot twice
         for event in client {
             // subscribed event item from remote
-            let event = event.unwrap();
+            let event = match event {
+                Ok(event) => event,
+                // without events to send, the closed receiver has to be checked here, or a
+                // failing beacon node would keep this task reconnecting forever
+                Err(_) if tx.is_closed() => {
+                    debug!("slot stream receiver dropped, stop following the chain");
+                    return;
+                }
+                Err(err) => {
+                    warn!(%err, "beacon event stream failed, reconnecting");
+                    continue;
+                }
+            };
 
             // use pattern match filter event type we care about
-            match event.event_type {
+            let items = match event.event_type {
                 Some(ref event_type) if event_type == "head" => {
                     let head =
                         serde_json::from_str::<HeadEvent>(&event.data).unwrap();
+                    let items = items_from_head_event(
+                        &head,
+                        last_head.as_ref(),
+                        &mut last_sent,
+                    );
+                    last_head = Some(head);
+                    items
+                }
 
-                    // header event's beacon latest slot value -> head.slot
-                    // local begin sync slot value -> slot_to_follow = last_slot
-                    // take this if expression to check there exists gap between two slots: head.slot and last_slot
-                    if head.slot > last_slot && head.slot != last_slot + 1 {
-                        for missing_slot in (last_slot + 1).0..head.slot.0 {
-                            debug!(
-                                missing_slot,
-                                "add missing slot to slots stream"
-                            );
-                            // appending missing slot that located between [last_slot, head.slot] via buffer write channel handler
-                            tx.send(Slot(missing_slot)).await.unwrap();
-                        }
-                    }
-                    // update last_slot value, and continue process next event's header slot value
-                    last_slot = head.slot;
-                    tx.send(head.slot).await.unwrap();
+                Some(ref event_type) if event_type == "chain_reorg" => {
+                    let reorg =
+                        serde_json::from_str::<ChainReorgEvent>(&event.data)
+                            .unwrap();
+                    warn!(
+                        slot = reorg.slot.0,
+                        depth = reorg.depth,
+                        "received chain reorg event"
+                    );
+                    vec![reorg.into()]
                 }
 
                 Some(event) => {
-                    warn!(event, "received an event from server that wes not head event, discard it!")
+                    warn!(event, "received an event from server that was neither head nor chain_reorg event, discard it!");
+                    vec![]
                 }
 
                 None => {
-                    debug!("received an empty server event, discard it!")
+                    debug!("received an empty server event, discard it!");
+                    vec![]
+                }
+            };
+
+            // push items via buffer write channel handler, once the reader is
+            // gone nobody is interested in following the chain anymore
+            for item in items {
+                if tx.send(item).await.is_err() {
+                    debug!("slot stream receiver dropped, stop following the chain");
+                    return;
                 }
             }
         }
@@ -102,11 +226,24 @@ async fn stream_slots(slot_to_follow: Slot) -> impl Stream<Item = Slot> {
     rx
 }
 
+// adapter for callers which only care about the slots to sync and are fine
+// discovering reorgs lazily through mismatching state_roots
+pub fn slots_only(
+    items_stream: impl Stream<Item = StreamItem>,
+) -> impl Stream<Item = Slot> {
+    items_stream.filter_map(|item| {
+        future::ready(match item {
+            StreamItem::Slot(slot) => Some(slot),
+            StreamItem::Reorg { .. } => None,
+        })
+    })
+}
+
 // after we fetch the start slot value from db or init value of Slot(0)
 // next we query from the beacon endpoint to extract the remote slot value from the latest header message
 // gte_slot --> our local latest slot value, and it is also the start slot
 // value we gonna fetch from the remote beacon endpoint [start = gte_slot, end = last_slot_on_start]
-async fn stream_slots_from(gte_slot: Slot) -> impl Stream<Item = Slot> {
+async fn stream_slots_from(gte_slot: Slot) -> impl Stream<Item = StreamItem> {
     debug!("streaming slots from {gte_slot}");
 
     let beacon_node = BeaconNodeHttp::new();
@@ -121,18 +258,53 @@ async fn stream_slots_from(gte_slot: Slot) -> impl Stream<Item = Slot> {
         .slot;
 
     debug!("last slot on chain: {}", &last_slot_on_start);
-    let slots_stream = stream_slots(last_slot_on_start).await;
+    let beacon_url = ENV_CONFIG
+        .beacon_url
+        .as_ref()
+        .expect("BEACON_URL is required for env to stream beacon updates");
+    stream_historic_then_live_slots(beacon_url, gte_slot, last_slot_on_start)
+        .await
+}
 
-    // slot_range => [start_slot = gte_slot, end_slot = last_slot_on_start]
-    let slot_range = SlotRange::new(gte_slot, last_slot_on_start);
+// the historic slots up to the head on start, then the slots of new heads as they come in
+async fn stream_historic_then_live_slots(
+    beacon_url: &str,
+    gte_slot: Slot,
+    last_slot_on_start: Slot,
+) -> impl Stream<Item = StreamItem> {
+    // with the local slot ahead of the chain, the heads up to it are stored already
+    let slot_to_follow = last_slot_on_start.max(gte_slot - 1);
+    let slots_stream = stream_slots_from_beacon_url(
+        beacon_url,
+        slot_to_follow,
+        SLOT_STREAM_CAPACITY,
+    )
+    .await;
 
-    let historic_slots_stream = stream::iter(slot_range);
-    historic_slots_stream.chain(slots_stream)
+    historic_slots_stream(gte_slot, last_slot_on_start).chain(slots_stream)
+}
+
+// the slots in [start_slot = gte_slot, end_slot = last_slot_on_start], nothing when the local
+// slot is already ahead of the chain, new heads bring the chain past it again
+fn historic_slots_stream(
+    gte_slot: Slot,
+    last_slot_on_start: Slot,
+) -> impl Stream<Item = StreamItem> {
+    let slot_range = SlotRange::try_new(gte_slot, last_slot_on_start);
+    if slot_range.is_none() {
+        warn!(
+            %gte_slot,
+            %last_slot_on_start,
+            "next slot to sync is ahead of the chain, no historic slots to stream"
+        );
+    }
+
+    stream::iter(slot_range.into_iter().flatten()).map(StreamItem::Slot)
 }
 
 pub async fn stream_slots_from_last(
     db_pool: &PgPool,
-) -> impl Stream<Item = Slot> {
+) -> impl Stream<Item = StreamItem> {
     // before we start to fetch data from beacon endpoints
     // we first fetch local db table beacon_states to get the latest/freshest record value and extract record's slot value,
     // let's say the LOCAL_LATEST_SLOT_VALUE
@@ -146,3 +318,291 @@ pub async fn stream_slots_from_last(
     // then we got the next slot value to be sync from beacon endpoint is LOCAL_LATEST_SLOT_VALUE + 1
     stream_slots_from(next_slot_to_sync).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::task;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn local_ahead_of_chain_follows_live_heads_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let body = (99..=102).fold("retry: 10\n\n".to_string(), |body, slot| {
+            let head = json!({
+                "slot": slot.to_string(),
+                "block": format!("0xblock_{slot}"),
+                "state": format!("0xstate_{slot}"),
+            });
+            body + &format!("event: head\ndata: {head}\n\n")
+        });
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create();
+
+        // the db holds slot 100 while the node said its head was 98
+        let items =
+            stream_historic_then_live_slots(&server.url(), Slot(101), Slot(98))
+                .await
+                .take(2)
+                .collect::<Vec<StreamItem>>()
+                .await;
+
+        assert_eq!(
+            items,
+            vec![StreamItem::Slot(Slot(101)), StreamItem::Slot(Slot(102))]
+        );
+    }
+
+    #[tokio::test]
+    async fn historic_slots_local_ahead_of_chain_test() {
+        // the db holds slot 100 while the node we asked is still at 98
+        let slots = historic_slots_stream(Slot(101), Slot(98))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(slots.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_chain_reorg_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let head = json!({
+            "slot": "101",
+            "block": "0xblock_101",
+            "state": "0xstate_101",
+        });
+        let reorg = json!({
+            "slot": "101",
+            "depth": "2",
+            "old_head_block": "0xblock_101",
+            "new_head_block": "0xblock_101_b",
+            "old_head_state": "0xstate_101",
+            "new_head_state": "0xstate_101_b",
+            "epoch": "3",
+        });
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!(
+                "retry: 10\n\nevent: head\ndata: {head}\n\nevent: chain_reorg\ndata: {reorg}\n\n"
+            ))
+            .create();
+
+        let items = stream_slots_from_beacon_url(&server.url(), Slot(100), 8)
+            .await
+            .take(2)
+            .collect::<Vec<StreamItem>>()
+            .await;
+
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Slot(Slot(101)),
+                StreamItem::Reorg {
+                    depth: 2,
+                    new_head_slot: Slot(101)
+                },
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_same_slot_sibling_head_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let head = json!({
+            "slot": "101",
+            "block": "0xblock_101",
+            "state": "0xstate_101",
+        });
+        let sibling_head = json!({
+            "slot": "101",
+            "block": "0xblock_101_b",
+            "state": "0xstate_101_b",
+        });
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!(
+                "retry: 10\n\nevent: head\ndata: {head}\n\nevent: head\ndata: {sibling_head}\n\n"
+            ))
+            .create();
+
+        let items = stream_slots_from_beacon_url(&server.url(), Slot(100), 8)
+            .await
+            .take(2)
+            .collect::<Vec<StreamItem>>()
+            .await;
+
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Slot(Slot(101)),
+                StreamItem::Reorg {
+                    depth: 1,
+                    new_head_slot: Slot(101)
+                },
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_resumes_after_reconnect_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let head_101 = json!({
+            "slot": "101",
+            "block": "0xblock_101",
+            "state": "0xstate_101",
+        });
+        let head_102 = json!({
+            "slot": "102",
+            "block": "0xblock_102",
+            "state": "0xstate_102",
+        });
+        // mocks still missing their expected hit are served first, the last one after that.
+        // the first connection closes after slot 101, reconnecting fails once, and the
+        // next subscription replays slot 101 before slot 102
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!("retry: 10\n\nevent: head\ndata: {head_101}\n\n"))
+            .expect(1)
+            .create();
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(503)
+            .expect(1)
+            .create();
+        server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!(
+                "retry: 10\n\nevent: head\ndata: {head_101}\n\nevent: head\ndata: {head_102}\n\n"
+            ))
+            .create();
+
+        let items = stream_slots_from_beacon_url(&server.url(), Slot(100), 8)
+            .await
+            .take(2)
+            .collect::<Vec<StreamItem>>()
+            .await;
+
+        assert_eq!(
+            items,
+            vec![StreamItem::Slot(Slot(101)), StreamItem::Slot(Slot(102))]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_slots_backpressure_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let body = (101..=105).fold("retry: 10\n\n".to_string(), |body, slot| {
+            let head = json!({
+                "slot": slot.to_string(),
+                "block": format!("0xblock_{slot}"),
+                "state": format!("0xstate_{slot}"),
+            });
+            body + &format!("event: head\ndata: {head}\n\n")
+        });
+        let mock = server
+            .mock("GET", "/eth/v1/events/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .expect(1)
+            .create();
+
+        // a buffer of one item, the channel takes one more for its single sender
+        let slots_stream =
+            stream_slots_from_beacon_url(&server.url(), Slot(100), 1).await;
+
+        // a producer which didn't block would have finished the body and reconnected
+        // many times by now, a blocked one is still waiting on its first connection
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        mock.assert();
+
+        let items = slots_stream.take(5).collect::<Vec<StreamItem>>().await;
+        assert_eq!(
+            items,
+            (101..=105)
+                .map(|slot| StreamItem::Slot(Slot(slot)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn slots_only_skips_reorgs_test() {
+        let items = stream::iter(vec![
+            StreamItem::Slot(Slot(1)),
+            StreamItem::Reorg {
+                depth: 1,
+                new_head_slot: Slot(1),
+            },
+            StreamItem::Slot(Slot(2)),
+        ]);
+
+        let slots = slots_only(items).collect::<Vec<Slot>>().await;
+
+        assert_eq!(slots, vec![Slot(1), Slot(2)]);
+    }
+
+    #[test]
+    fn items_from_head_event_deduplicates_slots_test() {
+        let head = |slot: i32, block: &str| HeadEvent {
+            slot: Slot(slot),
+            block: block.to_string(),
+            state: format!("0xstate_{block}"),
+        };
+        let heads = vec![
+            head(101, "0xblock_101"),
+            // gap, 102 gets filled in
+            head(103, "0xblock_103"),
+            head(103, "0xblock_103"),
+            // late head for the filled in slot
+            head(102, "0xblock_102"),
+            // the same head again after a reconnect
+            head(103, "0xblock_103"),
+            head(101, "0xblock_101"),
+            head(104, "0xblock_104"),
+        ];
+
+        let mut last_sent = Slot(100);
+        let mut last_head: Option<HeadEvent> = None;
+        let mut items = vec![];
+        for head in heads {
+            items.extend(items_from_head_event(
+                &head,
+                last_head.as_ref(),
+                &mut last_sent,
+            ));
+            last_head = Some(head);
+        }
+
+        assert_eq!(
+            items,
+            (101..=104)
+                .map(|slot| StreamItem::Slot(Slot(slot)))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(last_sent, Slot(104));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::beacon_chain::node::{BeaconNode, BeaconNodeHttp};
+use crate::beacon_chain::node::BeaconNode;
 use crate::beacon_chain::{states, Slot};
+#[cfg(test)]
+use crate::beacon_chain::SlotRange;
 use anyhow::{anyhow, Result};
 use chrono::Duration;
 use sqlx::PgPool;
@@ -8,7 +10,7 @@ use tracing::debug;
 // calculate two slots (on chain and off chain)'s timestamp lag value
 // attention: before can invoke this function, we need to ensure that two slots are belong to the same state_root value
 pub async fn get_sync_slot_lag(
-    beacon_node: &BeaconNodeHttp,
+    beacon_node: &impl BeaconNode,
     syncing_slot: Slot,
 ) -> Result<Duration> {
     let last_header = beacon_node.get_last_header().await?;
@@ -26,11 +28,14 @@ pub async fn get_sync_slot_lag(
 // query -> from local db's beacon-states table's state_root value off-chain
 // query -> from remote beacon url endpoint's state_root value  on-chain
 // continue compare
+// reorgs never reach past finality, so the search gives up with an error at the finalized slot,
+// stored and on-chain state not matching there means the stored data is corrupt
 pub async fn find_last_matching_slot(
     db_pool: &PgPool,
-    beacon_node: &BeaconNodeHttp,
+    beacon_node: &impl BeaconNode,
     starting_candidate: Slot,
 ) -> Result<Slot> {
+    let finalized_slot = beacon_node.get_finalized_slot().await?;
     let mut candidate_slot = starting_candidate;
     let mut off_chain_state_root =
         states::get_state_root_by_slot(db_pool, candidate_slot).await;
@@ -51,6 +56,13 @@ pub async fn find_last_matching_slot(
             }
 
             _ => {
+                if candidate_slot <= finalized_slot {
+                    return Err(anyhow!(
+                        "stored state root does not match the chain at slot {candidate_slot}, \
+                        at or before finalized slot {finalized_slot}, stored data looks corrupt"
+                    ));
+                }
+
                 // refresh the candidate_slot minus it by 1
                 candidate_slot = candidate_slot - 1;
 
@@ -74,3 +86,62 @@ pub async fn find_last_matching_slot(
     );
     Ok(candidate_slot)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{
+        mock_block::BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode,
+    };
+    use crate::beacon_chain::rollback_slot_range;
+    use crate::db::db::tests::TestDb;
+
+    #[tokio::test]
+    async fn find_last_matching_slot_stops_at_finality_test() {
+        let test_db = TestDb::new().await;
+        let finalized_slot = Slot(i32::MAX - 2400);
+        let head_slot = finalized_slot + 3;
+
+        // every stored slot, including the finalized one, disagrees with the chain
+        for slot in SlotRange::new(finalized_slot - 2, head_slot) {
+            states::store_state(
+                &test_db.pool,
+                &format!("0xstale_state_root_{slot}"),
+                slot,
+            )
+            .await;
+        }
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_finalized_slot()
+            .returning(move || Ok(finalized_slot));
+        // asking about a slot before finality fails the test, no expectation matches it
+        beacon_node
+            .expect_get_header_by_slot()
+            .withf(move |slot| *slot >= finalized_slot)
+            .returning(|slot| {
+                Ok(Some(
+                    BeaconHeaderSignedEnvelopeBuilder::new(
+                        &format!("finality_gate_{slot}"),
+                        slot,
+                    )
+                    .build(),
+                ))
+            });
+
+        let result =
+            find_last_matching_slot(&test_db.pool, &beacon_node, head_slot)
+                .await;
+        rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            finalized_slot - 2,
+            head_slot,
+        )
+        .await
+        .unwrap();
+
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains(&finalized_slot.to_string()));
+    }
+}
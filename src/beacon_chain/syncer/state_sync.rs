@@ -1,17 +1,55 @@
 use crate::beacon_chain::node::{
-    BeaconBlock, BeaconHeaderSignedEnvelope, BeaconNode, BeaconNodeHttp,
-    StateRoot, ValidatorBalance,
+    BeaconBlock, BeaconHeaderSignedEnvelope, BeaconNode, StateRoot,
+    ValidatorBalance,
 };
 use crate::beacon_chain::syncer::{cache_refresh, slot_sync, BLOCK_LAG_LIMIT};
+use crate::beacon_chain::slots::SlotRange;
 use crate::beacon_chain::{
     balances, blocks, deposits, issuance, states, withdrawals, Slot,
 };
 use crate::performance::TimedExt;
+use crate::supply;
+use crate::units::GweiNewtype;
 use anyhow::anyhow;
 use chrono::Duration;
+use futures::{stream, Future, StreamExt};
 use sqlx::PgPool;
+use std::fmt;
+use std::time::Instant;
 use tracing::{debug, warn};
 
+// wall clock time each phase of syncing a slot took, phases that run more than once add up
+#[derive(Debug, Default)]
+struct SlotSyncTimings {
+    phases: Vec<(&'static str, std::time::Duration)>,
+}
+
+impl SlotSyncTimings {
+    async fn time<F: Future>(&mut self, phase: &'static str, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.timed(phase).await;
+        let elapsed = start.elapsed();
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+        output
+    }
+}
+
+impl fmt::Display for SlotSyncTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut phases = self.phases.iter().peekable();
+        while let Some((phase, elapsed)) = phases.next() {
+            write!(f, "{phase}={elapsed:.2?}")?;
+            if phases.peek().is_some() {
+                write!(f, " ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 struct SyncData {
     header_block_tuple: Option<(BeaconHeaderSignedEnvelope, BeaconBlock)>,
     validator_balances: Option<Vec<ValidatorBalance>>,
@@ -26,7 +64,7 @@ struct SyncData {
 // cause slot is approximate 12 s , we can calculate the `lag` between local and remote beacon chain
 // slot is beacon chain global unique increase value, and this value will not be reset when state root modifies
 async fn gather_sync_data(
-    beacon_node: &BeaconNodeHttp,
+    beacon_node: &impl BeaconNode,
     state_root: &StateRoot,
     slot: Slot,
     sync_lag: &Duration,
@@ -102,22 +140,18 @@ async fn gather_sync_data(
     Ok(sync_data)
 }
 
-// this function is also the main entry point of start sync dataset from beacon chain to local
-// todo: this function looks so complicated maybe we can deposit it to make it a little easier to test and extend
-pub async fn sync_slot_by_state_root(
-    db_pool: &PgPool,             // db connection pool
-    beacon_node: &BeaconNodeHttp, // beacon chain htp request handler
-    state_root: &StateRoot,       // local latest state_root value
-    slot: Slot,                   // off chain slot value
+// store everything gathered for a slot, all in a single transaction
+async fn store_sync_data(
+    db_pool: &PgPool,
+    state_root: &StateRoot,
+    slot: Slot,
+    sync_data: SyncData,
+    timings: &mut SlotSyncTimings,
 ) -> anyhow::Result<()> {
-    // first we take the off chain slot value send request to beacon chain endpoint
-    // to fetch the lag value between local off chain slot and on chain latest slot value
-    let sync_lag = slot_sync::get_sync_slot_lag(beacon_node, slot).await?;
-
     let SyncData {
         header_block_tuple,
         validator_balances,
-    } = gather_sync_data(beacon_node, state_root, slot, &sync_lag).await?;
+    } = sync_data;
 
     // all data has been fetch and cached in the object of SyncData this object
     // now we begin the transaction, and break down & extract different parts from SyncData fields
@@ -131,25 +165,34 @@ pub async fn sync_slot_by_state_root(
                 "storing slot without block, slot: {:?}, state_root: {}",
                 slot, state_root
             );
-            states::store_state(&mut *transaction, state_root, slot)
-                .timed("store state without block")
+            timings
+                .time(
+                    "state",
+                    states::store_state(&mut *transaction, state_root, slot),
+                )
                 .await;
         }
         Some((ref header, ref block)) => {
             // calculate block's total input aggregated value,
             // first fetch block's parent aggregated sum value
             // then traverse each record in current block accumulate each record's value then return
-            let deposit_sum_aggregated =
-                deposits::get_deposit_sum_aggregated(&mut *transaction, block)
-                    .await;
+            let deposit_sum_aggregated = timings
+                .time(
+                    "deposits",
+                    deposits::get_deposit_sum_aggregated(&mut *transaction, block),
+                )
+                .await;
 
             // calculate block's total output aggregated values,
             // first fetch block's parent aggregated sum value,
             // then traverse each record in current block accumulate each record's value then return
-            let withdrawal_sum_aggregated =
-                withdrawals::get_withdrawal_sum_aggregated(
-                    &mut *transaction,
-                    block,
+            let withdrawal_sum_aggregated = timings
+                .time(
+                    "withdrawals",
+                    withdrawals::get_withdrawal_sum_aggregated(
+                        &mut *transaction,
+                        block,
+                    ),
                 )
                 .await;
 
@@ -172,26 +215,34 @@ pub async fn sync_slot_by_state_root(
             }
 
             // save on beacon chain fetched state_root(latest) and slot value to beacon_states table
-            states::store_state(
-                &mut *transaction,
-                &header.state_root(),
-                header.slot(),
-            )
-            .await;
+            timings
+                .time(
+                    "state",
+                    states::store_state(
+                        &mut *transaction,
+                        &header.state_root(),
+                        header.slot(),
+                    ),
+                )
+                .await;
 
             // after the on chain state_root value this anchor is saved, we continue store on chain fetched beacon block
-            blocks::store_block(
-                &mut *transaction,
-                block,
-                // invoke deposits function to calculate each deposit record deposit amount in current block
-                &deposits::get_deposit_sum_from_block(block),
-                &deposit_sum_aggregated, // current block deposits' amount + block's parent deposit aggregated sum
-                // invoke withdrawals inner defined functions to calculate each withdrawal amount in current block
-                &withdrawals::get_withdrawal_sum_from_block(block),
-                &withdrawal_sum_aggregated, // current block withdrawals' amount + block's parent withdrawals aggregated sum
-                header,
-            )
-            .await;
+            timings
+                .time(
+                    "block",
+                    blocks::store_block(
+                        &mut *transaction,
+                        block,
+                        // invoke deposits function to calculate each deposit record deposit amount in current block
+                        &deposits::get_deposit_sum_from_block(block),
+                        &deposit_sum_aggregated, // current block deposits' amount + block's parent deposit aggregated sum
+                        // invoke withdrawals inner defined functions to calculate each withdrawal amount in current block
+                        &withdrawals::get_withdrawal_sum_from_block(block),
+                        &withdrawal_sum_aggregated, // current block withdrawals' amount + block's parent withdrawals aggregated sum
+                        header,
+                    ),
+                )
+                .await;
         }
     }
 
@@ -202,34 +253,69 @@ pub async fn sync_slot_by_state_root(
         debug!("validator balances present");
         let validator_balances_sum =
             balances::sum_validator_balances(validator_balances);
-        balances::store_validators_balance(
-            &mut *transaction,
-            state_root,
-            slot,
-            &validator_balances_sum,
-        )
-        .await;
+        timings
+            .time(
+                "balances",
+                balances::store_validators_balance(
+                    &mut *transaction,
+                    state_root,
+                    slot,
+                    &validator_balances_sum,
+                ),
+            )
+            .await;
 
         if let Some((_, block)) = header_block_tuple {
-            let deposit_sum_aggregated =
-                deposits::get_deposit_sum_aggregated(&mut *transaction, &block)
-                    .await;
-            let withdrawal_sum_aggregated =
-                withdrawals::get_withdrawal_sum_aggregated(
-                    &mut *transaction,
-                    &block,
+            let deposit_sum_aggregated = timings
+                .time(
+                    "deposits",
+                    deposits::get_deposit_sum_aggregated(&mut *transaction, &block),
                 )
                 .await;
+            let withdrawal_sum_aggregated = timings
+                .time(
+                    "withdrawals",
+                    withdrawals::get_withdrawal_sum_aggregated(
+                        &mut *transaction,
+                        &block,
+                    ),
+                )
+                .await;
+
+            let issuance = issuance::calc_issuance(
+                &validator_balances_sum,
+                &withdrawal_sum_aggregated,
+                &deposit_sum_aggregated,
+            )?;
+
+            // issuance is cumulative since genesis, this slot's share of it is the change
+            // since the last slot we stored an issuance for
+            let previous_issuance = timings
+                .time(
+                    "issuance",
+                    issuance::get_issuance_before_slot(&mut *transaction, slot),
+                )
+                .await
+                .unwrap_or(GweiNewtype(0));
 
-            issuance::store_issuance(
+            timings
+                .time(
+                    "issuance",
+                    issuance::store_issuance(
+                        &mut *transaction,
+                        state_root,
+                        slot,
+                        &issuance,
+                    ),
+                )
+                .await;
+
+            // burn is not synced from the execution chain yet, so only issuance moves supply for now
+            supply::store_supply_delta(
                 &mut *transaction,
-                state_root,
                 slot,
-                &issuance::calc_issuance(
-                    &validator_balances_sum,
-                    &withdrawal_sum_aggregated,
-                    &deposit_sum_aggregated,
-                ),
+                &(issuance - previous_issuance),
+                &GweiNewtype(0),
             )
             .await;
         }
@@ -239,7 +325,35 @@ pub async fn sync_slot_by_state_root(
     }
 
     // --- end transaction ---
-    transaction.commit().await?;
+    timings.time("commit", transaction.commit()).await?;
+
+    Ok(())
+}
+
+// this function is also the main entry point of start sync dataset from beacon chain to local
+// todo: this function looks so complicated maybe we can deposit it to make it a little easier to test and extend
+pub async fn sync_slot_by_state_root(
+    db_pool: &PgPool,             // db connection pool
+    beacon_node: &impl BeaconNode, // beacon chain htp request handler
+    state_root: &StateRoot,       // local latest state_root value
+    slot: Slot,                   // off chain slot value
+) -> anyhow::Result<()> {
+    // first we take the off chain slot value send request to beacon chain endpoint
+    // to fetch the lag value between local off chain slot and on chain latest slot value
+    let sync_lag = slot_sync::get_sync_slot_lag(beacon_node, slot).await?;
+
+    let mut timings = SlotSyncTimings::default();
+
+    let sync_data = timings
+        .time(
+            "gather",
+            gather_sync_data(beacon_node, state_root, slot, &sync_lag),
+        )
+        .await?;
+
+    store_sync_data(db_pool, state_root, slot, sync_data, &mut timings).await?;
+
+    debug!(%slot, "slot sync timings: {timings}");
 
     // here we fetch the beacon chain latest state_root value
     // and compare it with our local state_root value
@@ -261,3 +375,230 @@ pub async fn sync_slot_by_state_root(
 
     Ok(())
 }
+
+// sync a range of slots well behind the head, where a reorg can't happen. the beacon node is
+// asked for up to `concurrency` slots at a time, their data is still stored one slot at a
+// time and in slot order, as each block builds on the aggregates stored for its parent.
+// unlike sync_slot_by_state_root this never refreshes the deferrable analysis.
+pub async fn sync_historic_range(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+    slot_range: SlotRange,
+    concurrency: usize,
+) -> anyhow::Result<()> {
+    let last_on_chain_slot =
+        beacon_node.get_last_header().await?.header.message.slot;
+
+    let mut sync_data_stream = stream::iter(slot_range)
+        .map(|slot| async move {
+            let state_root = beacon_node
+                .get_state_root_by_slot(slot)
+                .await?
+                .ok_or_else(|| {
+                    anyhow!("expect state_root to exist for historic slot {slot}")
+                })?;
+            let sync_lag = last_on_chain_slot.date_time() - slot.date_time();
+            let sync_data =
+                gather_sync_data(beacon_node, &state_root, slot, &sync_lag)
+                    .await?;
+            anyhow::Ok((slot, state_root, sync_data))
+        })
+        .buffered(concurrency);
+
+    while let Some(gathered) = sync_data_stream.next().await {
+        let (slot, state_root, sync_data) = gathered?;
+        let mut timings = SlotSyncTimings::default();
+        store_sync_data(db_pool, &state_root, slot, sync_data, &mut timings)
+            .await?;
+        debug!(%slot, "historic slot store timings: {timings}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::node::{
+        mock_block::{BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder},
+        MockBeaconNode,
+    };
+    use crate::db::db::tests::TestDb;
+
+    #[tokio::test]
+    async fn sync_historic_range_test() {
+        let test_db = TestDb::new().await;
+        let first_slot = Slot(i32::MAX - 1300);
+        let last_slot = first_slot + 7;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node.expect_get_last_header().returning(move || {
+            Ok(BeaconHeaderSignedEnvelopeBuilder::new(
+                "sync_historic_range_head",
+                last_slot,
+            )
+            .build())
+        });
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(|slot| Ok(Some(format!("0xsync_historic_range_{slot}"))));
+        // slots without a block only store their state and balances
+        beacon_node
+            .expect_get_header_by_slot()
+            .returning(|_| Ok(None));
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        let result = sync_historic_range(
+            &test_db.pool,
+            &beacon_node,
+            SlotRange::new(first_slot, last_slot),
+            3,
+        )
+        .await;
+
+        let mut stored_slots = vec![];
+        for slot in SlotRange::new(first_slot, last_slot) {
+            let state_root = format!("0xsync_historic_range_{slot}");
+            if let Some(balance) =
+                balances::get_balances_by_state_root(&test_db.pool, &state_root)
+                    .await
+            {
+                assert_eq!(balance, GweiNewtype(32_000_000_000));
+                stored_slots.push(slot);
+            }
+        }
+        crate::beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            first_slot,
+            last_slot,
+        )
+        .await
+        .unwrap();
+
+        result.unwrap();
+        assert_eq!(stored_slots.len(), 8);
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_slot_by_state_root_logs_phase_timings_test() {
+        let test_db = TestDb::new().await;
+        let slot = Slot(i32::MAX - 2200);
+        // the synced block's deposits and withdrawals build on its parent's aggregates
+        let parent_header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "sync_phase_timings_parent",
+            slot - 1,
+        )
+        .build();
+        let parent_block = BeaconBlockBuilder::from(&parent_header).build();
+        states::store_state(
+            &test_db.pool,
+            &parent_header.state_root(),
+            parent_header.slot(),
+        )
+        .await;
+        blocks::store_block(
+            &test_db.pool,
+            &parent_block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &parent_header,
+        )
+        .await;
+
+        let header =
+            BeaconHeaderSignedEnvelopeBuilder::new("sync_phase_timings", slot)
+                .parent_header(&parent_header)
+                .build();
+        let block = BeaconBlockBuilder::from(&header).build();
+        let state_root = header.state_root();
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node.expect_get_last_header().returning(move || {
+            Ok(BeaconHeaderSignedEnvelopeBuilder::new(
+                "sync_phase_timings_head",
+                slot + 1,
+            )
+            .build())
+        });
+        let header_clone = header.clone();
+        beacon_node
+            .expect_get_header_by_slot()
+            .returning(move |_| Ok(Some(header_clone.clone())));
+        let state_root_clone = state_root.clone();
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(move |_| Ok(Some(state_root_clone.clone())));
+        beacon_node
+            .expect_get_block_by_block_root()
+            .returning(move |_| Ok(Some(block.clone())));
+        beacon_node.expect_get_validator_balances().returning(|_| {
+            Ok(Some(vec![ValidatorBalance {
+                balance: GweiNewtype(32_000_000_000),
+            }]))
+        });
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let guard = tracing::subscriber::set_default(subscriber);
+
+        let result =
+            sync_slot_by_state_root(&test_db.pool, &beacon_node, &state_root, slot)
+                .await;
+        drop(guard);
+
+        crate::beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            slot - 1,
+            slot,
+        )
+        .await
+        .unwrap();
+        result.unwrap();
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let timings_line = logs
+            .lines()
+            .find(|line| line.contains("slot sync timings"))
+            .expect("expect a slot sync timings line to be logged");
+        for phase in [
+            "gather=",
+            "state=",
+            "deposits=",
+            "withdrawals=",
+            "block=",
+            "balances=",
+            "issuance=",
+            "commit=",
+        ] {
+            assert!(
+                timings_line.contains(phase),
+                "expect {phase} in {timings_line}"
+            );
+        }
+    }
+}
@@ -1,16 +1,45 @@
+use lazy_static::lazy_static;
 use pit_wall::Progress;
+use prometheus::{exponential_buckets, Histogram, HistogramOpts, IntGauge};
 use crate::beacon_chain::node::{BeaconNode, BeaconNodeHttp};
 use crate::beacon_chain::{states, Slot};
+use crate::caching::{self, CacheKey};
+use crate::performance::REGISTRY;
 use sqlx::{PgExecutor, PgPool};
-use tracing::debug;
+use tracing::{debug, warn};
+
+lazy_static! {
+    static ref BEACON_SYNC_SLOT_LAG: IntGauge = {
+        let gauge = IntGauge::new(
+            "beacon_sync_slot_lag",
+            "slots the last synced beacon state is behind the chain head",
+        )
+        .unwrap();
+        REGISTRY.register(Box::new(gauge.clone())).unwrap();
+        gauge
+    };
+    static ref BEACON_REORG_DEPTH: Histogram = {
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new(
+                "beacon_reorg_depth",
+                "slots rolled back, from the first invalid slot up to and including the head",
+            )
+            // reorgs are mostly a slot or two deep, anything past an epoch is worth a look
+            .buckets(exponential_buckets(1.0, 2.0, 8).unwrap()),
+        )
+        .unwrap();
+        REGISTRY.register(Box::new(histogram.clone())).unwrap();
+        histogram
+    };
+}
 
 // calculate the slot lag between on chain slot and local(off chain) slot value
 async fn estimate_slots_remaining(
     executor: impl PgExecutor<'_>,
     beacon_node: &BeaconNodeHttp,
-) -> i32 {
+) -> anyhow::Result<i32> {
     // on beacon chain latest slot value (slot value is increase and beacon chain global unique value)
-    let last_slot_on_chain = beacon_node.get_last_header().await.unwrap();
+    let last_slot_on_chain = beacon_node.get_last_header().await?;
 
     // off chain local recorded latest slot value
     let last_slot_off_chain = states::get_last_state(executor)
@@ -20,19 +49,76 @@ async fn estimate_slots_remaining(
     // calculate how many slots remain to be sync from remote to local
     let lag = last_slot_on_chain.slot().0 - last_slot_off_chain.0;
     debug!("#estimate_slots_remaining {}", lag);
-    return lag;
+    Ok(lag)
+}
+
+fn set_sync_slot_lag(lag: i32) {
+    BEACON_SYNC_SLOT_LAG.set(lag.into());
+}
+
+// estimate the current slot lag and publish it as the beacon_sync_slot_lag gauge, so
+// alerting can fire when sync falls behind, and under the block-lag cache key, which the
+// server's readiness check reads. both are only a signal, when the beacon node can't tell
+// us its head the last value stays and sync carries on
+pub async fn update_sync_slot_lag(
+    db_pool: &PgPool,
+    beacon_node: &BeaconNodeHttp,
+) {
+    let lag = match estimate_slots_remaining(db_pool, beacon_node).await {
+        Ok(lag) => lag,
+        Err(err) => {
+            warn!(%err, "failed to estimate the sync slot lag");
+            return;
+        }
+    };
+
+    set_sync_slot_lag(lag);
+    if let Err(err) =
+        caching::update_and_publish(db_pool, &CacheKey::BlockLag, lag).await
+    {
+        warn!(%err, "failed to publish the sync slot lag");
+    }
+}
+
+// record a rollback from the first invalid slot `from` up to and including `head` in the
+// beacon_reorg_depth histogram, the depth is returned so the caller can log it
+pub fn record_reorg(from: Slot, head: Slot) -> i32 {
+    let depth = head.0 - from.0 + 1;
+    BEACON_REORG_DEPTH.observe(depth.into());
+    depth
 }
 
 pub async fn sync_progress_tracker(
     db_pool: &PgPool,
     beacon_node: &BeaconNodeHttp,
-) -> Progress {
-    pit_wall::Progress::new(
+) -> anyhow::Result<Progress> {
+    let lag = estimate_slots_remaining(db_pool, beacon_node).await?;
+    set_sync_slot_lag(lag);
+    Ok(pit_wall::Progress::new(
         "sync beacon states",
         // we use estimate_slots_remaining this function to estimate the lag value between [off-chain-latest-slot, on-chain-latest-slot]
-        estimate_slots_remaining(db_pool, beacon_node)
-            .await
-            .try_into()
-            .unwrap(),
-    )
+        lag.try_into().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_sync_slot_lag_test() {
+        set_sync_slot_lag(42);
+        assert_eq!(BEACON_SYNC_SLOT_LAG.get(), 42);
+    }
+
+    #[test]
+    fn record_reorg_test() {
+        let sample_count = BEACON_REORG_DEPTH.get_sample_count();
+
+        let depth = record_reorg(Slot(100), Slot(103));
+
+        // slots 100, 101, 102 and 103 are rolled back
+        assert_eq!(depth, 4);
+        assert_eq!(BEACON_REORG_DEPTH.get_sample_count(), sample_count + 1);
+    }
 }
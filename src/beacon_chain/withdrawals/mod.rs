@@ -20,11 +20,16 @@ pub fn get_withdrawal_sum_from_block(block: &BeaconBlock) -> GweiNewtype {
     }
 }
 
+// running total of all withdrawals up to and including the given block.
+// withdrawals were enabled at SHAPELLA_SLOT, so the parent of any block at or before it was
+// pre-Shapella and contributes nothing. the aggregate therefore starts from the block's own
+// withdrawals there, without reading the parent from the db, which may not have been synced.
+// every block after SHAPELLA_SLOT adds its withdrawals to the parent's stored aggregate.
 pub async fn get_withdrawal_sum_aggregated(
     executor: impl PgExecutor<'_>,
     block: &BeaconBlock,
 ) -> GweiNewtype {
-    let parent_withdrawal_sum_aggregated = if block.slot < *SHAPELLA_SLOT {
+    let parent_withdrawal_sum_aggregated = if block.slot <= *SHAPELLA_SLOT {
         GweiNewtype(0)
     } else {
         blocks::get_withdrawal_sum_from_block_root(executor, &block.parent_root)
@@ -34,10 +39,80 @@ pub async fn get_withdrawal_sum_aggregated(
     parent_withdrawal_sum_aggregated + get_withdrawal_sum_from_block(block)
 }
 
+// total amount withdrawn to the given execution address over the slots in [from, to]
+pub async fn get_withdrawals_sum_by_address(
+    executor: impl PgExecutor<'_>,
+    address: &str,
+    from: Slot,
+    to: Slot,
+) -> GweiNewtype {
+    let sum = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(amount), 0)::INT8 AS "sum!"
+        FROM
+            beacon_withdrawals
+        WHERE address = $1
+        AND slot >= $2
+        AND slot <= $3
+        "#,
+        address,
+        from.0,
+        to.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .sum;
+
+    GweiNewtype(sum)
+}
+
+// delete the withdrawals of all slots in [greater_than_or_equal, ...)
+pub async fn delete_withdrawals(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM beacon_withdrawals
+        WHERE slot >= $1
+        ",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// delete the withdrawals of all slots in [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_withdrawals_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM beacon_withdrawals
+        WHERE slot >= $1
+        AND slot <= $2
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::beacon_chain::{node::Withdrawal, BeaconBlockBuilder};
+    use crate::beacon_chain::{
+        node::Withdrawal, states, BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder,
+    };
+    use crate::db::db::tests;
+    use sqlx::Acquire;
 
     #[test]
     fn zero_withdrawals_test() {
@@ -65,4 +140,142 @@ mod tests {
             .build();
         assert_eq!(get_withdrawal_sum_from_block(&block), GweiNewtype(3));
     }
+
+    #[tokio::test]
+    async fn get_withdrawals_sum_by_address_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let slot = Slot(i32::MAX - 2500);
+        let header =
+            BeaconHeaderSignedEnvelopeBuilder::new("withdrawals_by_address", slot)
+                .build();
+        let block = BeaconBlockBuilder::from(&header)
+            .block_hash("0xwithdrawals_by_address_block_hash")
+            .withdrawals(vec![
+                Withdrawal {
+                    index: i32::MAX - 2,
+                    address: "0xwithdrawals_by_address".to_string(),
+                    amount: GweiNewtype(5),
+                },
+                Withdrawal {
+                    index: i32::MAX - 1,
+                    address: "0xwithdrawals_by_address".to_string(),
+                    amount: GweiNewtype(7),
+                },
+                Withdrawal {
+                    index: i32::MAX,
+                    address: "0xwithdrawals_other_address".to_string(),
+                    amount: GweiNewtype(11),
+                },
+            ])
+            .build();
+
+        states::store_state(&mut *transaction, &header.state_root(), slot).await;
+        blocks::store_block(
+            &mut *transaction,
+            &block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &get_withdrawal_sum_from_block(&block),
+            &GweiNewtype(0),
+            &header,
+        )
+        .await;
+
+        let sum = get_withdrawals_sum_by_address(
+            &mut *transaction,
+            "0xwithdrawals_by_address",
+            slot,
+            slot,
+        )
+        .await;
+        assert_eq!(sum, GweiNewtype(12));
+
+        let sum_outside_range = get_withdrawals_sum_by_address(
+            &mut *transaction,
+            "0xwithdrawals_by_address",
+            slot + 1,
+            slot + 10,
+        )
+        .await;
+        assert_eq!(sum_outside_range, GweiNewtype(0));
+    }
+
+    fn test_withdrawal(index: i32, amount: i64) -> Withdrawal {
+        Withdrawal {
+            index,
+            address: "0xshapella_boundary_address".to_string(),
+            amount: GweiNewtype(amount),
+        }
+    }
+
+    #[tokio::test]
+    async fn withdrawal_sum_aggregated_at_shapella_slot_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // the pre-Shapella parent is not stored, the aggregate must not depend on it
+        let header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "shapella_slot_withdrawals",
+            *SHAPELLA_SLOT,
+        )
+        .build();
+        let block = BeaconBlockBuilder::from(&header)
+            .block_hash("0xshapella_slot_block_hash")
+            .withdrawals(vec![test_withdrawal(i32::MAX - 10, 4)])
+            .build();
+
+        let sum_aggregated =
+            get_withdrawal_sum_aggregated(&mut *transaction, &block).await;
+        assert_eq!(sum_aggregated, GweiNewtype(4));
+    }
+
+    #[tokio::test]
+    async fn withdrawal_sum_aggregated_after_shapella_slot_test() {
+        let mut connection = tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let parent_header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "shapella_slot_parent_withdrawals",
+            *SHAPELLA_SLOT,
+        )
+        .build();
+        let parent_block = BeaconBlockBuilder::from(&parent_header)
+            .block_hash("0xshapella_slot_parent_block_hash")
+            .withdrawals(vec![test_withdrawal(i32::MAX - 11, 4)])
+            .build();
+        let parent_sum_aggregated =
+            get_withdrawal_sum_aggregated(&mut *transaction, &parent_block)
+                .await;
+        states::store_state(
+            &mut *transaction,
+            &parent_header.state_root(),
+            parent_header.slot(),
+        )
+        .await;
+        blocks::store_block(
+            &mut *transaction,
+            &parent_block,
+            &GweiNewtype(0),
+            &GweiNewtype(0),
+            &get_withdrawal_sum_from_block(&parent_block),
+            &parent_sum_aggregated,
+            &parent_header,
+        )
+        .await;
+
+        let header = BeaconHeaderSignedEnvelopeBuilder::new(
+            "shapella_slot_child_withdrawals",
+            *SHAPELLA_SLOT,
+        )
+        .parent_header(&parent_header)
+        .build();
+        assert_eq!(header.slot(), *SHAPELLA_SLOT + 1);
+        let block = BeaconBlockBuilder::from(&header)
+            .block_hash("0xshapella_slot_child_block_hash")
+            .withdrawals(vec![test_withdrawal(i32::MAX - 12, 6)])
+            .build();
+
+        let sum_aggregated =
+            get_withdrawal_sum_aggregated(&mut *transaction, &block).await;
+        assert_eq!(sum_aggregated, GweiNewtype(10));
+    }
 }
@@ -0,0 +1,21 @@
+use eth_analysis_backend::beacon_chain::{
+    backfill_effective_balance_sums, BeaconNodeHttp, FIRST_POST_LONDON_SLOT,
+};
+use eth_analysis_backend::db::db;
+use tracing::info;
+
+#[tokio::main]
+pub async fn main() {
+    eth_analysis_backend::env::check_env_config(true);
+    info!("back filling effective balance sums from london");
+    let db_pool = db::get_db_pool("backfill_effective_balance_sums", 3).await;
+    let beacon_node = BeaconNodeHttp::new();
+    backfill_effective_balance_sums(
+        &db_pool,
+        &beacon_node,
+        FIRST_POST_LONDON_SLOT,
+    )
+    .await;
+
+    info!("done with back filling effective balance sums");
+}
@@ -6,6 +6,7 @@ use eth_analysis_backend::beacon_chain::Slot;
 
 #[tokio::main]
 pub async fn main() {
+    eth_analysis_backend::env::check_env_config(true);
     info!("back filling hourly beacon balances from 1 hour");
     let db_pool = db::get_db_pool("backfill_hourly_balances", 3).await;
     backfill_balances(&db_pool, &Granularity::Hour, Slot(0)).await;
@@ -5,6 +5,7 @@ use eth_analysis_backend::beacon_chain::FIRST_POST_LONDON_SLOT;
 
 #[tokio::main]
 pub async fn main() {
+    eth_analysis_backend::env::check_env_config(true);
     info!("back filling hourly beacon balances");
     let db_pool = db::get_db_pool("backfill_hourly_balances_to_london", 3).await;
     backfill_balances(&db_pool, &Granularity::Hour, FIRST_POST_LONDON_SLOT).await;
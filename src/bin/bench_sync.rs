@@ -0,0 +1,21 @@
+use anyhow::Context;
+use eth_analysis_backend::beacon_chain::bench_sync;
+
+const DEFAULT_SLOT_COUNT: i32 = 32;
+
+// syncs the next slots after the last stored one and prints how fast that went
+// usage: bench_sync [slot count], the slot count defaults to an epoch
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    eth_analysis_backend::env::check_env_config(true);
+    let slot_count = match std::env::args().nth(1) {
+        Some(slot_count) => slot_count
+            .parse::<i32>()
+            .with_context(|| format!("invalid slot count {slot_count}"))?,
+        None => DEFAULT_SLOT_COUNT,
+    };
+
+    let report = bench_sync(slot_count).await?;
+    println!("{report}");
+    Ok(())
+}
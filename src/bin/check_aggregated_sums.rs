@@ -0,0 +1,21 @@
+use anyhow::Context;
+use eth_analysis_backend::data_integrity::{
+    check_aggregated_sums, GWEI_NATIVE_TOLERANCE,
+};
+use eth_analysis_backend::units::GweiNewtype;
+
+// usage: check_aggregated_sums [tolerance in gwei], the tolerance defaults to 0
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    eth_analysis_backend::env::check_env_config(false);
+    let tolerance = match std::env::args().nth(1) {
+        Some(tolerance) => GweiNewtype(
+            tolerance
+                .parse::<i64>()
+                .with_context(|| format!("invalid tolerance {tolerance}"))?,
+        ),
+        None => GWEI_NATIVE_TOLERANCE,
+    };
+    check_aggregated_sums(tolerance).await?;
+    Ok(())
+}
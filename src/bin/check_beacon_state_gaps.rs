@@ -1,5 +1,6 @@
 #[tokio::main]
 pub async  fn main() -> anyhow::Result<()> {
+    eth_analysis_backend::env::check_env_config(false);
     eth_analysis_backend::check_beacon_state_gaps().await?;
     Ok(())
 }
\ No newline at end of file
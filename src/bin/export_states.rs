@@ -0,0 +1,28 @@
+use anyhow::Context;
+use eth_analysis_backend::beacon_chain::Slot;
+use eth_analysis_backend::db::{db, export::export_beacon_states_jsonl};
+use std::io::{stdout, BufWriter};
+
+// writes the beacon states of the slots in [from, to] to stdout as JSON Lines
+// usage: export_states <from slot> <to slot>
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    eth_analysis_backend::env::check_env_config(false);
+    let mut args = std::env::args().skip(1);
+    let mut next_slot = |name: &str| -> anyhow::Result<Slot> {
+        let slot = args
+            .next()
+            .with_context(|| format!("usage: export_states <from slot> <to slot>, missing {name}"))?;
+        let slot = slot
+            .parse::<i32>()
+            .with_context(|| format!("invalid {name} slot {slot}"))?;
+        Ok(Slot(slot))
+    };
+    let from = next_slot("from")?;
+    let to = next_slot("to")?;
+
+    let db_pool = db::get_db_pool("export-states", 1).await;
+    let mut writer = BufWriter::new(stdout().lock());
+    export_beacon_states_jsonl(&db_pool, from, to, &mut writer).await?;
+    Ok(())
+}
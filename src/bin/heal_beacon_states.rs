@@ -1,4 +1,5 @@
 #[tokio::main]
 pub async fn main() {
+    eth_analysis_backend::env::check_env_config(true);
     eth_analysis_backend::beacon_chain::heal_beacon_states().await;
 }
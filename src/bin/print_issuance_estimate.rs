@@ -0,0 +1,18 @@
+use eth_analysis_backend::beacon_chain::{
+    compute_issuance_estimate, IssuanceStoragePostgres,
+};
+use eth_analysis_backend::db::db;
+
+// prints the issuance estimate the update path would publish, without touching the cache
+#[tokio::main]
+pub async fn main() {
+    eth_analysis_backend::env::check_env_config(false);
+    let db_pool = db::get_db_pool("print-issuance-estimate", 3).await;
+    let issuance_store = IssuanceStoragePostgres::new(db_pool.clone());
+    let issuance_estimate =
+        compute_issuance_estimate(&db_pool, &issuance_store).await;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&issuance_estimate).unwrap()
+    );
+}
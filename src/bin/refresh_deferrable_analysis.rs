@@ -0,0 +1,15 @@
+use anyhow::Result;
+use eth_analysis_backend::beacon_chain::update_deferrable_analysis;
+use eth_analysis_backend::db::db;
+use tracing::info;
+
+// forces a refresh of the deferrable analyses without waiting for sync to catch up
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    eth_analysis_backend::env::check_env_config(false);
+    info!("refreshing deferrable analysis");
+    let db_pool = db::get_db_pool("refresh-deferrable-analysis", 3).await;
+    update_deferrable_analysis(&db_pool).await?;
+    info!("done refreshing deferrable analysis");
+    Ok(())
+}
@@ -0,0 +1,7 @@
+use anyhow::Result;
+
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    eth_analysis_backend::env::check_env_config(true);
+    eth_analysis_backend::run_all::run_all().await
+}
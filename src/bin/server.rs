@@ -1,4 +1,5 @@
 #[tokio::main]
 pub async fn main() {
+    eth_analysis_backend::env::check_env_config(false);
     eth_analysis_backend::server::start_server().await;
 }
@@ -1,7 +1,16 @@
+use std::net::SocketAddr;
+
 use anyhow::Result;
-use eth_analysis_backend::{beacon_chain::sync_beacon_states_to_local};
+use eth_analysis_backend::{
+    beacon_chain::sync_beacon_states_to_local, env::ENV_CONFIG, server,
+};
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
+    eth_analysis_backend::env::check_env_config(true);
+    tokio::spawn(server::serve_metrics(SocketAddr::from((
+        [0, 0, 0, 0],
+        ENV_CONFIG.sync_metrics_port,
+    ))));
     sync_beacon_states_to_local().await
-}
\ No newline at end of file
+}
@@ -3,6 +3,7 @@ use crate::{
     time_frames::{GrowingTimeFrame, LimitedTimeFrame, TimeFrame},
 };
 use anyhow::{Result};
+use chrono::Duration;
 use enum_iterator::Sequence;
 use serde::Serialize;
 use serde_json::Value;
@@ -31,12 +32,15 @@ pub enum CacheKey {
     SupplyParts,
     IssuanceBreakdown,
     IssuanceEstimate,
+    MevBidStats,
+    MevBidStatsTimeFrame(TimeFrame),
     SupplyChanges,
     SupplyDashboardAnalysis,
     SupplyOverTime,
     SupplyProjectionInputs,
     SupplySinceMerge,
     TotalDifficultyProgress,
+    ValidatorBalancesByDay,
     ValidatorRewards,
 }
 
@@ -81,6 +85,16 @@ impl CacheKey {
             GaugeRates => "gauge-rates",
             IssuanceBreakdown => "issuance-breakdown",
             IssuanceEstimate => "issuance-estimate",
+            MevBidStats => "mev-bid-stats",
+            MevBidStatsTimeFrame(time_frame) => match time_frame {
+                Growing(SinceBurn) => "mev-bid-stats-since_burn",
+                Growing(SinceMerge) => "mev-bid-stats-since_merge",
+                Limited(Minute5) => "mev-bid-stats-m5",
+                Limited(Hour1) => "mev-bid-stats-h1",
+                Limited(Day1) => "mev-bid-stats-d1",
+                Limited(Day7) => "mev-bid-stats-d7",
+                Limited(Day30) => "mev-bid-stats-d30",
+            },
             SupplyChanges => "supply-changes",
             SupplyDashboardAnalysis => "supply-dashboard-analysis",
             SupplyOverTime => "supply-over-time",
@@ -88,9 +102,50 @@ impl CacheKey {
             SupplyProjectionInputs => "supply-projection-inputs",
             SupplySinceMerge => "supply-since-merge",
             TotalDifficultyProgress => "total-difficulty-progress",
+            ValidatorBalancesByDay => "validator-balances-by-day",
             ValidatorRewards => "validator-rewards",
         }
     }
+
+    // whether some job in this backend computes and publishes a value for this key. keys
+    // without a producer will never show up in the cache, so asking for them is a client error
+    pub fn has_producer(&self) -> bool {
+        use CacheKey::*;
+
+        matches!(
+            self,
+            BlockLag
+                | EffectiveBalanceSum
+                | FlippeningData
+                | IssuanceBreakdown
+                | IssuanceEstimate
+                | SupplyParts
+                | ValidatorBalancesByDay
+        )
+    }
+
+    // how long clients and CDNs may serve a cached value as (max-age, stale-while-revalidate).
+    // fast moving values like the base fee or issuance estimate change every block or slot,
+    // others only change every epoch or are recomputed a few times a day, like the daily
+    // supply over time or validator balances by day, those can be cached for much longer
+    pub fn cache_durations(&self) -> (Duration, Duration) {
+        use CacheKey::*;
+
+        match self {
+            FlippeningData
+            | IssuanceBreakdown
+            | SupplyOverTime
+            | SupplyProjectionInputs
+            | TotalDifficultyProgress
+            | ValidatorBalancesByDay => {
+                (Duration::minutes(10), Duration::hours(1))
+            }
+            EffectiveBalanceSum
+            | SupplySinceMerge
+            | ValidatorRewards => (Duration::minutes(1), Duration::minutes(10)),
+            _ => (Duration::seconds(6), Duration::minutes(2)),
+        }
+    }
 }
 
 impl Display for CacheKey {
@@ -126,6 +181,7 @@ impl FromStr for CacheKey {
             "gauge-rates" => Ok(Self::GaugeRates),
             "issuance-breakdown" => Ok(Self::IssuanceBreakdown),
             "issuance-estimate" => Ok(Self::IssuanceEstimate),
+            "mev-bid-stats" => Ok(Self::MevBidStats),
             "supply-changes" => Ok(Self::SupplyChanges),
             "supply-dashboard-analysis" => Ok(Self::SupplyDashboardAnalysis),
             "supply-over-time" => Ok(Self::SupplyOverTime),
@@ -133,6 +189,7 @@ impl FromStr for CacheKey {
             "supply-projection-inputs" => Ok(Self::SupplyProjectionInputs),
             "supply-since-merge" => Ok(Self::SupplySinceMerge),
             "total-difficulty-progress" => Ok(Self::TotalDifficultyProgress),
+            "validator-balances-by-day" => Ok(Self::ValidatorBalancesByDay),
             "validator-rewards" => Ok(Self::ValidatorRewards),
             unknown_key if unknown_key.starts_with("base-fee-per-gas-stats-") => unknown_key
                 .split('-')
@@ -146,6 +203,28 @@ impl FromStr for CacheKey {
                     Err(ParseCacheKeyError::UnknownCacheKey(unknown_key.to_string())),
                     |key| Ok(Self::BaseFeePerGasStatsTimeFrame(key)),
                 ),
+            unknown_key if unknown_key.starts_with("blob-fee-per-gas-stats-") => unknown_key
+                .split('-')
+                .nth(5)
+                .expect(
+                    "expect keys which start with 'blob-fee-per-gas-stats-' to have a time frame",
+                )
+                .to_string()
+                .parse::<TimeFrame>()
+                .map_or(
+                    Err(ParseCacheKeyError::UnknownCacheKey(unknown_key.to_string())),
+                    |key| Ok(Self::BlobFeePerGasStatsTimeFrame(key)),
+                ),
+            unknown_key if unknown_key.starts_with("mev-bid-stats-") => unknown_key
+                .split('-')
+                .nth(3)
+                .expect("expect keys which start with 'mev-bid-stats-' to have a time frame")
+                .to_string()
+                .parse::<TimeFrame>()
+                .map_or(
+                    Err(ParseCacheKeyError::UnknownCacheKey(unknown_key.to_string())),
+                    |key| Ok(Self::MevBidStatsTimeFrame(key)),
+                ),
             unknown_key => Err(ParseCacheKeyError::UnknownCacheKey(unknown_key.to_string())),
         }
     }
@@ -173,26 +252,47 @@ pub async fn get_serialized_caching_value(
     key_value_store.get(cache_key.to_db_key()).await
 }
 
+// fails when the value can't be represented as json, e.g. a map with non-string keys
 pub async fn set_value<'a>(
     executor: impl PgExecutor<'_>,
     cache_key: &CacheKey,
     value: impl Serialize,
-) {
+) -> Result<(), serde_json::Error> {
     kv_store::set_value(
         executor,
         cache_key.to_db_key(),
-        &serde_json::to_value(value).expect("expect value to be serializable"),
+        &serde_json::to_value(value)?,
     )
     .await;
+    Ok(())
 }
 
 pub async fn update_and_publish(
     db_pool: &PgPool,
     cache_key: &CacheKey,
     value: impl Serialize,
-) {
-    set_value(db_pool, cache_key, value).await;
+) -> Result<(), serde_json::Error> {
+    set_value(db_pool, cache_key, value).await?;
     publish_cache_update(db_pool, cache_key).await;
+    Ok(())
+}
+
+// like update_and_publish, but when the stored value is already equal to the new one nothing is
+// written and listeners are not asked to re-apply it. returns whether an update was published
+pub async fn update_and_publish_if_changed(
+    db_pool: &PgPool,
+    cache_key: &CacheKey,
+    value: impl Serialize,
+) -> Result<bool, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let current_value = kv_store::get_value(db_pool, cache_key.to_db_key()).await;
+    if current_value.as_ref() == Some(&value) {
+        return Ok(false);
+    }
+
+    kv_store::set_value(db_pool, cache_key.to_db_key(), &value).await;
+    publish_cache_update(db_pool, cache_key).await;
+    Ok(true)
 }
 
 #[cfg(test)]
@@ -274,7 +374,7 @@ mod tests {
             &test_db.pool,
             &CacheKey::BaseFeeOverTime,
             test_json.clone(),
-        ).await;
+        ).await.unwrap();
 
         let caching_value = kv_store
             .get_deserializable_value::<TestJson>(CacheKey::BaseFeeOverTime.to_db_key())
@@ -287,6 +387,77 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn update_and_publish_if_changed_test() {
+        let test_db = db::db::tests::TestDb::new().await;
+        // no job produces this key, so no other test publishes it
+        let cache_key = CacheKey::MevBidStats;
+        let done_key = CacheKey::MevBidStatsTimeFrame(TimeFrame::Limited(LimitedTimeFrame::Day7));
+        kv_store::delete_value(&test_db.pool, cache_key.to_db_key()).await;
+
+        let mut listener = sqlx::postgres::PgListener::connect_with(&test_db.pool)
+            .await
+            .unwrap();
+        listener.listen("cache-update").await.unwrap();
+
+        let value = TestJson {
+            name: "Sam".to_string(),
+            age: 29,
+        };
+        let first_published = update_and_publish_if_changed(&test_db.pool, &cache_key, &value)
+            .await
+            .unwrap();
+        let second_published = update_and_publish_if_changed(&test_db.pool, &cache_key, &value)
+            .await
+            .unwrap();
+        publish_cache_update(&test_db.pool, &done_key).await;
+
+        let mut notifications = 0;
+        loop {
+            let notification = listener.recv().await.unwrap();
+            if notification.payload() == done_key.to_db_key() {
+                break;
+            }
+            if notification.payload() == cache_key.to_db_key() {
+                notifications += 1;
+            }
+        }
+        kv_store::delete_value(&test_db.pool, cache_key.to_db_key()).await;
+
+        assert!(first_published);
+        assert!(!second_published);
+        assert_eq!(notifications, 1);
+    }
+
+    #[tokio::test]
+    async fn set_unserializable_value_test() {
+        let test_db = db::db::tests::TestDb::new().await;
+        // json object keys have to be strings
+        let value = std::collections::HashMap::from([((1, 2), 3)]);
+
+        // a key no other test sets, so an empty key means the failed write left it alone
+        let cache_key = CacheKey::SupplyProjectionInputs;
+        let result = set_value(&test_db.pool, &cache_key, value).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            kv_store::get_value(&test_db.pool, cache_key.to_db_key()).await,
+            None
+        );
+    }
+
+    #[test]
+    fn cache_durations_test() {
+        assert_eq!(
+            CacheKey::BaseFeePerGas.cache_durations(),
+            (Duration::seconds(6), Duration::seconds(120))
+        );
+        assert!(
+            CacheKey::SupplySinceMerge.cache_durations().0
+                > CacheKey::BaseFeePerGas.cache_durations().0
+        );
+    }
+
     #[test]
     fn parse_base_fees_time_frame_test() {
         assert_eq!(
@@ -300,4 +471,33 @@ mod tests {
             CacheKey::BaseFeePerGasStatsTimeFrame(TimeFrame::Growing(GrowingTimeFrame::SinceMerge))
         );
     }
+    #[test]
+    fn blob_fee_per_gas_stats_time_frame_round_trip_test() {
+        for time_frame in enum_iterator::all::<TimeFrame>() {
+            let key = CacheKey::BlobFeePerGasStatsTimeFrame(time_frame);
+            assert_eq!(key.to_db_key().parse::<CacheKey>().unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn db_key_round_trip_test() {
+        for key in enum_iterator::all::<CacheKey>() {
+            assert_eq!(key.to_db_key().parse::<CacheKey>().unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn parse_mev_bid_stats_time_frame_test() {
+        let key = "mev-bid-stats-d7".parse::<CacheKey>().unwrap();
+        assert_eq!(
+            key,
+            CacheKey::MevBidStatsTimeFrame(TimeFrame::Limited(LimitedTimeFrame::Day7))
+        );
+        assert_eq!(key.to_db_key(), "mev-bid-stats-d7");
+    }
+
+    #[test]
+    fn parse_unknown_blob_fees_time_frame_test() {
+        assert!("blob-fee-per-gas-stats-d2".parse::<CacheKey>().is_err());
+    }
 }
@@ -1,3 +1,10 @@
+mod check_aggregated_sums;
 mod check_blocks_gaps;
 mod check_beacon_state_gaps;
+mod compare_state_roots;
+pub use check_aggregated_sums::{
+    check_aggregated_sums, diff_sums, find_aggregated_sum_differences,
+    AggregatedSum, SumDifference, GWEI_NATIVE_TOLERANCE,
+};
 pub use check_beacon_state_gaps::check_beacon_state_gaps;
+pub use compare_state_roots::{compare_state_roots_stream, StateRootComparison};
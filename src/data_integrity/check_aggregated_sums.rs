@@ -0,0 +1,151 @@
+use anyhow::Result;
+use futures::TryStreamExt;
+use sqlx::PgExecutor;
+use tracing::{error, info};
+
+use crate::{beacon_chain::Slot, db::db, units::GweiNewtype};
+
+// aggregates are summed in gwei, the same unit they are stored in, so they should match
+// exactly. comparisons against amounts converted from wei should allow for rounding
+pub const GWEI_NATIVE_TOLERANCE: GweiNewtype = GweiNewtype(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AggregatedSum {
+    Deposits,
+    Withdrawals,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SumDifference {
+    pub sum: AggregatedSum,
+    pub slot: Slot,
+    pub stored: GweiNewtype,
+    pub expected: GweiNewtype,
+}
+
+// the differences between stored and expected sums larger than `tolerance`
+pub fn diff_sums(
+    sum: AggregatedSum,
+    stored_and_expected: impl IntoIterator<Item = (Slot, GweiNewtype, GweiNewtype)>,
+    tolerance: GweiNewtype,
+) -> Vec<SumDifference> {
+    stored_and_expected
+        .into_iter()
+        .filter(|(_, stored, expected)| {
+            // widened so sums near the bounds of i64 can't overflow
+            (stored.0 as i128 - expected.0 as i128).abs() > tolerance.0 as i128
+        })
+        .map(|(slot, stored, expected)| SumDifference {
+            sum,
+            slot,
+            stored,
+            expected,
+        })
+        .collect()
+}
+
+// every block's aggregates should be those of the block before it plus the block's own sums.
+// returns the blocks for which that is off by more than `tolerance`
+pub async fn find_aggregated_sum_differences(
+    executor: impl PgExecutor<'_>,
+    tolerance: GweiNewtype,
+) -> Result<Vec<SumDifference>> {
+    let mut rows = sqlx::query!(
+        r#"
+        SELECT
+            slot AS "slot!",
+            deposit_sum_aggregated AS "deposit_sum_aggregated!",
+            expected_deposit_sum_aggregated AS "expected_deposit_sum_aggregated!",
+            withdrawal_sum_aggregated AS "withdrawal_sum_aggregated!",
+            expected_withdrawal_sum_aggregated AS "expected_withdrawal_sum_aggregated!"
+        FROM (
+            SELECT
+                beacon_states.slot,
+                beacon_blocks.deposit_sum_aggregated,
+                LAG(beacon_blocks.deposit_sum_aggregated) OVER (ORDER BY beacon_states.slot)
+                    + beacon_blocks.deposit_sum AS expected_deposit_sum_aggregated,
+                COALESCE(beacon_blocks.withdrawal_sum_aggregated, 0) AS withdrawal_sum_aggregated,
+                LAG(COALESCE(beacon_blocks.withdrawal_sum_aggregated, 0)) OVER (ORDER BY beacon_states.slot)
+                    + COALESCE(beacon_blocks.withdrawal_sum, 0) AS expected_withdrawal_sum_aggregated
+            FROM
+                beacon_blocks
+            JOIN beacon_states ON
+                beacon_blocks.state_root = beacon_states.state_root
+        ) AS aggregates
+        -- the first block has no parent to check against
+        WHERE expected_deposit_sum_aggregated IS NOT NULL
+        ORDER BY slot
+        "#
+    )
+    .fetch(executor);
+
+    let mut differences = vec![];
+    while let Some(row) = rows.try_next().await? {
+        let slot = Slot(row.slot);
+        differences.extend(diff_sums(
+            AggregatedSum::Deposits,
+            [(
+                slot,
+                GweiNewtype(row.deposit_sum_aggregated),
+                GweiNewtype(row.expected_deposit_sum_aggregated),
+            )],
+            tolerance,
+        ));
+        differences.extend(diff_sums(
+            AggregatedSum::Withdrawals,
+            [(
+                slot,
+                GweiNewtype(row.withdrawal_sum_aggregated),
+                GweiNewtype(row.expected_withdrawal_sum_aggregated),
+            )],
+            tolerance,
+        ));
+    }
+
+    Ok(differences)
+}
+
+pub async fn check_aggregated_sums(tolerance: GweiNewtype) -> Result<()> {
+    info!(%tolerance, "checking aggregated deposit and withdrawal sums");
+
+    let mut connection = db::get_db_connection("check-aggregated-sums").await;
+    let differences =
+        find_aggregated_sum_differences(&mut connection, tolerance).await?;
+    for difference in &differences {
+        error!(?difference, "aggregated sum does not add up");
+    }
+
+    info!(
+        differences = differences.len(),
+        "done checking aggregated sums"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_sums_tolerance_test() {
+        let sums = [(
+            Slot(100),
+            GweiNewtype(32_000_000_001),
+            GweiNewtype(32_000_000_000),
+        )];
+
+        assert_eq!(
+            diff_sums(AggregatedSum::Deposits, sums, GWEI_NATIVE_TOLERANCE),
+            vec![SumDifference {
+                sum: AggregatedSum::Deposits,
+                slot: Slot(100),
+                stored: GweiNewtype(32_000_000_001),
+                expected: GweiNewtype(32_000_000_000),
+            }]
+        );
+        assert_eq!(
+            diff_sums(AggregatedSum::Deposits, sums, GweiNewtype(1)),
+            vec![]
+        );
+    }
+}
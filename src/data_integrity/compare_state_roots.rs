@@ -0,0 +1,154 @@
+use anyhow::Result;
+use futures::{stream, Stream, StreamExt};
+use sqlx::PgPool;
+
+use crate::beacon_chain::{self, BeaconNode, Slot, SlotRange, StateRoot};
+
+#[derive(Debug, PartialEq)]
+pub struct StateRootComparison {
+    pub slot: Slot,
+    pub stored_state_root: Option<StateRoot>,
+    pub on_chain_state_root: Option<StateRoot>,
+}
+
+impl StateRootComparison {
+    // a slot we have nothing stored for does not match, whatever the chain says
+    pub fn is_match(&self) -> bool {
+        self.stored_state_root.is_some()
+            && self.stored_state_root == self.on_chain_state_root
+    }
+}
+
+// how many slots' stored state roots to read from the db in one query
+const STORED_STATE_ROOTS_CHUNK: i32 = 10000;
+
+// compares the stored state root of every slot in the range with the one on chain, asking the
+// beacon node about up to `concurrency` slots at a time. stored roots are read a chunk of
+// slots at a time. comparisons come out in slot order
+pub fn compare_state_roots_stream<'a>(
+    db_pool: &'a PgPool,
+    beacon_node: &'a impl BeaconNode,
+    slot_range: SlotRange,
+    concurrency: usize,
+) -> impl Stream<Item = Result<StateRootComparison>> + 'a {
+    stream::iter(chunks(slot_range, STORED_STATE_ROOTS_CHUNK))
+        .then(move |chunk| async move {
+            let stored_state_roots = beacon_chain::get_state_roots_in_range(
+                db_pool,
+                chunk.greater_than_or_equal(),
+                chunk.less_than_or_equal(),
+            )
+            .await;
+            (chunk, stored_state_roots)
+        })
+        .flat_map(move |(chunk, mut stored_state_roots)| {
+            stream::iter(chunk)
+                .map(move |slot| {
+                    let stored_state_root = stored_state_roots.remove(&slot.0);
+                    async move {
+                        let on_chain_state_root =
+                            beacon_node.get_state_root_by_slot(slot).await?;
+                        Ok(StateRootComparison {
+                            slot,
+                            stored_state_root,
+                            on_chain_state_root,
+                        })
+                    }
+                })
+                .buffered(concurrency)
+        })
+}
+
+// consecutive ranges of at most `size` slots covering `slot_range`
+fn chunks(slot_range: SlotRange, size: i32) -> impl Iterator<Item = SlotRange> {
+    let last = slot_range.less_than_or_equal();
+    (slot_range.greater_than_or_equal().0..=last.0)
+        .step_by(size as usize)
+        .map(move |first| {
+            let chunk_last = Slot(first.saturating_add(size - 1)).min(last);
+            SlotRange::new(Slot(first), chunk_last)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::beacon_chain::{store_state, MockBeaconNode};
+    use crate::db::db::tests::TestDb;
+
+    #[test]
+    fn chunks_test() {
+        let chunks = chunks(SlotRange::new(Slot(5), Slot(14)), 4)
+            .map(|chunk| {
+                (chunk.greater_than_or_equal(), chunk.less_than_or_equal())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            chunks,
+            vec![
+                (Slot(5), Slot(8)),
+                (Slot(9), Slot(12)),
+                (Slot(13), Slot(14)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_state_roots_stream_test() {
+        let test_db = TestDb::new().await;
+        let first_slot = Slot(i32::MAX - 2300);
+        let last_slot = first_slot + 3;
+
+        // the first two slots match, the third was reorged, the last was never stored
+        store_state(&test_db.pool, "0xcompare_state_roots_0", first_slot).await;
+        store_state(&test_db.pool, "0xcompare_state_roots_1", first_slot + 1)
+            .await;
+        store_state(&test_db.pool, "0xcompare_state_roots_stale", first_slot + 2)
+            .await;
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_state_root_by_slot()
+            .returning(move |slot| {
+                Ok(Some(format!(
+                    "0xcompare_state_roots_{}",
+                    slot.0 - first_slot.0
+                )))
+            });
+
+        let comparisons = compare_state_roots_stream(
+            &test_db.pool,
+            &beacon_node,
+            SlotRange::new(first_slot, last_slot),
+            2,
+        )
+        .try_collect::<Vec<_>>()
+        .await;
+
+        beacon_chain::rollback_slot_range(
+            &mut test_db.pool.acquire().await.unwrap(),
+            first_slot,
+            last_slot,
+        )
+        .await
+        .unwrap();
+
+        let matches = comparisons
+            .unwrap()
+            .iter()
+            .map(|comparison| (comparison.slot, comparison.is_match()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matches,
+            vec![
+                (first_slot, true),
+                (first_slot + 1, true),
+                (first_slot + 2, false),
+                (first_slot + 3, false),
+            ]
+        );
+    }
+}
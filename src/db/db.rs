@@ -2,6 +2,56 @@ use crate::env::ENV_CONFIG;
 use sqlx::{
     postgres::PgPoolOptions, Connection, Executor, PgConnection, PgPool,
 };
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+// SQLSTATE postgres reports when a transaction could not be serialized against a concurrent one
+const SERIALIZATION_FAILURE_CODE: &str = "40001";
+
+// attempts including the first one, after which a serialization failure is returned to the caller
+const MAX_SERIALIZATION_FAILURE_ATTEMPTS: u32 = 3;
+
+// counts every retry caused by a serialization failure since the process started
+static SERIALIZATION_FAILURE_RETRIES: AtomicU64 = AtomicU64::new(0);
+
+pub fn serialization_failure_retries() -> u64 {
+    SERIALIZATION_FAILURE_RETRIES.load(Ordering::Relaxed)
+}
+
+fn is_serialization_failure(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|error| error.as_database_error())
+        .and_then(|error| error.code())
+        .is_some_and(|code| code == SERIALIZATION_FAILURE_CODE)
+}
+
+// runs the transaction in `f`, running it again when postgres aborts it with a serialization
+// failure, e.g. when sync and heal touch the same slots concurrently.
+// `f` should begin and commit its own transaction so every attempt starts from scratch.
+pub async fn with_retry_on_serialization_failure<T, F, Fut>(
+    mut f: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Err(error)
+                if attempt < MAX_SERIALIZATION_FAILURE_ATTEMPTS
+                    && is_serialization_failure(&error) =>
+            {
+                SERIALIZATION_FAILURE_RETRIES.fetch_add(1, Ordering::Relaxed);
+                warn!(attempt, "serialization failure, retrying transaction");
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
 pub async fn get_db_pool(name: &str, max_connections: u32) -> PgPool {
     let name_query = format!("SET application_name = '{}'; ", name);
     PgPoolOptions::new()
@@ -86,5 +136,118 @@ pub mod tests {
 
             Self { pool, name }
         }
+
+        // `new` hands out a pool on the shared db, where tests keep to their own slots.
+        // tests whose rollbacks delete every slot past a point would wipe the slots of
+        // tests running alongside, so they get a database of their own with the schema
+        // applied from the migrations
+        pub async fn new_isolated() -> Self {
+            let name = format!("testdb_{}", nanoid!(15, &ALPHABET));
+
+            let mut connection = get_test_db_connection().await;
+            sqlx::query(&format!("CREATE DATABASE {name}"))
+                .execute(&mut connection)
+                .await
+                .unwrap();
+
+            let pool = PgPoolOptions::new()
+                .max_connections(5)
+                .max_lifetime(std::time::Duration::from_secs(20))
+                .connect(&ENV_CONFIG.test_db_url.replace("testdb", &name))
+                .await
+                .unwrap();
+            apply_migrations(&pool).await;
+
+            Self { pool, name }
+        }
+    }
+
+    // runs every up migration in file name order, each in a transaction of its own
+    async fn apply_migrations(pool: &PgPool) {
+        let migrations_dir =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let mut migrations = std::fs::read_dir(migrations_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".up.sql"))
+            .collect::<Vec<_>>();
+        migrations.sort();
+
+        for migration in migrations {
+            let sql = std::fs::read_to_string(&migration).unwrap();
+            let mut transaction = pool.begin().await.unwrap();
+            transaction
+                .execute(sql.as_str())
+                .await
+                .unwrap_or_else(|error| {
+                    panic!("failed to apply {}: {error}", migration.display())
+                });
+            transaction.commit().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn new_isolated_test() {
+        let test_db = TestDb::new_isolated().await;
+
+        let state_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM beacon_states")
+                .fetch_one(&test_db.pool)
+                .await
+                .unwrap();
+
+        assert_eq!(state_count, 0);
+    }
+
+    // transactions run under READ COMMITTED never fail to serialize, so postgres is made to
+    // raise the error code itself. it reaches the retry as a real sqlx database error
+    async fn fail_with_serialization_failure(
+        connection: &mut PgConnection,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "DO $$ BEGIN RAISE EXCEPTION 'could not serialize access' USING ERRCODE = '40001'; END $$",
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retry_on_serialization_failure_test() {
+        let connection = tokio::sync::Mutex::new(get_test_db_connection().await);
+        let retries_before = serialization_failure_retries();
+        let mut attempts = 0;
+
+        let result = with_retry_on_serialization_failure(|| {
+            attempts += 1;
+            let first_attempt = attempts == 1;
+            let connection = &connection;
+            async move {
+                if first_attempt {
+                    fail_with_serialization_failure(&mut *connection.lock().await)
+                        .await?;
+                }
+                Ok(attempts)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert!(serialization_failure_retries() > retries_before);
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up_on_other_errors_test() {
+        let mut attempts = 0;
+
+        let result: anyhow::Result<()> =
+            with_retry_on_serialization_failure(|| {
+                attempts += 1;
+                async { Err(anyhow::anyhow!("not a serialization failure")) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
     }
 }
@@ -0,0 +1,134 @@
+use std::io::Write;
+use std::iter;
+
+use futures::{stream, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgExecutor;
+
+use crate::beacon_chain::{GweiInTime, Slot};
+
+// the points of a published `Series`, its meta is not part of the csv
+#[derive(Deserialize)]
+struct CachedSeries {
+    data: Vec<GweiInTime>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedState {
+    slot: i32,
+    state_root: String,
+    effective_balance_sum: Option<i64>,
+}
+
+// write every beacon state in [from, to] to `writer` as one JSON object per line, in slot
+// order. rows are streamed from the db, so any range can be exported without holding it
+// in memory. returns the number of lines written
+pub async fn export_beacon_states_jsonl(
+    executor: impl PgExecutor<'_>,
+    from: Slot,
+    to: Slot,
+    writer: &mut impl Write,
+) -> anyhow::Result<usize> {
+    let mut rows = sqlx::query_as!(
+        ExportedState,
+        "
+        SELECT
+            slot,
+            state_root,
+            effective_balance_sum
+        FROM
+            beacon_states
+        WHERE slot >= $1 AND slot <= $2
+        ORDER BY slot
+        ",
+        from.0,
+        to.0
+    )
+    .fetch(executor);
+
+    let mut line_count = 0;
+    while let Some(state) = rows.try_next().await? {
+        serde_json::to_writer(&mut *writer, &state)?;
+        writer.write_all(b"\n")?;
+        line_count += 1;
+    }
+    writer.flush()?;
+
+    Ok(line_count)
+}
+
+// the daily validator balances series as `timestamp,gwei` csv, timestamps in unix
+// seconds. built from the series the syncer publishes to the cache, so serving it costs
+// no db query. each line is written out as the response body asks for it, the csv as a
+// whole is never held in memory
+pub fn validator_balances_csv(
+    series: &Value,
+) -> anyhow::Result<impl Stream<Item = anyhow::Result<Vec<u8>>>> {
+    let series = CachedSeries::deserialize(series)?;
+
+    let header = csv_record(["timestamp", "gwei"]);
+    let rows = series.data.into_iter().map(|balance| {
+        csv_record([balance.t.to_string(), balance.v.to_string()])
+    });
+    Ok(stream::iter(iter::once(header).chain(rows)))
+}
+
+// a single csv line, quoted and terminated the way csv::Writer does it
+fn csv_record<I, F>(record: I) -> anyhow::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = F>,
+    F: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(record)?;
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db::tests::get_test_db_connection;
+    use sqlx::Connection;
+
+    #[tokio::test]
+    async fn export_beacon_states_jsonl_test() {
+        let mut connection = get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let first_slot = Slot(i32::MAX - 1400);
+        for slot in [first_slot, first_slot + 1, first_slot + 2, first_slot + 3] {
+            sqlx::query!(
+                "INSERT INTO beacon_states (state_root, slot) VALUES ($1, $2)",
+                format!("0xexport_{slot}"),
+                slot.0
+            )
+            .execute(&mut *transaction)
+            .await
+            .unwrap();
+        }
+
+        let mut output = vec![];
+        let line_count = export_beacon_states_jsonl(
+            &mut *transaction,
+            first_slot,
+            first_slot + 2,
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        let lines = String::from_utf8(output).unwrap();
+        let lines: Vec<Value> = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(line_count, 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["slot"], first_slot.0);
+        assert_eq!(
+            lines[2]["state_root"],
+            format!("0xexport_{}", first_slot + 2)
+        );
+        assert_eq!(lines[2]["effective_balance_sum"], Value::Null);
+    }
+}
@@ -1,3 +1,7 @@
 pub mod db;
+pub mod export;
 
-pub use db::{get_db_connection, get_db_pool};
+pub use db::{
+    get_db_connection, get_db_pool, serialization_failure_retries,
+    with_retry_on_serialization_failure,
+};
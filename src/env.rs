@@ -1,9 +1,11 @@
 //! Fns to read variables from the environment more conveniently and help other functions figure
 //! out what environment they're running in.
 
-use std::env;
+use std::{env, fmt::Display, str::FromStr};
 
+use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
+use thiserror::Error;
 use tracing::debug;
 
 const SECRET_LOG_BLACKLIST: [&str; 3] =
@@ -62,12 +64,130 @@ pub struct EnvConfig {
     // pub etherscan_api_key: Option<String>,
     // pub dune_api_key: Option<String>,
     // // Separate out geth deltas fork URL.
-    // pub geth_url: Option<String>,
+    // execution node JSON-RPC url
+    pub geth_url: Option<String>,
     // pub log_json: bool,
     pub log_perf: bool,
+    pub port: u16,
+    // the beacon state sync serves its prometheus metrics on this port
+    pub sync_metrics_port: u16,
+    pub beacon_timeout_seconds: u64,
+    pub max_sync_lag_slots: i64,
+    pub request_timeout_seconds: u64,
+    // slots are counted from it, set GENESIS_TIMESTAMP (RFC 3339) to run against a testnet
+    pub genesis_timestamp: DateTime<Utc>,
+    // env vars which are set but fail to parse, their default is used until validate
+    // reports them
+    pub invalid_vars: Vec<InvalidVar>,
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("{key}={value} ({reason})")]
+pub struct InvalidVar {
+    key: &'static str,
+    value: String,
+    reason: String,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigError {
+    #[error("missing required env vars: {}", .0.join(", "))]
+    MissingVars(Vec<&'static str>),
+    #[error("invalid env vars: {}", join_invalid_vars(.0))]
+    InvalidVars(Vec<InvalidVar>),
+}
+
+fn join_invalid_vars(invalid_vars: &[InvalidVar]) -> String {
+    invalid_vars
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl EnvConfig {
+    // check every field the running binary needs is set, reporting all missing vars at
+    // once instead of failing on the first one deep inside a running job
+    pub fn validate(&self, beacon_url_required: bool) -> Result<(), ConfigError> {
+        let mut missing_vars = vec![];
+        if self.db_url.is_empty() {
+            missing_vars.push("DATABASE_URL");
+        }
+        if beacon_url_required && self.beacon_url.is_none() {
+            missing_vars.push("BEACON_URL");
+        }
+
+        if !missing_vars.is_empty() {
+            Err(ConfigError::MissingVars(missing_vars))
+        } else if !self.invalid_vars.is_empty() {
+            Err(ConfigError::InvalidVars(self.invalid_vars.clone()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// meant to be the first thing a binary's main does, exits listing the missing env vars
+pub fn check_env_config(beacon_url_required: bool) {
+    if let Err(err) = ENV_CONFIG.validate(beacon_url_required) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+const DEFAULT_PORT: u16 = 3002;
+const DEFAULT_SYNC_METRICS_PORT: u16 = 3003;
+const DEFAULT_BEACON_TIMEOUT_SECONDS: u64 = 30;
+// an epoch
+const DEFAULT_MAX_SYNC_LAG_SLOTS: i64 = 32;
+const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+const MAINNET_GENESIS_TIMESTAMP: &str = "2020-12-01T12:00:23Z";
+
+fn mainnet_genesis_timestamp() -> DateTime<Utc> {
+    MAINNET_GENESIS_TIMESTAMP
+        .parse()
+        .expect("expect the mainnet genesis timestamp to be RFC 3339")
+}
+
+fn parse_var<T>(
+    key: &'static str,
+    value: Option<&str>,
+    default: T,
+) -> Result<T, InvalidVar>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.map_or(Ok(default), |value| {
+        value.parse().map_err(|err: T::Err| InvalidVar {
+            key,
+            value: value.to_string(),
+            reason: err.to_string(),
+        })
+    })
+}
+
+// reads and parses the env var, an unparsable value is recorded in invalid_vars and
+// replaced by the default
+fn get_env_var_or<T>(
+    key: &'static str,
+    default: T,
+    invalid_vars: &mut Vec<InvalidVar>,
+) -> T
+where
+    T: FromStr + Copy,
+    T::Err: Display,
+{
+    parse_var(key, get_env_var(key).as_deref(), default).unwrap_or_else(
+        |invalid_var| {
+            invalid_vars.push(invalid_var);
+            default
+        },
+    )
 }
 
 pub fn get_env_config() -> EnvConfig {
+    let mut invalid_vars = vec![];
     EnvConfig {
         beacon_url: Option::from("https://docs-demo.quiknode.pro".to_string()),
         //get_env_var("BEACON_URL"),
@@ -79,9 +199,36 @@ pub fn get_env_config() -> EnvConfig {
         //get_env_var("DATABASE_URL").unwrap_or("".to_string()),
         // etherscan_api_key: get_env_var("ETHERSCAN_API_KEY"),
         // dune_api_key: get_env_var("DUNE_API_KEY"),
-        // geth_url: get_env_var("GETH_URL"),
+        geth_url: get_env_var("GETH_URL"),
         // log_json: get_env_bool("LOG_JSON").unwrap_or(false),
         log_perf: false, //get_env_bool("LOG_PERF").unwrap_or(false),
+        port: get_env_var_or("PORT", DEFAULT_PORT, &mut invalid_vars),
+        sync_metrics_port: get_env_var_or(
+            "SYNC_METRICS_PORT",
+            DEFAULT_SYNC_METRICS_PORT,
+            &mut invalid_vars,
+        ),
+        beacon_timeout_seconds: get_env_var_or(
+            "BEACON_TIMEOUT_SECONDS",
+            DEFAULT_BEACON_TIMEOUT_SECONDS,
+            &mut invalid_vars,
+        ),
+        max_sync_lag_slots: get_env_var_or(
+            "MAX_SYNC_LAG_SLOTS",
+            DEFAULT_MAX_SYNC_LAG_SLOTS,
+            &mut invalid_vars,
+        ),
+        request_timeout_seconds: get_env_var_or(
+            "REQUEST_TIMEOUT_SECONDS",
+            DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            &mut invalid_vars,
+        ),
+        genesis_timestamp: get_env_var_or(
+            "GENESIS_TIMESTAMP",
+            mainnet_genesis_timestamp(),
+            &mut invalid_vars,
+        ),
+        invalid_vars,
     }
 }
 
@@ -148,4 +295,97 @@ mod tests {
             "my_normal_value"
         );
     }
+
+    #[test]
+    fn test_parse_var_default() {
+        assert_eq!(parse_var("PORT", None, DEFAULT_PORT), Ok(3002));
+    }
+
+    #[test]
+    fn test_parse_var_override() {
+        assert_eq!(parse_var("PORT", Some("3003"), DEFAULT_PORT), Ok(3003));
+    }
+
+    #[test]
+    fn test_parse_var_out_of_range() {
+        assert_eq!(
+            parse_var("PORT", Some("70000"), DEFAULT_PORT)
+                .unwrap_err()
+                .to_string(),
+            "PORT=70000 (number too large to fit in target type)"
+        );
+    }
+
+    #[test]
+    fn test_parse_var_genesis_timestamp() {
+        let mainnet_genesis = mainnet_genesis_timestamp();
+        let sepolia_genesis: DateTime<Utc> =
+            "2022-06-20T22:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            parse_var("GENESIS_TIMESTAMP", None, mainnet_genesis),
+            Ok(mainnet_genesis)
+        );
+        assert_eq!(
+            parse_var(
+                "GENESIS_TIMESTAMP",
+                Some("2022-06-20T22:00:00Z"),
+                mainnet_genesis
+            ),
+            Ok(sepolia_genesis)
+        );
+        assert!(
+            parse_var("GENESIS_TIMESTAMP", Some("yesterday"), mainnet_genesis)
+                .is_err()
+        );
+    }
+
+    fn test_config(db_url: &str, beacon_url: Option<&str>) -> EnvConfig {
+        EnvConfig {
+            beacon_url: beacon_url.map(str::to_string),
+            db_url: db_url.to_string(),
+            test_db_url: "".to_string(),
+            geth_url: None,
+            log_perf: false,
+            port: DEFAULT_PORT,
+            sync_metrics_port: DEFAULT_SYNC_METRICS_PORT,
+            beacon_timeout_seconds: DEFAULT_BEACON_TIMEOUT_SECONDS,
+            max_sync_lag_slots: DEFAULT_MAX_SYNC_LAG_SLOTS,
+            request_timeout_seconds: DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            genesis_timestamp: mainnet_genesis_timestamp(),
+            invalid_vars: vec![],
+        }
+    }
+
+    #[test]
+    fn test_validate_missing_vars() {
+        let config = test_config("", None);
+        assert_eq!(
+            config.validate(true),
+            Err(ConfigError::MissingVars(vec!["DATABASE_URL", "BEACON_URL"]))
+        );
+        assert_eq!(
+            config.validate(true).unwrap_err().to_string(),
+            "missing required env vars: DATABASE_URL, BEACON_URL"
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_vars() {
+        let mut config = test_config("postgresql://localhost/db", None);
+        let invalid_var =
+            parse_var("PORT", Some("abc"), DEFAULT_PORT).unwrap_err();
+        config.invalid_vars = vec![invalid_var];
+        assert_eq!(
+            config.validate(false).unwrap_err().to_string(),
+            "invalid env vars: PORT=abc (invalid digit found in string)"
+        );
+    }
+
+    #[test]
+    fn test_validate_beacon_url_not_required() {
+        let config = test_config("postgresql://localhost/db", None);
+        assert_eq!(config.validate(false), Ok(()));
+        assert!(config.validate(true).is_err());
+    }
 }
@@ -0,0 +1,160 @@
+use sqlx::PgExecutor;
+
+use crate::units::WeiNewtype;
+
+use super::{BlockNumber, ExecutionNodeBlock, LONDON_HARD_FORK_BLOCK_NUMBER};
+
+// EIP-1559 introduced the base fee, and with it the burn, in the London fork block
+pub fn is_post_london(block_number: BlockNumber) -> bool {
+    block_number >= LONDON_HARD_FORK_BLOCK_NUMBER
+}
+
+// the base fee every unit of gas used in the block paid is burned. a base fee above
+// u64::MAX wei times a full block already overflows i64, so this is done in u128
+pub fn compute_burn(block: &ExecutionNodeBlock) -> WeiNewtype {
+    // whatever base fee a block before London claims, nothing was burned in it
+    if !is_post_london(block.number) {
+        return WeiNewtype(0);
+    }
+
+    let base_fee_per_gas = u128::try_from(block.base_fee_per_gas.0)
+        .expect("expect base fee per gas to be positive");
+    let burn = base_fee_per_gas
+        .checked_mul(block.gas_used as u128)
+        .expect("expect block burn to fit in u128");
+    WeiNewtype(i128::try_from(burn).expect("expect block burn to fit in i128"))
+}
+
+// the base fee burned by all stored blocks in [from, to], blocks before London burned nothing
+pub async fn sum_burn(
+    executor: impl PgExecutor<'_>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> WeiNewtype {
+    // summed as NUMERIC and read as text, the sum of a range easily exceeds i64
+    let sum = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(base_fee_per_gas::NUMERIC * gas_used), 0)::TEXT AS "sum!"
+        FROM
+            blocks_next
+        WHERE
+            number >= $1 AND number <= $2
+        "#,
+        from.max(LONDON_HARD_FORK_BLOCK_NUMBER),
+        to
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .sum;
+
+    WeiNewtype(sum.parse().expect("expect burn sum to be an integer"))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use super::*;
+    use crate::db::db;
+
+    fn block(base_fee_per_gas: i128, gas_used: u64) -> ExecutionNodeBlock {
+        block_at(17_000_000, base_fee_per_gas, gas_used)
+    }
+
+    fn block_at(
+        number: BlockNumber,
+        base_fee_per_gas: i128,
+        gas_used: u64,
+    ) -> ExecutionNodeBlock {
+        ExecutionNodeBlock {
+            number,
+            hash: "0xcompute_burn".to_string(),
+            base_fee_per_gas: WeiNewtype(base_fee_per_gas),
+            gas_used,
+        }
+    }
+
+    #[test]
+    fn compute_burn_test() {
+        // 30 gwei on 12M gas
+        assert_eq!(
+            compute_burn(&block(30_000_000_000, 12_000_000)),
+            WeiNewtype(360_000_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn compute_burn_past_u64_test() {
+        let burn = compute_burn(&block(u64::MAX as i128, 30_000_000));
+
+        assert_eq!(burn, WeiNewtype(u64::MAX as i128 * 30_000_000));
+        assert!(burn.0 > u64::MAX as i128);
+    }
+
+    #[test]
+    fn compute_burn_london_guard_test() {
+        let burn_at =
+            |number| compute_burn(&block_at(number, 1_000_000_000, 21_000));
+
+        assert!(!is_post_london(LONDON_HARD_FORK_BLOCK_NUMBER - 1));
+        assert_eq!(burn_at(LONDON_HARD_FORK_BLOCK_NUMBER - 1), WeiNewtype(0));
+        assert!(is_post_london(LONDON_HARD_FORK_BLOCK_NUMBER));
+        assert_eq!(
+            burn_at(LONDON_HARD_FORK_BLOCK_NUMBER),
+            WeiNewtype(21_000_000_000_000)
+        );
+        assert!(is_post_london(LONDON_HARD_FORK_BLOCK_NUMBER + 1));
+        assert_eq!(
+            burn_at(LONDON_HARD_FORK_BLOCK_NUMBER + 1),
+            WeiNewtype(21_000_000_000_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn sum_burn_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // far beyond any block number synced to the test db
+        let first_block = i32::MAX - 100;
+        for (number, base_fee_per_gas, gas_used) in [
+            (first_block, 30_000_000_000_i64, 12_000_000),
+            (first_block + 1, 20_000_000_000, 15_000_000),
+            (first_block + 2, 10_000_000_000, 10_000_000),
+        ] {
+            sqlx::query!(
+                "
+                INSERT INTO blocks_next (
+                    base_fee_per_gas,
+                    difficulty,
+                    eth_price,
+                    gas_used,
+                    hash,
+                    number,
+                    parent_hash,
+                    timestamp,
+                    total_difficulty
+                )
+                VALUES ($1, 0, 0, $2, $3, $4, $5, NOW(), 0)
+                ",
+                base_fee_per_gas,
+                gas_used,
+                format!("0xsum_burn_{number}"),
+                number,
+                format!("0xsum_burn_{}", number - 1)
+            )
+            .execute(&mut *transaction)
+            .await
+            .unwrap();
+        }
+
+        let burn =
+            sum_burn(&mut *transaction, first_block, first_block + 1).await;
+
+        assert_eq!(
+            burn,
+            WeiNewtype(360_000_000_000_000_000 + 300_000_000_000_000_000)
+        );
+    }
+}
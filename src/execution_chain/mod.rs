@@ -1,4 +1,6 @@
+mod burn;
 mod node;
+mod supply_delta;
 
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
@@ -15,8 +17,7 @@ pub const TOTAL_TERMINAL_DIFFICULTY: u128 = 58750000000000000000000;
 // This number was recorded before we has a rigorous definition of how to combine the execution and
 // beacon chains to come up with a precise supply. After a rigorous supply is established for every
 // block and slot it would be good to update this number.
-#[allow(dead_code)]
-const MERGE_SLOT_SUPPLY: WeiNewtype = WeiNewtype(120_521_140_924_621_298_474_538_089);
+pub const MERGE_SLOT_SUPPLY: WeiNewtype = WeiNewtype(120_521_140_924_621_298_474_538_089);
 
 // Until we have an eth supply calculated by adding together per-block supply deltas, we're using
 // an estimate based on glassnode data.
@@ -33,5 +34,10 @@ lazy_static! {
         "2022-09-15T06:42:59Z".parse::<DateTime<Utc>>().unwrap();
 }
 
-pub use node::BlockHash;
+pub use burn::{compute_burn, is_post_london, sum_burn};
+pub use node::{
+    BlockHash, ExecutionNode, ExecutionNodeBlock, ExecutionNodeHttp,
+    MockExecutionNode,
+};
+pub use supply_delta::{compute_supply_delta, SupplyDelta};
 use crate::units::WeiNewtype;
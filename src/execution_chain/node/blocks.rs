@@ -1,2 +1,29 @@
+use serde::Deserialize;
+
+use crate::{
+    execution_chain::BlockNumber,
+    json_codecs::{i32_from_hex_string, u64_from_hex_string, wei_from_hex_string},
+    units::WeiNewtype,
+};
+
 ///! Hash for a block on the execution layer.
 pub type BlockHash = String;
+
+// blocks from before London have no base fee, nothing was burned in them
+fn no_base_fee() -> WeiNewtype {
+    WeiNewtype(0)
+}
+
+// the fields of an execution block the analyses need, as eth_getBlockByNumber returns them
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionNodeBlock {
+    #[serde(deserialize_with = "i32_from_hex_string")]
+    pub number: BlockNumber,
+    pub hash: BlockHash,
+    // every unit of gas used in the block burns this much
+    #[serde(default = "no_base_fee", deserialize_with = "wei_from_hex_string")]
+    pub base_fee_per_gas: WeiNewtype,
+    #[serde(deserialize_with = "u64_from_hex_string")]
+    pub gas_used: u64,
+}
@@ -1,2 +1,183 @@
+//! Talks to an execution node over JSON-RPC to get the blocks the analyses need.
 mod blocks;
-pub use blocks::BlockHash;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use mockall::automock;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::env::ENV_CONFIG;
+
+use super::BlockNumber;
+pub use blocks::{BlockHash, ExecutionNodeBlock};
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+// a missing result, like for a block that doesn't exist yet, comes back as null
+#[derive(Debug, Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+impl<T> JsonRpcResponse<T> {
+    fn into_result(self) -> Result<Option<T>> {
+        match self.error {
+            Some(JsonRpcError { code, message }) => {
+                Err(anyhow!("execution node error {code}: {message}"))
+            }
+            None => Ok(self.result),
+        }
+    }
+}
+
+#[automock]
+#[async_trait]
+pub trait ExecutionNode {
+    async fn get_block_by_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<ExecutionNodeBlock>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecutionNodeHttp {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl ExecutionNodeHttp {
+    pub fn new() -> Self {
+        let url = ENV_CONFIG
+            .geth_url
+            .as_ref()
+            .expect("GETH_URL is required in env to fetch execution blocks");
+        Self::new_with_url(url)
+    }
+
+    pub fn new_with_url(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Option<T>> {
+        self.client
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JsonRpcResponse<T>>()
+            .await?
+            .into_result()
+    }
+}
+
+impl Default for ExecutionNodeHttp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ExecutionNode for ExecutionNodeHttp {
+    async fn get_block_by_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<Option<ExecutionNodeBlock>> {
+        // false, the transactions as hashes only, we don't need their bodies
+        self.call(
+            "eth_getBlockByNumber",
+            json!([format!("{block_number:#x}"), false]),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::WeiNewtype;
+    use tokio::task;
+
+    const SAMPLE_BLOCK_RESPONSE: &str = r#"{
+        "jsonrpc": "2.0",
+        "id": 1,
+        "result": {
+            "baseFeePerGas": "0x4a817c800",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0xe4e1c0",
+            "hash": "0x6b1b7f0b8d4a6dbf1c48e2e0f9c7f1b8d3e8f5c6a0a1b2c3d4e5f60718293a4b",
+            "number": "0x1036640",
+            "timestamp": "0x643dcb9b",
+            "transactions": []
+        }
+    }"#;
+
+    #[test]
+    fn decode_block_by_number_response_test() {
+        let response: JsonRpcResponse<ExecutionNodeBlock> =
+            serde_json::from_str(SAMPLE_BLOCK_RESPONSE).unwrap();
+
+        assert_eq!(
+            response.into_result().unwrap(),
+            Some(ExecutionNodeBlock {
+                number: 17_000_000,
+                hash: "0x6b1b7f0b8d4a6dbf1c48e2e0f9c7f1b8d3e8f5c6a0a1b2c3d4e5f60718293a4b"
+                    .to_string(),
+                base_fee_per_gas: WeiNewtype(20_000_000_000),
+                gas_used: 15_000_000,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_pre_london_block_test() {
+        let block: ExecutionNodeBlock = serde_json::from_str(
+            r#"{ "number": "0xc5d487", "hash": "0xpre_london", "gasUsed": "0x5208" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(block.base_fee_per_gas, WeiNewtype(0));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn get_block_by_number_test() {
+        let mut server =
+            task::spawn_blocking(mockito::Server::new).await.unwrap();
+        let mock = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "method": "eth_getBlockByNumber",
+                "params": ["0x1036640", false],
+            })))
+            .with_body(SAMPLE_BLOCK_RESPONSE)
+            .create();
+        let execution_node = ExecutionNodeHttp::new_with_url(&server.url());
+
+        let block = execution_node
+            .get_block_by_number(17_000_000)
+            .await
+            .unwrap()
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(block.gas_used, 15_000_000);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{beacon_chain::Slot, units::WeiNewtype};
+
+use super::{compute_burn, ExecutionNodeBlock};
+
+// how much the supply changed in a slot, summing these up from genesis gives the supply
+#[derive(Debug, PartialEq)]
+pub struct SupplyDelta {
+    pub slot: Slot,
+    pub issuance_wei: WeiNewtype,
+    pub burn_wei: WeiNewtype,
+    pub net_wei: WeiNewtype,
+}
+
+// combine the beacon chain issuance for a slot with the base fee burned by its execution
+// block. slots before the merge and missed slots have no execution block, so burn nothing
+pub fn compute_supply_delta(
+    slot: Slot,
+    block: Option<&ExecutionNodeBlock>,
+    issuance_per_slot: WeiNewtype,
+) -> SupplyDelta {
+    let burn_wei = block.map_or(WeiNewtype(0), compute_burn);
+
+    SupplyDelta {
+        slot,
+        issuance_wei: issuance_per_slot,
+        burn_wei,
+        net_wei: issuance_per_slot - burn_wei,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUANCE_PER_SLOT: WeiNewtype = WeiNewtype(23_000_000_000_000_000);
+
+    #[test]
+    fn pre_merge_supply_delta_test() {
+        let supply_delta =
+            compute_supply_delta(Slot(4_000_000), None, ISSUANCE_PER_SLOT);
+
+        assert_eq!(
+            supply_delta,
+            SupplyDelta {
+                slot: Slot(4_000_000),
+                issuance_wei: ISSUANCE_PER_SLOT,
+                burn_wei: WeiNewtype(0),
+                net_wei: ISSUANCE_PER_SLOT,
+            }
+        );
+    }
+
+    #[test]
+    fn post_merge_supply_delta_test() {
+        let block = ExecutionNodeBlock {
+            number: 17_000_000,
+            hash: "0xpost_merge_supply_delta".to_string(),
+            base_fee_per_gas: WeiNewtype(20_000_000_000),
+            gas_used: 15_000_000,
+        };
+
+        let supply_delta = compute_supply_delta(
+            Slot(6_000_000),
+            Some(&block),
+            ISSUANCE_PER_SLOT,
+        );
+
+        // 20 gwei base fee on 15M gas burns 0.3 ETH, more than the slot issued
+        assert_eq!(supply_delta.burn_wei, WeiNewtype(300_000_000_000_000_000));
+        assert_eq!(
+            supply_delta.net_wei,
+            WeiNewtype(23_000_000_000_000_000 - 300_000_000_000_000_000)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! The eth side of the flippening comparison shown on the frontend.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    beacon_chain::{Slot, FIRST_POST_MERGE_SLOT},
+    caching::{self, CacheKey},
+    execution_chain::MERGE_SLOT_SUPPLY,
+    units::{GweiNewtype, WeiNewtype},
+};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FlippeningData {
+    pub eth_supply_wei: WeiNewtype,
+    pub timestamp: DateTime<Utc>,
+}
+
+// estimate the eth supply at the last slot with a stored issuance by adding the beacon
+// issuance since the merge to the supply recorded at the merge. burn is not tracked yet, so
+// this overestimates the supply by everything burned since the merge.
+// issuance stored before the merge is taken as the baseline, when there is none we assume
+// issuance tracking started at the merge. returns None while no issuance has been stored
+pub async fn compute_flippening_data(
+    executor: impl PgExecutor<'_>,
+) -> Option<FlippeningData> {
+    sqlx::query!(
+        r#"
+        SELECT
+            beacon_states.slot,
+            beacon_issuance.gwei,
+            (
+                SELECT
+                    merge_issuance.gwei
+                FROM
+                    beacon_issuance AS merge_issuance
+                JOIN beacon_states AS merge_states ON
+                    merge_issuance.state_root = merge_states.state_root
+                WHERE
+                    merge_states.slot < $1
+                ORDER BY
+                    merge_states.slot DESC
+                LIMIT 1
+            ) AS "merge_gwei?"
+        FROM
+            beacon_issuance
+        JOIN beacon_states ON
+            beacon_issuance.state_root = beacon_states.state_root
+        ORDER BY
+            beacon_states.slot DESC
+        LIMIT 1
+        "#,
+        FIRST_POST_MERGE_SLOT.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| {
+        let issuance_since_merge =
+            GweiNewtype(row.gwei) - GweiNewtype(row.merge_gwei.unwrap_or(0));
+
+        FlippeningData {
+            eth_supply_wei: MERGE_SLOT_SUPPLY
+                + WeiNewtype::from(issuance_since_merge),
+            timestamp: Slot(row.slot).date_time(),
+        }
+    })
+}
+
+pub async fn update_flippening_data(
+    db_pool: &PgPool,
+) -> Result<(), serde_json::Error> {
+    match compute_flippening_data(db_pool).await {
+        Some(flippening_data) => {
+            debug!(?flippening_data, "publishing flippening data");
+            caching::update_and_publish(
+                db_pool,
+                &CacheKey::FlippeningData,
+                flippening_data,
+            )
+            .await?;
+        }
+        None => {
+            debug!("no issuance stored yet, skipping flippening data")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Acquire, PgConnection};
+
+    use super::*;
+    use crate::{beacon_chain::store_state, db::db};
+
+    async fn store_test_issuance(
+        executor: &mut PgConnection,
+        slot: Slot,
+        gwei: i64,
+    ) {
+        let state_root = format!("0xflippening_test_{}", slot.0);
+        store_state(&mut *executor, &state_root, slot).await;
+        sqlx::query!(
+            "
+            INSERT INTO beacon_issuance (timestamp, state_root, gwei)
+            VALUES ($1, $2, $3)
+            ",
+            slot.date_time(),
+            state_root,
+            gwei
+        )
+        .execute(executor)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn compute_flippening_data_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // far beyond slots other tests store so these are the latest issuances
+        let slot = Slot(i32::MAX - 2600);
+
+        store_test_issuance(&mut transaction, slot, 1_000).await;
+        let first = compute_flippening_data(&mut *transaction).await.unwrap();
+        store_test_issuance(&mut transaction, slot + 1, 3_000).await;
+        let second = compute_flippening_data(&mut *transaction).await.unwrap();
+
+        assert_eq!(first.timestamp, slot.date_time());
+        assert_eq!(second.timestamp, (slot + 1).date_time());
+        assert_eq!(
+            second.eth_supply_wei - first.eth_supply_wei,
+            WeiNewtype::from(GweiNewtype(2_000))
+        );
+    }
+}
@@ -5,6 +5,7 @@ use axum::{
 use reqwest::StatusCode;
 use serde_json::json;
 
+#[derive(Clone, Debug)]
 pub enum HealthStatus {
     Healthy(Option<String>),
     UnHealthy(Option<String>),
@@ -14,6 +15,47 @@ pub trait HealthCheckable {
     fn health_status(&self) -> HealthStatus;
 }
 
+// a status that was already determined, e.g. by an async check, can be combined with others
+impl HealthCheckable for HealthStatus {
+    fn health_status(&self) -> HealthStatus {
+        self.clone()
+    }
+}
+
+// healthy only when every check is healthy. the messages of the failing checks, or of all
+// checks when everything is fine, are joined into one
+pub struct CompositeHealth<'a>(Vec<&'a dyn HealthCheckable>);
+
+impl<'a> CompositeHealth<'a> {
+    pub fn new(checks: Vec<&'a dyn HealthCheckable>) -> Self {
+        Self(checks)
+    }
+}
+
+impl HealthCheckable for CompositeHealth<'_> {
+    fn health_status(&self) -> HealthStatus {
+        let mut healthy_messages = vec![];
+        let mut unhealthy_messages = vec![];
+        for check in &self.0 {
+            match check.health_status() {
+                HealthStatus::Healthy(message) => healthy_messages.extend(message),
+                HealthStatus::UnHealthy(message) => {
+                    unhealthy_messages.extend(message)
+                }
+            }
+        }
+
+        let join = |messages: Vec<String>| {
+            (!messages.is_empty()).then(|| messages.join("; "))
+        };
+        if unhealthy_messages.is_empty() {
+            HealthStatus::Healthy(join(healthy_messages))
+        } else {
+            HealthStatus::UnHealthy(join(unhealthy_messages))
+        }
+    }
+}
+
 impl IntoResponse for HealthStatus {
     fn into_response(self) -> Response {
         match self {
@@ -34,3 +76,23 @@ impl IntoResponse for HealthStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_health_test() {
+        let healthy = HealthStatus::Healthy(Some("db ok".to_string()));
+        let unhealthy = HealthStatus::UnHealthy(Some("sync behind".to_string()));
+
+        assert!(matches!(
+            CompositeHealth::new(vec![&healthy, &healthy]).health_status(),
+            HealthStatus::Healthy(Some(message)) if message == "db ok; db ok"
+        ));
+        assert!(matches!(
+            CompositeHealth::new(vec![&healthy, &unhealthy]).health_status(),
+            HealthStatus::UnHealthy(Some(message)) if message == "sync behind"
+        ));
+    }
+}
@@ -1,8 +1,23 @@
-use crate::kv_store::KvStore;
+use crate::kv_store::{KvStore, TypedKvStore};
 ///! Job Progress
 ///! This module is designed for tracking the progress of long executed jobs.
 ///! Each tracked long executed job is stored to cache by its DB key and its progress value.
-use serde::{de::DeserializeOwned, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+// progress is stored either as the bare value, or together with when it was stored
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredProgress<A> {
+    Timestamped { value: A, updated_at: DateTime<Utc> },
+    Plain(A),
+}
+
+#[derive(Serialize)]
+struct TimestampedProgress<'a, A> {
+    value: &'a A,
+    updated_at: DateTime<Utc>,
+}
 
 pub struct JobProgress<'a, A: Serialize + DeserializeOwned> {
     key: &'static str,
@@ -10,7 +25,7 @@ pub struct JobProgress<'a, A: Serialize + DeserializeOwned> {
     phantom: std::marker::PhantomData<A>,
 }
 
-impl<A: Serialize + DeserializeOwned> JobProgress<'_, A> {
+impl<A: Serialize + DeserializeOwned + Send + Sync> JobProgress<'_, A> {
     pub fn new<'a>(
         key: &'static str,
         key_value_store: &'a impl KvStore,
@@ -22,16 +37,167 @@ impl<A: Serialize + DeserializeOwned> JobProgress<'_, A> {
         }
     }
 
-    pub async fn get(&self) -> Option<A> {
-        self.key_value_store
-            .get(self.key)
-            .await
-            .map(|value| serde_json::from_value(value).unwrap())
+    async fn get_stored(
+        &self,
+    ) -> Result<Option<StoredProgress<A>>, serde_json::Error> {
+        self.key_value_store.get_typed(self.key).await
     }
 
-    pub async fn set(&self, value: &A) {
+    pub async fn get(&self) -> Result<Option<A>, serde_json::Error> {
+        let stored_progress = self.get_stored().await?;
+        Ok(stored_progress.map(|stored_progress| match stored_progress {
+            StoredProgress::Timestamped { value, .. } => value,
+            StoredProgress::Plain(value) => value,
+        }))
+    }
+
+    pub async fn set(&self, value: &A) -> Result<(), serde_json::Error> {
+        self.key_value_store.set_typed(self.key, value).await
+    }
+
+    // like set, but also records when the job got here, so stuck jobs can be spotted
+    pub async fn set_with_timestamp(
+        &self,
+        value: &A,
+    ) -> Result<(), serde_json::Error> {
+        let timestamped_progress = TimestampedProgress {
+            value,
+            updated_at: Utc::now(),
+        };
         self.key_value_store
-            .set(self.key, &serde_json::to_value(value).unwrap())
+            .set_typed(self.key, &timestamped_progress)
             .await
     }
+
+    // when progress was last stored with set_with_timestamp, None when there is no progress,
+    // it was stored without a timestamp, or it can't be read
+    pub async fn get_last_updated(&self) -> Option<DateTime<Utc>> {
+        match self.get_stored().await {
+            Ok(Some(StoredProgress::Timestamped { updated_at, .. })) => {
+                Some(updated_at)
+            }
+            _ => None,
+        }
+    }
+
+    // forget the tracked progress, e.g. once a job finished and should start over next time
+    pub async fn clear(&self) {
+        self.key_value_store.delete(self.key).await
+    }
+}
+
+impl JobProgress<'_, i64> {
+    // adds `by` to the stored count, starting from 0, and returns the new total. the read and
+    // the write are separate, two jobs incrementing the same key at once can lose an increment,
+    // callers have to make sure only one of them writes at a time
+    pub async fn increment(&self, by: i64) -> Result<i64, serde_json::Error> {
+        let total = self.get().await?.unwrap_or(0) + by;
+        self.set(&total).await?;
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon_chain::Slot;
+    use crate::db::db::tests::TestDb;
+    use crate::kv_store::KVStorePostgres;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct InMemoryKvStore(Mutex<HashMap<String, Value>>);
+
+    #[async_trait]
+    impl KvStore for InMemoryKvStore {
+        async fn get(&self, key: &str) -> Option<Value> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        async fn get_many(&self, keys: &[&str]) -> HashMap<String, Value> {
+            let values = self.0.lock().unwrap();
+            keys.iter()
+                .filter_map(|key| {
+                    values.get(*key).map(|value| (key.to_string(), value.clone()))
+                })
+                .collect()
+        }
+
+        async fn set(&self, key: &str, value: &Value) {
+            self.0.lock().unwrap().insert(key.to_string(), value.clone());
+        }
+
+        async fn delete(&self, key: &str) {
+            self.0.lock().unwrap().remove(key);
+        }
+    }
+
+    #[tokio::test]
+    async fn in_memory_round_trip_test() {
+        let kv_store = InMemoryKvStore::default();
+        let job_progress: JobProgress<'_, Slot> =
+            JobProgress::new("job-progress-in-memory-test", &kv_store);
+
+        assert_eq!(job_progress.get().await.unwrap(), None);
+
+        job_progress.set(&Slot(6)).await.unwrap();
+        assert_eq!(job_progress.get().await.unwrap(), Some(Slot(6)));
+        assert!(kv_store.get("job-progress-in-memory-test").await.is_some());
+
+        job_progress.clear().await;
+        assert_eq!(job_progress.get().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn set_with_timestamp_test() {
+        let test_db = TestDb::new().await;
+        let kv_store = KVStorePostgres::new(test_db.pool.clone());
+        let job_progress: JobProgress<'_, Slot> =
+            JobProgress::new("job-progress-set-with-timestamp-test", &kv_store);
+
+        let before = Utc::now();
+        job_progress.set_with_timestamp(&Slot(4)).await.unwrap();
+        let after = Utc::now();
+
+        let progress = job_progress.get().await.unwrap();
+        let last_updated = job_progress.get_last_updated().await;
+        job_progress.clear().await;
+
+        assert_eq!(progress, Some(Slot(4)));
+        let last_updated = last_updated.unwrap();
+        assert!(before <= last_updated && last_updated <= after);
+    }
+
+    #[tokio::test]
+    async fn set_without_timestamp_test() {
+        let test_db = TestDb::new().await;
+        let kv_store = KVStorePostgres::new(test_db.pool.clone());
+        let job_progress: JobProgress<'_, Slot> = JobProgress::new(
+            "job-progress-set-without-timestamp-test",
+            &kv_store,
+        );
+
+        job_progress.set(&Slot(5)).await.unwrap();
+
+        let progress = job_progress.get().await.unwrap();
+        let last_updated = job_progress.get_last_updated().await;
+        job_progress.clear().await;
+
+        assert_eq!(progress, Some(Slot(5)));
+        assert_eq!(last_updated, None);
+    }
+
+    #[tokio::test]
+    async fn increment_test() {
+        let kv_store = InMemoryKvStore::default();
+        let job_progress: JobProgress<'_, i64> =
+            JobProgress::new("job-progress-increment-test", &kv_store);
+
+        assert_eq!(job_progress.increment(3).await.unwrap(), 3);
+        assert_eq!(job_progress.increment(4).await.unwrap(), 7);
+        assert_eq!(job_progress.get().await.unwrap(), Some(7));
+    }
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Deserializer};
 
+use crate::units::{EthNewtype, GweiNewtype, WeiNewtype};
+
 pub fn i32_from_string<'de, D>(deserializer: D) -> Result<i32, D::Error>
 where
     D: Deserializer<'de>,
@@ -9,6 +11,113 @@ where
     Ok(num_i32)
 }
 
+pub fn opt_i32_from_string<'de, D>(
+    deserializer: D,
+) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+    s.map(|s| s.parse::<i32>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+// execution node JSON-RPC encodes quantities as 0x prefixed hex
+fn parse_hex_quantity(s: &str) -> Result<u128, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| format!("hex quantity missing 0x prefix: {s}"))?;
+    u128::from_str_radix(digits, 16).map_err(|e| format!("{e}: {s}"))
+}
+
+pub fn i32_from_hex_string<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex_quantity(&s)
+        .and_then(|quantity| i32::try_from(quantity).map_err(|e| e.to_string()))
+        .map_err(serde::de::Error::custom)
+}
+
+pub fn u64_from_hex_string<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex_quantity(&s)
+        .and_then(|quantity| u64::try_from(quantity).map_err(|e| e.to_string()))
+        .map_err(serde::de::Error::custom)
+}
+
+pub fn wei_from_hex_string<'de, D>(
+    deserializer: D,
+) -> Result<WeiNewtype, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex_quantity(&s)
+        .and_then(|quantity| i128::try_from(quantity).map_err(|e| e.to_string()))
+        .map(WeiNewtype)
+        .map_err(serde::de::Error::custom)
+}
+
+// gwei have 9 decimals in an ETH amount
+const GWEI_DECIMALS: usize = 9;
+
+// parses a decimal ETH amount like "1.5" into gwei without going through a float.
+// digits past the ninth decimal have to be zero, anything else is sub-gwei precision
+// GweiNewtype can't hold.
+fn parse_gwei_from_eth_str(s: &str) -> Result<GweiNewtype, String> {
+    let (negative, amount) = match s.strip_prefix('-') {
+        Some(amount) => (true, amount),
+        None => (false, s),
+    };
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty())
+        || !is_digits(whole)
+        || !is_digits(fraction)
+    {
+        return Err(format!("invalid ETH amount: {s}"));
+    }
+
+    let (gwei_digits, sub_gwei_digits) =
+        fraction.split_at(fraction.len().min(GWEI_DECIMALS));
+    if sub_gwei_digits.chars().any(|c| c != '0') {
+        return Err(format!("ETH amount has sub-gwei precision: {s}"));
+    }
+
+    let whole_gwei = match whole {
+        "" => 0,
+        whole => whole.parse::<i64>().map_err(|e| e.to_string())?,
+    }
+    .checked_mul(EthNewtype::GWEI_PER_ETH);
+    let fraction_gwei = match gwei_digits {
+        "" => 0,
+        gwei_digits => {
+            format!("{gwei_digits:0<GWEI_DECIMALS$}").parse::<i64>().unwrap()
+        }
+    };
+    let gwei = whole_gwei
+        .and_then(|whole_gwei| whole_gwei.checked_add(fraction_gwei))
+        .ok_or_else(|| format!("ETH amount too large: {s}"))?;
+
+    Ok(GweiNewtype(if negative { -gwei } else { gwei }))
+}
+
+pub fn gwei_from_eth_string<'de, D>(
+    deserializer: D,
+) -> Result<GweiNewtype, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_gwei_from_eth_str(&s).map_err(serde::de::Error::custom)
+}
+
 #[cfg(test)]
 mod tests {
     use serde::{Serialize, Serializer};
@@ -56,4 +165,30 @@ mod tests {
         .unwrap();
         assert_eq!(actual, expected);
     }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Bid {
+        #[serde(deserialize_with = "gwei_from_eth_string")]
+        value: GweiNewtype,
+    }
+
+    #[test]
+    fn deserialize_gwei_from_eth_string_test() {
+        let actual = serde_json::from_str::<Bid>(r#"{ "value": "1.5" }"#).unwrap();
+        assert_eq!(actual.value, GweiNewtype(1_500_000_000));
+    }
+
+    #[test]
+    fn deserialize_one_gwei_from_eth_string_test() {
+        let actual =
+            serde_json::from_str::<Bid>(r#"{ "value": "0.000000001" }"#).unwrap();
+        assert_eq!(actual.value, GweiNewtype(1));
+    }
+
+    #[test]
+    fn deserialize_sub_gwei_eth_string_test() {
+        let actual =
+            serde_json::from_str::<Bid>(r#"{ "value": "0.0000000015" }"#);
+        assert!(actual.is_err());
+    }
 }
@@ -1,7 +1,8 @@
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
 
 pub async fn get_value(
     executor: impl PgExecutor<'_>,
@@ -20,6 +21,27 @@ pub async fn get_value(
     .and_then(|row| row.value)
 }
 
+// fetch several keys in one round trip, keys without a (non-null) value are left out of the map
+pub async fn get_values(
+    executor: impl PgExecutor<'_>,
+    keys: &[&str],
+) -> HashMap<String, Value> {
+    let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    sqlx::query!(
+        "
+        SELECT key, value FROM key_value_store
+        WHERE key = ANY($1)
+        ",
+        &keys,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter_map(|row| row.value.map(|value| (row.key, value)))
+    .collect()
+}
+
 pub async fn set_value(
     executor: impl PgExecutor<'_>,
     key: &str,
@@ -39,10 +61,62 @@ pub async fn set_value(
     .unwrap();
 }
 
+pub async fn delete_value(executor: impl PgExecutor<'_>, key: &str) {
+    sqlx::query!(
+        "
+        DELETE FROM key_value_store
+        WHERE key = $1
+        ",
+        key
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 #[async_trait]
-pub trait KvStore {
+pub trait KvStore: Send + Sync {
     async fn get(&self, key: &str) -> Option<Value>;
+    async fn get_many(&self, keys: &[&str]) -> HashMap<String, Value>;
     async fn set(&self, key: &str, value: &Value);
+    async fn delete(&self, key: &str);
+}
+
+// typed get and set on top of any KvStore, values are (de)serialized with serde_json.
+// generic methods would stop KvStore from being usable as `dyn KvStore`, that's why they
+// live in this extension trait which every KvStore, including `dyn KvStore`, implements
+#[async_trait]
+pub trait TypedKvStore {
+    async fn get_typed<T: DeserializeOwned + Send>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_json::Error>;
+
+    async fn set_typed<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), serde_json::Error>;
+}
+
+#[async_trait]
+impl<S: KvStore + ?Sized> TypedKvStore for S {
+    async fn get_typed<T: DeserializeOwned + Send>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_json::Error> {
+        self.get(key).await.map(serde_json::from_value).transpose()
+    }
+
+    async fn set_typed<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        self.set(key, &value).await;
+        Ok(())
+    }
 }
 
 pub struct KVStorePostgres {
@@ -84,9 +158,17 @@ impl KvStore for KVStorePostgres {
         get_value(&self.db_pool, key).await
     }
 
+    async fn get_many(&self, keys: &[&str]) -> HashMap<String, Value> {
+        get_values(&self.db_pool, keys).await
+    }
+
     async fn set(&self, key: &str, value: &Value) {
         set_value(&self.db_pool, key, value).await
     }
+
+    async fn delete(&self, key: &str) {
+        delete_value(&self.db_pool, key).await
+    }
 }
 
 #[cfg(test)]
@@ -147,6 +229,70 @@ mod tests {
         assert_eq!(test_json_from_db, None)
     }
 
+    #[tokio::test]
+    async fn delete_value_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        set_value(&mut *transaction, "test-delete-key", &json!("value")).await;
+        delete_value(&mut *transaction, "test-delete-key").await;
+
+        let value = get_value(&mut *transaction, "test-delete-key").await;
+        assert_eq!(value, None)
+    }
+
+    #[tokio::test]
+    async fn get_many_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let store = KVStorePostgres::new(test_db.pool.clone());
+        let keys = ["test-many-key-1", "test-many-key-2", "test-many-key-3"];
+
+        for (i, key) in keys.iter().enumerate() {
+            store.set(key, &json!(i)).await;
+        }
+
+        let values = store.get_many(&keys).await;
+
+        for key in keys {
+            store.delete(key).await;
+        }
+
+        assert_eq!(values.len(), 3);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(values[*key], json!(i));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_set_typed_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let store = KVStorePostgres::new(test_db.pool.clone());
+        let test_json = TestJson {
+            name: "alex".to_string(),
+            age: 29,
+        };
+
+        store.set_typed("test-typed-key", &test_json).await.unwrap();
+        let value = store.get_typed::<TestJson>("test-typed-key").await;
+        store.delete("test-typed-key").await;
+
+        assert_eq!(value.unwrap(), Some(test_json));
+    }
+
+    #[tokio::test]
+    async fn get_typed_malformed_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let store = KVStorePostgres::new(test_db.pool.clone());
+
+        store
+            .set("test-typed-malformed-key", &json!({ "name": 29 }))
+            .await;
+        let value = store.get_typed::<TestJson>("test-typed-malformed-key").await;
+        store.delete("test-typed-malformed-key").await;
+
+        assert!(value.is_err());
+    }
+
     #[tokio::test]
     async fn test_set_and_get_value() {
         let test_db = db::tests::TestDb::new().await;
@@ -1,6 +1,9 @@
 pub mod kv_store;
 
+pub use kv_store::delete_value;
 pub use kv_store::get_value;
+pub use kv_store::get_values;
 pub use kv_store::set_value;
 pub use kv_store::KVStorePostgres;
 pub use kv_store::KvStore;
+pub use kv_store::TypedKvStore;
@@ -1,18 +1,23 @@
 pub mod beacon_chain;
 pub mod db;
 pub mod env;
-mod execution_chain;
+pub mod execution_chain;
 pub mod job;
 pub mod json_codecs;
 pub mod kv_store;
-mod performance;
+pub mod performance;
+pub mod run_all;
 pub mod server;
+pub mod rewards;
+pub mod series;
+pub mod supply;
 pub mod units;
 pub mod caching;
 pub mod time_frames;
 pub mod health;
 pub mod data_integrity;
 pub mod mev_blocks;
+pub mod flippening;
 
 
 pub use data_integrity::check_beacon_state_gaps;
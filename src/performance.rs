@@ -1,4 +1,6 @@
+use lazy_static::lazy_static;
 use pin_project::pin_project;
+use prometheus::{proto::MetricFamily, HistogramOpts, HistogramVec, Registry};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -7,6 +9,28 @@ use tracing::debug;
 
 use crate::env::ENV_CONFIG;
 
+lazy_static! {
+    // every prometheus metric in the crate registers here, served by the server on /metrics
+    pub static ref REGISTRY: Registry = Registry::new();
+    static ref FUTURE_DURATION_SECONDS: HistogramVec = {
+        let histogram = HistogramVec::new(
+            HistogramOpts::new(
+                "future_duration_seconds",
+                "time futures wrapped with timed_metric took to complete",
+            ),
+            &["label"],
+        )
+        .unwrap();
+        REGISTRY.register(Box::new(histogram.clone())).unwrap();
+        histogram
+    };
+}
+
+// collects all metrics registered with the global registry, ready to be encoded for prometheus
+pub fn gather_metrics() -> Vec<MetricFamily> {
+    REGISTRY.gather()
+}
+
 /// A wrapper around a Future which adds timing data.
 #[pin_project]
 pub struct Timed<Fut>
@@ -17,6 +41,7 @@ where
     inner: Fut,
     name: String,
     start: Option<Instant>,
+    record_metric: bool,
 }
 
 impl<Fut> Future for Timed<Fut>
@@ -35,10 +60,15 @@ where
 
             // If the inner future is done, measure the elapsed time and finish this wrapper future.
             Poll::Ready(v) => {
+                let elapsed = start.elapsed();
                 if ENV_CONFIG.log_perf {
-                    let elapsed = start.elapsed();
                     debug!("{} took {:.2?}", this.name, elapsed);
                 }
+                if *this.record_metric {
+                    FUTURE_DURATION_SECONDS
+                        .with_label_values(&[this.name])
+                        .observe(elapsed.as_secs_f64());
+                }
                 Poll::Ready(v)
             }
         }
@@ -51,9 +81,45 @@ pub trait TimedExt: Sized + Future {
             inner: self,
             name: name.to_string(),
             start: None,
+            record_metric: false,
+        }
+    }
+
+    // like `timed`, but also records the duration in the `future_duration_seconds` histogram
+    fn timed_metric(self, label: &str) -> Timed<Self> {
+        Timed {
+            inner: self,
+            name: label.to_string(),
+            start: None,
+            record_metric: true,
         }
     }
 }
 
 // All futures can use the `.timed` method defined above
 impl<F: Future> TimedExt for F {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timed_metric_records_duration_test() {
+        let label = "timed_metric_records_duration_test";
+        let sample_count = || {
+            FUTURE_DURATION_SECONDS
+                .with_label_values(&[label])
+                .get_sample_count()
+        };
+        let sample_count_before = sample_count();
+
+        async {}.timed_metric(label).await;
+
+        assert_eq!(sample_count(), sample_count_before + 1);
+        let metric_names: Vec<String> = gather_metrics()
+            .iter()
+            .map(|family| family.get_name().to_string())
+            .collect();
+        assert!(metric_names.contains(&"future_duration_seconds".to_string()));
+    }
+}
@@ -0,0 +1,99 @@
+//! Validator rewards derived from the per-slot issuance deltas in supply_deltas.
+
+use std::collections::BTreeMap;
+
+use sqlx::PgExecutor;
+
+use crate::{beacon_chain::Slot, units::GweiNewtype};
+
+const SLOTS_PER_EPOCH: i32 = 32;
+
+// issuance per epoch for every epoch in [from_epoch, to_epoch] we have deltas for, in
+// ascending epoch order. issuance is what validators earned, net of deposits and withdrawals
+pub async fn rewards_by_epoch(
+    executor: impl PgExecutor<'_>,
+    from_epoch: i32,
+    to_epoch: i32,
+) -> Vec<(i32, GweiNewtype)> {
+    let first_slot = from_epoch.saturating_mul(SLOTS_PER_EPOCH);
+    let last_slot = to_epoch
+        .saturating_mul(SLOTS_PER_EPOCH)
+        .saturating_add(SLOTS_PER_EPOCH - 1);
+
+    let rows = sqlx::query!(
+        "
+        SELECT slot, issuance_delta
+        FROM supply_deltas
+        WHERE slot >= $1 AND slot <= $2
+        ",
+        first_slot,
+        last_slot
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap();
+
+    rows.into_iter()
+        .fold(BTreeMap::new(), |mut rewards, row| {
+            *rewards.entry(Slot(row.slot).epoch()).or_insert(0) +=
+                row.issuance_delta;
+            rewards
+        })
+        .into_iter()
+        .map(|(epoch, gwei)| (epoch, GweiNewtype(gwei)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Connection, PgConnection};
+
+    use super::*;
+    use crate::{db::db, supply::store_supply_delta};
+
+    async fn store_test_state(executor: &mut PgConnection, slot: Slot) {
+        sqlx::query!(
+            "
+            INSERT INTO beacon_states (state_root, slot)
+            VALUES ($1, $2)
+            ",
+            format!("0xrewards_by_epoch_test_{}", slot.0),
+            slot.0
+        )
+        .execute(executor)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rewards_by_epoch_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // far beyond epochs other tests store deltas for
+        let epoch = (i32::MAX - 1000) / SLOTS_PER_EPOCH;
+        let deltas = [
+            (Slot(epoch * SLOTS_PER_EPOCH), 10),
+            (Slot(epoch * SLOTS_PER_EPOCH + 31), 20),
+            (Slot((epoch + 1) * SLOTS_PER_EPOCH + 5), 7),
+        ];
+
+        for (slot, issuance_delta) in deltas {
+            store_test_state(&mut transaction, slot).await;
+            store_supply_delta(
+                &mut *transaction,
+                slot,
+                &GweiNewtype(issuance_delta),
+                &GweiNewtype(0),
+            )
+            .await;
+        }
+
+        let rewards =
+            rewards_by_epoch(&mut *transaction, epoch, epoch + 1).await;
+
+        assert_eq!(
+            rewards,
+            vec![(epoch, GweiNewtype(30)), (epoch + 1, GweiNewtype(7))]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Runs the whole backend, the server and the beacon state sync, in a single process. Meant
+//! for local development, in production both run as their own bins.
+
+use std::future::Future;
+
+use anyhow::{anyhow, Result};
+use futures::{future, try_join};
+use tracing::info;
+
+use crate::{
+    beacon_chain::sync_beacon_states, server::start_server_with_shutdown,
+};
+
+// drive the server and the sync side by side until either fails or shutdown resolves.
+// the server never returns on its own, if it does anyway we treat it as a failure so the
+// sync doesn't keep running without anything serving its results
+pub async fn run_until_shutdown(
+    server: impl Future<Output = ()>,
+    sync: impl Future<Output = Result<()>>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let server = async {
+        server.await;
+        Err::<(), _>(anyhow!("server stopped unexpectedly"))
+    };
+
+    tokio::select! {
+        result = async { try_join!(server, sync) } => result.map(|_| ()),
+        _ = shutdown => {
+            // dropping the services' futures stops them at their next await point
+            info!("received shutdown signal, stopping server and sync");
+            Ok(())
+        }
+    }
+}
+
+pub async fn run_all() -> Result<()> {
+    info!("running server and beacon state sync");
+    // the server doesn't listen for ctrl-c itself here, shutdown below stops both services
+    let server = start_server_with_shutdown(future::pending());
+    run_until_shutdown(server, sync_beacon_states(), async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("expect to be able to listen for ctrl-c");
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    #[tokio::test]
+    async fn shutdown_stops_both_services_test() {
+        let result = run_until_shutdown(
+            future::pending(),
+            future::pending(),
+            future::ready(()),
+        )
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn sync_error_stops_run_test() {
+        let result = run_until_shutdown(
+            future::pending(),
+            future::ready(Err(anyhow!("sync failed"))),
+            future::pending(),
+        )
+        .await;
+
+        assert_eq!(result.unwrap_err().to_string(), "sync failed");
+    }
+
+    #[tokio::test]
+    async fn server_exit_stops_run_test() {
+        let result = run_until_shutdown(
+            future::ready(()),
+            future::pending(),
+            future::pending(),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Time series responses, the points together with the range they cover.
+
+use serde::Serialize;
+
+use crate::beacon_chain::GweiInTime;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SeriesMeta {
+    // timestamps of the first and last point, None for an empty series
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub count: usize,
+    // whether older points were dropped to stay within the limit
+    pub truncated: bool,
+}
+
+#[derive(Serialize)]
+pub struct Series {
+    pub data: Vec<GweiInTime>,
+    pub meta: SeriesMeta,
+}
+
+impl Series {
+    // `points` in ascending time order, with a limit only the most recent `limit` points are kept
+    pub fn new(mut points: Vec<GweiInTime>, limit: Option<usize>) -> Self {
+        let truncated = limit.is_some_and(|limit| points.len() > limit);
+        if let Some(limit) = limit.filter(|_| truncated) {
+            points.drain(..points.len() - limit);
+        }
+
+        let meta = SeriesMeta {
+            from: points.first().map(|point| point.t),
+            to: points.last().map(|point| point.t),
+            count: points.len(),
+            truncated,
+        };
+
+        Self { data: points, meta }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(timestamps: &[u64]) -> Vec<GweiInTime> {
+        timestamps
+            .iter()
+            .map(|&t| GweiInTime { t, v: t as i64 })
+            .collect()
+    }
+
+    #[test]
+    fn series_meta_test() {
+        let series = Series::new(points(&[100, 200, 300]), None);
+
+        assert_eq!(
+            series.meta,
+            SeriesMeta {
+                from: Some(100),
+                to: Some(300),
+                count: 3,
+                truncated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn series_truncated_to_limit_test() {
+        let series = Series::new(points(&[100, 200, 300, 400]), Some(2));
+
+        assert_eq!(
+            series.meta,
+            SeriesMeta {
+                from: Some(300),
+                to: Some(400),
+                count: 2,
+                truncated: true,
+            }
+        );
+        assert_eq!(
+            series.data.iter().map(|point| point.t).collect::<Vec<_>>(),
+            vec![300, 400]
+        );
+        assert_eq!(
+            serde_json::to_value(&series).unwrap()["meta"],
+            serde_json::json!({
+                "from": 300,
+                "to": 400,
+                "count": 2,
+                "truncated": true,
+            })
+        );
+    }
+
+    #[test]
+    fn empty_series_test() {
+        let series = Series::new(vec![], Some(2));
+
+        assert_eq!(
+            series.meta,
+            SeriesMeta {
+                from: None,
+                to: None,
+                count: 0,
+                truncated: false,
+            }
+        );
+    }
+}
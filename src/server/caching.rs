@@ -1,20 +1,23 @@
-use super::{State, StateExtension};
+use super::{metrics, State, StateExtension};
 use crate::caching::{CacheKey, ParseCacheKeyError};
 use crate::{
     caching,
+    db::export,
     env::ENV_CONFIG,
     kv_store::{KVStorePostgres, KvStore},
 };
 use axum::{
+    body::StreamBody,
+    extract::Path,
     http::{HeaderMap, HeaderValue},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Extension, Json,
 };
 use chrono::Duration;
 use enum_iterator::all;
 use futures::{Stream, TryStreamExt};
-use lazy_static::lazy_static;
 use reqwest::{header, StatusCode};
+use serde::Serialize;
 use serde_json::Value;
 use sqlx::{postgres::PgNotification, PgPool};
 use std::{
@@ -27,6 +30,44 @@ use tracing::{debug, info, trace, warn};
 
 pub struct Cache(RwLock<HashMap<CacheKey, Value>>);
 
+// why a requested key is not in the cache, served as a JSON body so clients can tell
+// a key that will never be there apart from one that is still being computed
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "error", content = "cache_key", rename_all = "snake_case")]
+pub enum CacheMissError {
+    #[error("no producer publishes cache key {0}")]
+    NoProducer(#[serde(serialize_with = "serialize_cache_key")] CacheKey),
+    #[error("cache key {0} is not available yet")]
+    NotYetAvailable(#[serde(serialize_with = "serialize_cache_key")] CacheKey),
+}
+
+fn serialize_cache_key<S: serde::Serializer>(
+    cache_key: &CacheKey,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(cache_key.to_db_key())
+}
+
+impl From<CacheKey> for CacheMissError {
+    fn from(cache_key: CacheKey) -> Self {
+        if cache_key.has_producer() {
+            Self::NotYetAvailable(cache_key)
+        } else {
+            Self::NoProducer(cache_key)
+        }
+    }
+}
+
+impl IntoResponse for CacheMissError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            Self::NoProducer(_) => StatusCode::NOT_FOUND,
+            Self::NotYetAvailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
 impl Default for Cache {
     fn default() -> Self {
         Self::new()
@@ -38,22 +79,40 @@ impl Cache {
         Self(RwLock::new(HashMap::new()))
     }
 
-    async fn load_from_db(&self, kv_store: &impl KvStore) {}
+    // warm up the cache with every known key in a single query
+    async fn load_from_db(&self, kv_store: &impl KvStore) {
+        let db_keys: Vec<&str> =
+            all::<CacheKey>().map(|key| key.to_db_key()).collect();
+        let values = kv_store.get_many(&db_keys).await;
+
+        let mut cache = self.0.write().unwrap();
+        for key in all::<CacheKey>() {
+            if let Some(value) = values.get(key.to_db_key()) {
+                cache.insert(key, value.clone());
+            }
+        }
+        debug!(count = cache.len(), "loaded cache values from db");
+    }
 
     pub async fn new_with_data(kv_store: &impl KvStore) -> Self {
         let cache: Cache = Self::new();
         cache.load_from_db(kv_store).await;
         cache
     }
+
+    pub fn key_count(&self) -> usize {
+        self.0.read().unwrap().len()
+    }
+
+    pub fn get(&self, cache_key: &CacheKey) -> Option<Value> {
+        self.0.read().unwrap().get(cache_key).cloned()
+    }
 }
 
-pub async fn cached_get_with_custom_duration(
-    Extension(state): StateExtension,
-    analysis_cache_key: &CacheKey,
+fn cache_control_headers(
     max_age: &Duration,
-    // s_max_age: Option<u32>,
     stale_while_revalidate: &Duration,
-) -> impl IntoResponse {
+) -> HeaderMap {
     let mut headers = HeaderMap::new();
 
     headers.insert(
@@ -66,32 +125,105 @@ pub async fn cached_get_with_custom_duration(
         .unwrap(),
     );
 
+    headers
+}
+
+pub async fn cached_get_with_custom_duration(
+    Extension(state): StateExtension,
+    analysis_cache_key: &CacheKey,
+    max_age: &Duration,
+    // s_max_age: Option<u32>,
+    stale_while_revalidate: &Duration,
+) -> impl IntoResponse {
+    let headers = cache_control_headers(max_age, stale_while_revalidate);
+
     match state.cache.0.read().unwrap().get(analysis_cache_key) {
-        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+        None => CacheMissError::from(*analysis_cache_key).into_response(),
         Some(cached_value) => {
             (headers, Json(cached_value).into_response()).into_response()
         }
     }
 }
 
-lazy_static! {
-    static ref SIX_SECONDS: Duration = Duration::seconds(6);
-    static ref TWO_MINUTES: Duration = Duration::seconds(120);
-}
-
 pub async fn cached_get(
     state: StateExtension,
     analysis_cache_key: &CacheKey,
 ) -> impl IntoResponse {
+    let (max_age, stale_while_revalidate) =
+        analysis_cache_key.cache_durations();
     cached_get_with_custom_duration(
         state,
         analysis_cache_key,
-        &SIX_SECONDS,
-        &TWO_MINUTES,
+        &max_age,
+        &stale_while_revalidate,
     )
     .await
 }
 
+// serves /api/v2/fees/<db key>, a name that is no cache key at all is a plain 404
+pub async fn cached_get_by_db_key(
+    state: StateExtension,
+    Path(db_key): Path<String>,
+) -> Response {
+    match all::<CacheKey>().find(|cache_key| cache_key.to_db_key() == db_key) {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(cache_key) => cached_get(state, &cache_key).await.into_response(),
+    }
+}
+
+// the cached daily validator balances as csv, with the same cache headers as the json
+pub async fn cached_validator_balances_csv(
+    Extension(state): StateExtension,
+) -> Response {
+    let cache_key = CacheKey::ValidatorBalancesByDay;
+    let Some(series) = state.cache.get(&cache_key) else {
+        return CacheMissError::from(cache_key).into_response();
+    };
+
+    match export::validator_balances_csv(&series) {
+        Ok(csv) => {
+            let (max_age, stale_while_revalidate) = cache_key.cache_durations();
+            let mut headers =
+                cache_control_headers(&max_age, &stale_while_revalidate);
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv"),
+            );
+            (headers, StreamBody::new(csv)).into_response()
+        }
+        Err(err) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+        }
+    }
+}
+
+// every cached value in a single object keyed by db key, so the dashboard needs only one
+// request. the response may only be cached as long as its fastest changing value
+pub async fn cached_get_all(Extension(state): StateExtension) -> impl IntoResponse {
+    let cache = state.cache.0.read().unwrap();
+
+    let (max_age, stale_while_revalidate) = cache
+        .keys()
+        .map(CacheKey::cache_durations)
+        .min()
+        .unwrap_or_else(|| {
+            all::<CacheKey>()
+                .map(|key| key.cache_durations())
+                .min()
+                .expect("expect at least one cache key")
+        });
+
+    let values: serde_json::Map<String, Value> = cache
+        .iter()
+        .map(|(key, value)| (key.to_db_key().to_string(), value.clone()))
+        .collect();
+
+    (
+        cache_control_headers(&max_age, &stale_while_revalidate),
+        Json(values),
+    )
+}
+
 async fn process_notifications(
     mut notification_stream: impl Stream<Item = Result<PgNotification, sqlx::Error>>
         + Unpin,
@@ -116,14 +248,16 @@ async fn process_notifications(
                 .await;
                 if let Some(value) = value {
                     state.cache.0.write().unwrap().insert(cache_key, value);
+                    // a successfully applied update is what keeps the
+                    // server's health check green
+                    state.health.set_cache_updated();
+                    metrics::inc_cache_updates_applied();
                 } else {
                     warn!(
                         %cache_key,
                         "got a message to update our served cache, but DB had no value to give"
                     );
                 }
-
-                // todo: update state health status
             }
         }
     }
@@ -147,3 +281,107 @@ pub async fn update_cache_from_notifications(
             .await;
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db::tests::TestDb;
+    use crate::health::{HealthCheckable, HealthStatus};
+    use crate::server::health::ServerHealth;
+    use chrono::Utc;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn cache_update_notification_refreshes_health_test() {
+        let test_db = TestDb::new().await;
+
+        // started long enough ago that only a cache update can keep us healthy
+        let state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now() - Duration::minutes(10)),
+        });
+        assert!(matches!(
+            state.health.health_status(),
+            HealthStatus::UnHealthy(_)
+        ));
+
+        let update_cache_thread =
+            update_cache_from_notifications(state.clone(), &test_db.pool)
+                .await;
+
+        caching::update_and_publish(
+            &test_db.pool,
+            &CacheKey::EffectiveBalanceSum,
+            json!({ "sum": 1 }),
+        )
+        .await
+        .unwrap();
+
+        // the notification is processed on another task, give it some time
+        let mut health_status = state.health.health_status();
+        for _ in 0..50 {
+            if let HealthStatus::Healthy(_) = health_status {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            health_status = state.health.health_status();
+        }
+        update_cache_thread.abort();
+
+        match health_status {
+            HealthStatus::Healthy(Some(message)) => {
+                assert!(message.contains("updated in last 5 minutes"))
+            }
+            _ => panic!("expect cache update to mark the server healthy"),
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_get_uses_cache_key_durations_test() {
+        let test_db = TestDb::new().await;
+        let state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+        let expected_cache_controls = [
+            (
+                CacheKey::BaseFeePerGas,
+                "public, max-age=6, stale-while-revalidate=120",
+            ),
+            (
+                CacheKey::IssuanceEstimate,
+                "public, max-age=6, stale-while-revalidate=120",
+            ),
+            (
+                CacheKey::SupplySinceMerge,
+                "public, max-age=60, stale-while-revalidate=600",
+            ),
+            // slow changing keys may be cached longer
+            (
+                CacheKey::SupplyOverTime,
+                "public, max-age=600, stale-while-revalidate=3600",
+            ),
+        ];
+        for (cache_key, _) in expected_cache_controls {
+            state
+                .cache
+                .0
+                .write()
+                .unwrap()
+                .insert(cache_key, json!({ "value": 1 }));
+        }
+
+        for (cache_key, expected_cache_control) in expected_cache_controls {
+            let response = cached_get(Extension(state.clone()), &cache_key)
+                .await
+                .into_response();
+            assert_eq!(
+                response.headers()[header::CACHE_CONTROL],
+                expected_cache_control,
+                "cache control of {cache_key}"
+            );
+        }
+    }
+}
@@ -9,6 +9,12 @@ use etag::EntityTag;
 use reqwest::StatusCode;
 use tracing::{error, trace};
 
+// Response bodies larger than this are not buffered to compute an ETag. Hashing
+// requires holding the whole body in memory, which for big payloads like supply
+// over time spikes memory and delays the first byte. Bodies without a known
+// upper bound on their size (e.g. streams) are treated as large.
+pub const MAX_ETAG_BODY_SIZE: u64 = 256 * 1024;
+
 pub async fn middleware_fn<B: std::fmt::Debug>(
     req: Request<B>,
     next: Next<B>
@@ -16,6 +22,32 @@ pub async fn middleware_fn<B: std::fmt::Debug>(
     let if_none_match_header = req.headers().get(header::IF_NONE_MATCH).cloned();
     let path = req.uri().path().to_owned();
     let res = next.run(req).await;
+
+    // only successful responses carry a body worth tagging, anything else
+    // (304s, errors) is passed on untouched without buffering the body
+    if !res.status().is_success() {
+        return Ok(res);
+    }
+
+    // large or unbounded bodies are streamed to the client as they are, without an ETag
+    let body_too_large = res
+        .body()
+        .size_hint()
+        .upper()
+        .is_none_or(|upper| upper > MAX_ETAG_BODY_SIZE);
+    if body_too_large {
+        return Ok(res);
+    }
+
+    // JSON responses get compressed by the compression layer depending on the client's
+    // Accept-Encoding, the bytes sent differ per encoding while the content stays the same,
+    // so their ETag is weak
+    let is_compressible_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+
     let (mut parts, mut body) = res.into_parts();
     let bytes = {
         let mut body_bytes = vec![];
@@ -26,45 +58,35 @@ pub async fn middleware_fn<B: std::fmt::Debug>(
         body_bytes
     };
 
-    match bytes.is_empty() {
-        true => {
-            Ok(parts.into_response())
-        }
-        false => match if_none_match_header {
-            None => {
-                let etag = EntityTag::from_data(&bytes);
-                parts.headers.insert(header::ETAG,
-                HeaderValue::from_str(&etag.to_string()).unwrap(),);
-                Ok((parts, bytes).into_response())
-            }
-            Some(if_none_match_header) => {
-                let if_none_match_header = if_none_match_header.to_str().unwrap().parse::<EntityTag>();
-                match if_none_match_header {
-                    Err(ref err) => {
-                        let etag = EntityTag::from_data(&bytes);
-                        parts.headers.insert(
-                            header::ETAG,
-                            HeaderValue::from_str(&etag.to_string()).unwrap(),
-                        );
-                        Ok((parts, bytes).into_response())
-                    }
-                    Ok(if_none_match_etag) => {
-                        let etag = EntityTag::from_data(&bytes);
-                        parts.headers.insert(
-                            header::ETAG,
-                            HeaderValue::from_str(&if_none_match_etag.to_string()).unwrap(),
-                        );
-                        let some_match = etag.strong_eq(&if_none_match_etag);
-
-                        if some_match {
-                            Ok((StatusCode::NOT_MODIFIED, parts).into_response())
-                        } else {
-                            Ok((parts, bytes).into_response())
-                        }
-                    }
-                }
-            }
+    if bytes.is_empty() {
+        return Ok(parts.into_response());
+    }
+
+    let etag = {
+        let etag = EntityTag::from_data(&bytes);
+        if is_compressible_json {
+            EntityTag::weak(etag.tag())
+        } else {
+            etag
         }
+    };
+    parts.headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag.to_string()).unwrap(),
+    );
+
+    // If-None-Match uses the weak comparison, a weak and a strong tag with the same
+    // opaque value match
+    let some_match = if_none_match_header
+        .and_then(|if_none_match_header| {
+            if_none_match_header.to_str().ok()?.parse::<EntityTag>().ok()
+        })
+        .is_some_and(|if_none_match_etag| etag.weak_eq(&if_none_match_etag));
+
+    if some_match {
+        Ok((StatusCode::NOT_MODIFIED, parts).into_response())
+    } else {
+        Ok((parts, bytes).into_response())
     }
 }
 
@@ -80,6 +102,7 @@ mod tests {
     };
     use http_body_util::BodyExt;
     use tower::ServiceExt;
+    use tower_http::compression::CompressionLayer;
 
     #[tokio::test]
     async fn test_etag_middleware_no_if_none_match() {
@@ -123,6 +146,29 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
     }
 
+    #[tokio::test]
+    async fn test_etag_middleware_with_weak_if_none_match() {
+        let app = Router::new()
+            .route("/", get(|| async { "Hello, world!" }))
+            .layer(from_fn(middleware_fn));
+        let weak_etag =
+            EntityTag::weak(EntityTag::from_data(b"Hello, world!").tag());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::IF_NONE_MATCH, weak_etag.to_string())
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().contains_key(header::ETAG));
+    }
+
     #[tokio::test]
     async fn test_etag_middleware_with_non_matching_if_none_match() {
         let app = Router::new()
@@ -143,4 +189,113 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
         assert!(response.headers().contains_key(header::ETAG));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_etag_middleware_skips_error_response() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "something broke")
+                }),
+            )
+            .layer(from_fn(middleware_fn));
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!response.headers().contains_key(header::ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_etag_middleware_skips_large_body() {
+        let large_body = "a".repeat(MAX_ETAG_BODY_SIZE as usize + 1);
+        let expected_body = large_body.clone();
+        let app = Router::new()
+            .route("/", get(|| async move { large_body }))
+            .layer(from_fn(middleware_fn));
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(header::ETAG));
+
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.put(chunk.unwrap());
+        }
+        assert_eq!(body_bytes, expected_body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_etag_middleware_inside_compression() {
+        let app = Router::new()
+            // long enough for the compression layer to bother compressing it
+            .route("/", get(|| async { "Hello, world! ".repeat(16) }))
+            // router layers wrap the ones added before, compression runs outside the ETag middleware
+            .layer(from_fn(middleware_fn))
+            .layer(CompressionLayer::new());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(response.headers().contains_key(header::ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_etag_middleware_weak_etag_across_gzip() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    axum::Json(serde_json::json!({
+                        "supply": "120000000000000000000000000",
+                        "timestamp": "2024-10-17T12:00:00Z",
+                    }))
+                }),
+            )
+            // router layers wrap the ones added before, compression runs outside the ETag middleware
+            .layer(from_fn(middleware_fn))
+            .layer(CompressionLayer::new());
+        let gzip_request = |if_none_match: Option<&str>| {
+            let request = Request::builder()
+                .uri("/")
+                .header(header::ACCEPT_ENCODING, "gzip");
+            match if_none_match {
+                Some(etag) => request.header(header::IF_NONE_MATCH, etag),
+                None => request,
+            }
+            .body(Body::empty())
+            .unwrap()
+        };
+
+        let initial_response =
+            app.clone().oneshot(gzip_request(None)).await.unwrap();
+        assert_eq!(initial_response.headers()[header::CONTENT_ENCODING], "gzip");
+        let etag = initial_response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(etag.starts_with("W/"));
+
+        let response = app.oneshot(gzip_request(Some(&etag))).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+}
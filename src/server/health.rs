@@ -1,6 +1,9 @@
+use crate::caching::CacheKey;
 use crate::health::{HealthCheckable, HealthStatus};
+use crate::server::caching::Cache;
 use axum::response::IntoResponse;
 use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
 use std::sync::RwLock;
 
 pub struct ServerHealth {
@@ -43,6 +46,62 @@ impl HealthCheckable for ServerHealth {
     }
 }
 
+// whether the beacon sync keeps up with the chain, based on the slot lag published under
+// the block-lag cache key. as long as nothing is published we can't tell, that's not a
+// reason to take the server out of rotation
+pub struct SyncLagHealth {
+    block_lag: Option<i64>,
+    max_block_lag: i64,
+}
+
+impl SyncLagHealth {
+    pub fn new(block_lag: Option<i64>, max_block_lag: i64) -> Self {
+        Self {
+            block_lag,
+            max_block_lag,
+        }
+    }
+
+    pub fn from_cache(cache: &Cache, max_block_lag: i64) -> Self {
+        let block_lag = cache
+            .get(&CacheKey::BlockLag)
+            .and_then(|value| value.as_i64());
+        Self::new(block_lag, max_block_lag)
+    }
+}
+
+impl HealthCheckable for SyncLagHealth {
+    fn health_status(&self) -> HealthStatus {
+        match self.block_lag {
+            None => HealthStatus::Healthy(Some(
+                "[Health] no sync lag published yet".to_string(),
+            )),
+            Some(block_lag) if block_lag <= self.max_block_lag => {
+                HealthStatus::Healthy(Some(format!(
+                    "[Health] sync is {block_lag} slots behind"
+                )))
+            }
+            Some(block_lag) => HealthStatus::UnHealthy(Some(format!(
+                "[UnHealth] sync is {block_lag} slots behind, more than the allowed {}",
+                self.max_block_lag
+            ))),
+        }
+    }
+}
+
+// readiness: the server can only answer requests which need the DB when the
+// pool is able to hand out a working connection.
+pub async fn db_health_status(db_pool: &PgPool) -> HealthStatus {
+    match sqlx::query("SELECT 1").execute(db_pool).await {
+        Ok(_) => HealthStatus::Healthy(Some(
+            "[Health] db connection is available".to_string(),
+        )),
+        Err(err) => HealthStatus::UnHealthy(Some(format!(
+            "[UnHealth] db connection is unavailable: {err}"
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +203,23 @@ mod tests {
             _ => panic!("Expected UnHealthy status beyond 5 minutes"),
         }
     }
+
+    #[test]
+    fn test_sync_lag_health_large_lag() {
+        let health = SyncLagHealth::new(Some(500), 32);
+
+        match health.health_status() {
+            HealthStatus::UnHealthy(Some(msg)) => {
+                assert!(msg.contains("500 slots behind"));
+            }
+            _ => panic!("Expected UnHealthy status for a large sync lag"),
+        }
+    }
+
+    #[test]
+    fn test_sync_lag_health_small_lag() {
+        let health = SyncLagHealth::new(Some(2), 32);
+
+        assert!(matches!(health.health_status(), HealthStatus::Healthy(_)));
+    }
 }
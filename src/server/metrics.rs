@@ -0,0 +1,64 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use lazy_static::lazy_static;
+use prometheus::{Encoder, IntCounter, IntGauge, TextEncoder};
+
+use crate::performance::{gather_metrics, REGISTRY};
+
+use super::StateExtension;
+
+lazy_static! {
+    static ref CACHE_KEYS_PRESENT: IntGauge = {
+        let gauge = IntGauge::new(
+            "cache_keys_present",
+            "number of keys in the in-memory cache the server serves from",
+        )
+        .unwrap();
+        REGISTRY.register(Box::new(gauge.clone())).unwrap();
+        gauge
+    };
+    static ref CACHE_UPDATES_APPLIED: IntCounter = {
+        let counter = IntCounter::new(
+            "cache_updates_applied_total",
+            "cache update notifications applied to the in-memory cache",
+        )
+        .unwrap();
+        REGISTRY.register(Box::new(counter.clone())).unwrap();
+        counter
+    };
+}
+
+pub fn inc_cache_updates_applied() {
+    CACHE_UPDATES_APPLIED.inc();
+}
+
+// serves every metric in the global registry in the prometheus text exposition format
+pub async fn get_metrics(state: StateExtension) -> Response {
+    // read the cache size on scrape, that way the gauge can't drift from the map
+    CACHE_KEYS_PRESENT.set(state.cache.key_count() as i64);
+    // make sure the counter shows up in the exposition before the first update arrives
+    lazy_static::initialize(&CACHE_UPDATES_APPLIED);
+
+    get_registry_metrics().await
+}
+
+// like get_metrics, without the server's own cache metrics, for processes without a cache
+pub async fn get_registry_metrics() -> Response {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    match encoder.encode(&gather_metrics(), &mut buffer) {
+        Ok(()) => (
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(prometheus::TEXT_FORMAT),
+            )],
+            buffer,
+        )
+            .into_response(),
+        Err(err) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use crate::db::db;
 use crate::env;
-use crate::health::HealthCheckable;
+use crate::health::{CompositeHealth, HealthCheckable};
 use crate::kv_store::KVStorePostgres;
 use crate::server::caching::Cache;
 use crate::server::etag_middleware::middleware_fn;
 use crate::server::health::ServerHealth;
+use axum::error_handling::HandleErrorLayer;
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::{middleware, Extension, Router};
@@ -13,14 +15,25 @@ use lazy_static::lazy_static;
 use log::{error, info};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use futures::{try_join, TryFutureExt};
-use tower::ServiceBuilder;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::timeout::error::Elapsed;
+use tower::{BoxError, ServiceBuilder};
 use tower_http::compression::CompressionLayer;
 
 mod caching;
 mod etag_middleware;
 mod health;
+mod metrics;
+
+// connections the server's db pool opens
+const DB_POOL_MAX_CONNECTIONS: u32 = 3;
+
+// most requests are answered from the in-memory cache, so many more of them than there
+// are db connections can be in flight before the pool gets exhausted
+const MAX_IN_FLIGHT_REQUESTS_PER_DB_CONNECTION: usize = 32;
 
 lazy_static! {
     static ref FOUR_SECONDS: Duration = Duration::seconds(4);
@@ -36,10 +49,103 @@ pub struct State {
 
 pub type StateExtension = Extension<Arc<State>>;
 
+fn make_router(shared_state: Arc<State>) -> Router {
+    let max_in_flight_requests = shared_state.db_pool.options().get_max_connections()
+        as usize
+        * MAX_IN_FLIGHT_REQUESTS_PER_DB_CONNECTION;
+    make_router_with_in_flight_limit(shared_state, max_in_flight_requests)
+}
+
+// requests over `max_in_flight_requests` are answered with a 503 right away instead of
+// queueing up behind the ones in flight
+fn make_router_with_in_flight_limit(
+    shared_state: Arc<State>,
+    max_in_flight_requests: usize,
+) -> Router {
+    let router = Router::new()
+        .route("/metrics", get(metrics::get_metrics))
+        .route("/api/v2/fees/:cache_key", get(caching::cached_get_by_db_key))
+        .route("/api/v2/fees/all", get(caching::cached_get_all))
+        .route(
+            "/api/v2/fees/validator-balances.csv",
+            get(caching::cached_validator_balances_csv),
+        )
+        .route(
+            "/api/v2/fees/healthz",
+            get(|state: StateExtension| async move {
+                state.health.health_status().into_response()
+            }),
+        )
+        .route(
+            "/api/v2/fees/readyz",
+            get(|state: StateExtension| async move {
+                let db_health = health::db_health_status(&state.db_pool).await;
+                let sync_lag_health = health::SyncLagHealth::from_cache(
+                    &state.cache,
+                    env::ENV_CONFIG.max_sync_lag_slots,
+                );
+                CompositeHealth::new(vec![&db_health, &sync_lag_health])
+                    .health_status()
+                    .into_response()
+            }),
+        )
+        .layer(
+            ServiceBuilder::new()
+                // compression runs outside the ETag middleware, its output is a stream of
+                // unknown size which the middleware would pass through without an ETag.
+                // the tag is computed over the uncompressed body, so the same content
+                // gets the same (weak) tag whatever the encoding
+                .layer(CompressionLayer::new())
+                .layer(middleware::from_fn(etag_middleware::middleware_fn))
+                .layer(Extension(shared_state)),
+        );
+
+    with_request_limits(
+        router,
+        max_in_flight_requests,
+        std::time::Duration::from_secs(env::ENV_CONFIG.request_timeout_seconds),
+    )
+}
+
+// requests taking longer than `request_timeout` are answered with a 504, the timeout sits
+// inside the in-flight limit so a timed out request frees its slot
+fn with_request_limits(
+    router: Router,
+    max_in_flight_requests: usize,
+    request_timeout: std::time::Duration,
+) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|err: BoxError| async move {
+                if err.is::<Elapsed>() {
+                    StatusCode::GATEWAY_TIMEOUT
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                }
+            }))
+            .load_shed()
+            // the layer is applied to every route, a global limit shares a single
+            // semaphore between all of them
+            .layer(GlobalConcurrencyLimitLayer::new(max_in_flight_requests))
+            .timeout(request_timeout),
+    )
+}
+
 pub async fn start_server() {
+    start_server_with_shutdown(async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("expect to be able to listen for ctrl-c");
+    })
+    .await
+}
+
+// like start_server, but stops serving once `shutdown` resolves instead of on ctrl-c
+pub async fn start_server_with_shutdown(shutdown: impl Future<Output = ()>) {
     info!("starting serve fees");
     let started_on: DateTime<Utc> = chrono::Utc::now();
-    let db_pool = db::get_db_pool("eth-analysis-server", 3).await;
+    let db_pool =
+        db::get_db_pool("eth-analysis-server", DB_POOL_MAX_CONNECTIONS).await;
     let kv_store: KVStorePostgres = KVStorePostgres::new(db_pool.clone());
 
     let cache = Cache::new_with_data(&kv_store).await;
@@ -54,33 +160,458 @@ pub async fn start_server() {
 
     info!("health ready");
 
-    let update_cache_thread = caching::update_cache_from_notifications(
+    let socket_addr = SocketAddr::from(([0, 0, 0, 0], env::ENV_CONFIG.port));
+    serve(shared_state, socket_addr, shutdown).await;
+}
+
+// serve until `shutdown` resolves, in-flight requests are allowed to finish. the cache
+// update task is aborted afterwards so its PgListener connection is released
+async fn serve(
+    shared_state: Arc<State>,
+    socket_addr: SocketAddr,
+    shutdown: impl Future<Output = ()>,
+) {
+    let mut update_cache_thread = caching::update_cache_from_notifications(
         shared_state.clone(),
         &shared_state.db_pool,
     )
     .await;
 
-    let app = Router::new()
-        .route(
-            "/api/v2/fees/healthz",
-            get(|state: StateExtension| async move {
-                state.health.health_status().into_response()
+    let app = make_router(shared_state);
+    let server_thread = axum::Server::bind(&socket_addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown);
+
+    tokio::select! {
+        result = server_thread => {
+            if let Err(err) = result {
+                error!("{}", err);
+            }
+        }
+        result = &mut update_cache_thread => {
+            error!("cache update task stopped: {:?}", result);
+        }
+    }
+
+    update_cache_thread.abort();
+    info!("server stopped");
+}
+
+fn make_metrics_router() -> Router {
+    Router::new().route("/metrics", get(metrics::get_registry_metrics))
+}
+
+// serves only /metrics, for processes like the beacon state sync which have no api of their
+// own but whose metrics should still be scraped
+pub async fn serve_metrics(socket_addr: SocketAddr) {
+    info!("serving metrics on {socket_addr}");
+    if let Err(err) = axum::Server::bind(&socket_addr)
+        .serve(make_metrics_router().into_make_service())
+        .await
+    {
+        error!("metrics server stopped: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caching::CacheKey;
+    use crate::db::db::tests::TestDb;
+    use axum::{
+        body::{Body, HttpBody},
+        http::{header, Request},
+    };
+    use reqwest::StatusCode;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn readyz_with_available_db_test() {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn requests_over_in_flight_limit_test() {
+        // /held takes the only in-flight slot until the test releases it
+        let held_started = Arc::new(tokio::sync::Notify::new());
+        let release_held = Arc::new(tokio::sync::Notify::new());
+        let router = Router::new()
+            .route(
+                "/held",
+                get({
+                    let held_started = held_started.clone();
+                    let release_held = release_held.clone();
+                    move || async move {
+                        held_started.notify_one();
+                        release_held.notified().await;
+                        "released"
+                    }
+                }),
+            )
+            .route("/quick", get(|| async { "quick" }));
+        let router =
+            with_request_limits(router, 1, std::time::Duration::from_secs(5));
+        let request = |uri: &str| {
+            Request::builder().uri(uri).body(Body::empty()).unwrap()
+        };
+
+        let held_request =
+            tokio::spawn(router.clone().oneshot(request("/held")));
+        held_started.notified().await;
+
+        let shed_response =
+            router.clone().oneshot(request("/quick")).await.unwrap();
+        assert_eq!(shed_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        release_held.notify_one();
+        let held_response = held_request.await.unwrap().unwrap();
+        assert_eq!(held_response.status(), StatusCode::OK);
+
+        let quick_response = router.oneshot(request("/quick")).await.unwrap();
+        assert_eq!(quick_response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn slow_request_times_out_test() {
+        let router = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                "too late"
             }),
+        );
+        let router = with_request_limits(
+            router,
+            1,
+            std::time::Duration::from_millis(50),
+        );
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            router.oneshot(Request::builder().uri("/slow").body(Body::empty()).unwrap()),
         )
-        .layer(
-            ServiceBuilder::new()
-                .layer(middleware::from_fn(etag_middleware::middleware_fn))
-                .layer(CompressionLayer::new())
-                .layer(Extension(shared_state)),
+        .await
+        .expect("expect the request to time out before the handler finishes")
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn cached_get_all_test() {
+        let test_db = TestDb::new().await;
+        let kv_store = KVStorePostgres::new(test_db.pool.clone());
+        let values = [
+            (CacheKey::BaseFeePerGas, serde_json::json!(819)),
+            (CacheKey::EffectiveBalanceSum, serde_json::json!({ "sum": 1 })),
+            (CacheKey::SupplyOverTime, serde_json::json!([1, 2, 3])),
+        ];
+        for (cache_key, value) in &values {
+            crate::caching::set_value(&test_db.pool, cache_key, value)
+                .await
+                .unwrap();
+        }
+        let shared_state = Arc::new(State {
+            cache: Cache::new_with_data(&kv_store).await,
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/all")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        // the base fee changes fastest, so it sets the max age of the whole response
+        assert_eq!(
+            response.headers().get("cache-control").unwrap(),
+            "public, max-age=6, stale-while-revalidate=120"
         );
-    let port = "3002";
-    let socket_addr = format!("0.0.0.0:{}", port).parse().unwrap();
-    let server_thread =
-        axum::Server::bind(&socket_addr).serve(app.into_make_service());
-
-    try_join!(
-        update_cache_thread.map_err(|err| error!("{}", err)),
-        server_thread.map_err(|err| error!("{}", err))
-    )
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body: serde_json::Value =
+            serde_json::from_slice(&body_bytes).unwrap();
+        for (cache_key, value) in &values {
+            assert_eq!(&body[cache_key.to_db_key()], value);
+        }
+    }
+
+    async fn get_cached(cache_key: CacheKey) -> (StatusCode, serde_json::Value) {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/v2/fees/{}", cache_key.to_db_key()))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, serde_json::from_slice(&body_bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn cached_get_without_producer_test() {
+        let (status, body) = get_cached(CacheKey::BurnRates).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "no_producer",
+                "cache_key": "burn-rates",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_get_not_yet_available_test() {
+        let (status, body) = get_cached(CacheKey::EffectiveBalanceSum).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "not_yet_available",
+                "cache_key": "effective-balance-sum",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_get_unknown_cache_key_test() {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/no-such-cache-key")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn cached_validator_balances_by_day_test() {
+        let test_db = TestDb::new().await;
+        let kv_store = KVStorePostgres::new(test_db.pool.clone());
+        // a series no aggregation of beacon_validators_balance would produce, so getting it
+        // back shows the endpoint serves the published value
+        let series = serde_json::json!([{ "t": 0, "v": 814 }]);
+        crate::caching::set_value(
+            &test_db.pool,
+            &CacheKey::ValidatorBalancesByDay,
+            &series,
+        )
+        .await
         .unwrap();
+        let shared_state = Arc::new(State {
+            cache: Cache::new_with_data(&kv_store).await,
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/validator-balances-by-day")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body: serde_json::Value =
+            serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(body, series);
+    }
+
+    #[tokio::test]
+    async fn validator_balances_csv_test() {
+        let test_db = TestDb::new().await;
+        let kv_store = KVStorePostgres::new(test_db.pool.clone());
+        // nothing in beacon_validators_balance aggregates to this, so getting it back
+        // shows the csv is built from the published series
+        let series = serde_json::json!({
+            "data": [{ "t": 0, "v": 822 }, { "t": 86400, "v": 823 }],
+            "meta": { "from": 0, "to": 86400, "count": 2, "truncated": false },
+        });
+        crate::caching::set_value(
+            &test_db.pool,
+            &CacheKey::ValidatorBalancesByDay,
+            &series,
+        )
+        .await
+        .unwrap();
+        let shared_state = Arc::new(State {
+            cache: Cache::new_with_data(&kv_store).await,
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/validator-balances.csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(
+            String::from_utf8(body_bytes).unwrap(),
+            "timestamp,gwei\n0,822\n86400,823\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn validator_balances_csv_not_yet_available_test() {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v2/fees/validator-balances.csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn metrics_test() {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+
+        let response = make_router(shared_state)
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            prometheus::TEXT_FORMAT
+        );
+        let mut body = response.into_body();
+        let mut body_bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            body_bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body = String::from_utf8(body_bytes).unwrap();
+        assert!(body.lines().any(|line| line == "cache_keys_present 0"));
+        assert!(body.contains("cache_updates_applied_total"));
+    }
+
+    #[tokio::test]
+    async fn metrics_router_test() {
+        let response = make_metrics_router()
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_TYPE],
+            prometheus::TEXT_FORMAT
+        );
+    }
+
+    #[tokio::test]
+    async fn serve_stops_on_shutdown_test() {
+        let test_db = TestDb::new().await;
+        let shared_state = Arc::new(State {
+            cache: Cache::new(),
+            db_pool: test_db.pool.clone(),
+            health: ServerHealth::new(Utc::now()),
+        });
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let server = serve(
+            shared_state,
+            "127.0.0.1:0".parse().unwrap(),
+            async {
+                shutdown_rx.await.ok();
+            },
+        );
+        shutdown_tx.send(()).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("expect server to stop after shutdown");
+    }
 }
@@ -0,0 +1,489 @@
+//! Per-slot supply deltas, summing them up from genesis gives a rigorous eth supply instead of
+//! the glassnode based estimates.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    beacon_chain::Slot,
+    caching::{self, CacheKey},
+    execution_chain::{GENESIS_SUPPLY, MERGE_SLOT_SUPPLY},
+    units::{GweiNewtype, WeiNewtype},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SupplyAtSlot {
+    pub slot: Slot,
+    pub timestamp: DateTime<Utc>,
+    pub supply: WeiNewtype,
+}
+
+// sums of int8 columns come back as NUMERIC, we read them as text to keep full precision
+fn supply_from_delta_sum(delta_sum_gwei: &str) -> WeiNewtype {
+    let delta_sum_gwei = delta_sum_gwei
+        .parse::<i128>()
+        .expect("expect supply delta sum to be an integer");
+    GENESIS_SUPPLY
+        + WeiNewtype(delta_sum_gwei * GweiNewtype::WEI_PER_GWEI as i128)
+}
+
+// store how much eth was issued and burned in the given slot, both in gwei
+pub async fn store_supply_delta(
+    executor: impl PgExecutor<'_>,
+    slot: Slot,
+    issuance_delta: &GweiNewtype,
+    burn_delta: &GweiNewtype,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO supply_deltas (slot, timestamp, issuance_delta, burn_delta)
+        VALUES ($1, $2, $3, $4)
+        ",
+        slot.0,
+        slot.date_time(),
+        issuance_delta.0,
+        burn_delta.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// delete all deltas with slot >= given slot, should run before the matching beacon_states
+// records are deleted as supply_deltas references them
+pub async fn delete_supply_deltas(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM supply_deltas
+        WHERE slot >= $1
+        ",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_supply_delta(executor: impl PgExecutor<'_>, slot: Slot) {
+    sqlx::query!(
+        "
+        DELETE FROM supply_deltas
+        WHERE slot = $1
+        ",
+        slot.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// delete the deltas of all slots in [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_supply_deltas_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM supply_deltas
+        WHERE slot >= $1 AND slot <= $2
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// eth_supply rows point at the beacon states they were computed from, delete the ones
+// computed from any state with slot >= given slot. should run before the matching
+// beacon_states records are deleted. on an empty table this is simply a no-op
+pub async fn delete_supply_by_state_root(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM eth_supply
+        WHERE deposits_slot >= $1
+        OR balances_slot >= $1
+        ",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// same as delete_supply_by_state_root, for the eth_supply rows computed from a single slot
+pub async fn delete_supply_by_slot(executor: impl PgExecutor<'_>, slot: Slot) {
+    sqlx::query!(
+        "
+        DELETE FROM eth_supply
+        WHERE deposits_slot = $1
+        OR balances_slot = $1
+        ",
+        slot.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// same as delete_supply_by_state_root, limited to the states in
+// [greater_than_or_equal, less_than_or_equal]
+pub async fn delete_supply_in_range(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM eth_supply
+        WHERE (deposits_slot >= $1 AND deposits_slot <= $2)
+        OR (balances_slot >= $1 AND balances_slot <= $2)
+        ",
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+// supply at the given slot, genesis supply plus every delta up to and including the slot
+pub async fn get_supply_at_slot(
+    executor: impl PgExecutor<'_>,
+    slot: Slot,
+) -> WeiNewtype {
+    let delta_sum = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(issuance_delta - burn_delta), 0)::TEXT AS "delta_sum!"
+        FROM
+            supply_deltas
+        WHERE
+            slot <= $1
+        "#,
+        slot.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .delta_sum;
+
+    supply_from_delta_sum(&delta_sum)
+}
+
+// supply after every slot in [greater_than_or_equal, less_than_or_equal] we have a delta for,
+// the running sum of the deltas plus genesis supply. the deltas before the range are summed
+// up once, so only the rows in the range are fetched
+pub async fn get_supply_over_time(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: Slot,
+    less_than_or_equal: Slot,
+) -> Vec<SupplyAtSlot> {
+    sqlx::query!(
+        r#"
+        SELECT
+            slot,
+            timestamp,
+            (
+                (
+                    SELECT
+                        COALESCE(SUM(issuance_delta - burn_delta), 0)
+                    FROM
+                        supply_deltas
+                    WHERE
+                        slot < $1
+                )
+                + SUM(issuance_delta - burn_delta) OVER (ORDER BY slot)
+            )::TEXT AS "delta_sum!"
+        FROM
+            supply_deltas
+        WHERE
+            slot >= $1
+        AND
+            slot <= $2
+        ORDER BY slot ASC
+        "#,
+        greater_than_or_equal.0,
+        less_than_or_equal.0
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| SupplyAtSlot {
+        slot: Slot(row.slot),
+        timestamp: row.timestamp,
+        supply: supply_from_delta_sum(&row.delta_sum),
+    })
+    .collect()
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SupplyParts {
+    pub slot: Slot,
+    pub beacon_balances_sum_gwei: GweiNewtype,
+    pub beacon_deposits_sum_gwei: GweiNewtype,
+    pub execution_balances_sum_estimate_wei: WeiNewtype,
+}
+
+// the parts the supply is made of at the last slot we have validator balances for.
+// supply = execution balances + beacon balances - beacon deposits, as deposited eth is both
+// on the beacon chain and in the deposit contract. we don't track execution balances yet,
+// so they're estimated by taking the supply to have stayed at what it was at the merge.
+// returns None while no validator balances have been synced
+pub async fn compute_supply_parts(
+    executor: impl PgExecutor<'_>,
+) -> Option<SupplyParts> {
+    sqlx::query!(
+        r#"
+        SELECT
+            beacon_states.slot,
+            beacon_validators_balance.gwei AS balances_sum,
+            (
+                SELECT
+                    beacon_blocks.deposit_sum_aggregated
+                FROM
+                    beacon_blocks
+                JOIN beacon_states AS block_states ON
+                    beacon_blocks.state_root = block_states.state_root
+                WHERE
+                    block_states.slot <= beacon_states.slot
+                ORDER BY
+                    block_states.slot DESC
+                LIMIT 1
+            ) AS "deposits_sum?"
+        FROM
+            beacon_validators_balance
+        JOIN beacon_states ON
+            beacon_validators_balance.state_root = beacon_states.state_root
+        ORDER BY
+            beacon_states.slot DESC
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| {
+        let beacon_balances_sum_gwei = GweiNewtype(row.balances_sum);
+        // no block stored up to the slot means no deposits we know of
+        let beacon_deposits_sum_gwei =
+            GweiNewtype(row.deposits_sum.unwrap_or(0));
+        let execution_balances_sum_estimate_wei = MERGE_SLOT_SUPPLY
+            - WeiNewtype::from(beacon_balances_sum_gwei)
+            + WeiNewtype::from(beacon_deposits_sum_gwei);
+
+        SupplyParts {
+            slot: Slot(row.slot),
+            beacon_balances_sum_gwei,
+            beacon_deposits_sum_gwei,
+            execution_balances_sum_estimate_wei,
+        }
+    })
+}
+
+pub async fn update_supply_parts(
+    db_pool: &PgPool,
+) -> Result<(), serde_json::Error> {
+    match compute_supply_parts(db_pool).await {
+        Some(supply_parts) => {
+            debug!(?supply_parts, "publishing supply parts");
+            caching::update_and_publish(
+                db_pool,
+                &CacheKey::SupplyParts,
+                supply_parts,
+            )
+            .await?;
+        }
+        None => {
+            debug!("no validator balances stored yet, skipping supply parts")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Connection, PgConnection};
+
+    use super::*;
+    use crate::beacon_chain::store_state;
+    use crate::db::db;
+
+    async fn store_test_state(executor: &mut PgConnection, slot: Slot) {
+        store_state(executor, &format!("0xsupply_delta_test_{}", slot.0), slot)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn supply_from_deltas_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // far beyond slots other tests store so no other deltas are summed up
+        let first_slot = Slot(i32::MAX - 201);
+        let second_slot = Slot(i32::MAX - 200);
+
+        // deltas other tests committed below our slots are part of the supply too
+        let supply_before =
+            get_supply_at_slot(&mut *transaction, first_slot - 1).await;
+        store_test_state(&mut transaction, first_slot).await;
+        store_test_state(&mut transaction, second_slot).await;
+        store_supply_delta(
+            &mut *transaction,
+            first_slot,
+            &GweiNewtype(100),
+            &GweiNewtype(30),
+        )
+        .await;
+        store_supply_delta(
+            &mut *transaction,
+            second_slot,
+            &GweiNewtype(50),
+            &GweiNewtype(80),
+        )
+        .await;
+
+        let expected_supply =
+            supply_before + WeiNewtype::from(GweiNewtype(100 - 30 + 50 - 80));
+        let supply = get_supply_at_slot(&mut *transaction, second_slot).await;
+        assert_eq!(supply, expected_supply);
+
+        let supply_over_time =
+            get_supply_over_time(&mut *transaction, first_slot, second_slot)
+                .await;
+        assert_eq!(
+            supply_over_time
+                .iter()
+                .map(|supply_at_slot| (
+                    supply_at_slot.slot,
+                    supply_at_slot.supply
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (first_slot, supply_before + WeiNewtype::from(GweiNewtype(70))),
+                (second_slot, expected_supply),
+            ]
+        );
+    }
+
+    async fn store_test_eth_supply(executor: &mut PgConnection, slot: Slot) {
+        sqlx::query!(
+            "
+            INSERT INTO eth_supply (timestamp, block_number, deposits_slot, balances_slot, supply)
+            VALUES ($1, $2, $3, $3, 0)
+            ",
+            slot.date_time(),
+            slot.0,
+            slot.0
+        )
+        .execute(executor)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_supply_by_state_root_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        // far beyond slots other tests store supply for
+        let slots = [
+            Slot(i32::MAX - 702),
+            Slot(i32::MAX - 701),
+            Slot(i32::MAX - 700),
+        ];
+
+        for slot in slots {
+            store_test_state(&mut transaction, slot).await;
+            store_test_eth_supply(&mut transaction, slot).await;
+        }
+
+        delete_supply_by_state_root(&mut *transaction, slots[1]).await;
+
+        let remaining_slots: Vec<i32> = sqlx::query_scalar!(
+            "
+            SELECT balances_slot FROM eth_supply
+            WHERE balances_slot >= $1
+            ORDER BY balances_slot
+            ",
+            slots[0].0
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .unwrap();
+        assert_eq!(remaining_slots, vec![slots[0].0]);
+    }
+
+    #[tokio::test]
+    async fn compute_supply_parts_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+        let slot = Slot(i32::MAX - 1800);
+        let state_root = format!("0xsupply_delta_test_{}", slot.0);
+        store_test_state(&mut transaction, slot).await;
+        sqlx::query!(
+            "
+            INSERT INTO beacon_validators_balance (timestamp, state_root, gwei)
+            VALUES ($1, $2, $3)
+            ",
+            slot.date_time(),
+            state_root,
+            32_000_000_000_i64
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            "
+            INSERT INTO beacon_blocks (
+                block_root,
+                state_root,
+                parent_root,
+                deposit_sum,
+                deposit_sum_aggregated
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            "0xsupply_parts_test_block_root",
+            state_root,
+            "0xsupply_parts_test_parent_root",
+            0,
+            64_000_000_000_i64
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        let supply_parts =
+            compute_supply_parts(&mut *transaction).await.unwrap();
+
+        assert_eq!(supply_parts.slot, slot);
+        assert_eq!(
+            supply_parts.beacon_balances_sum_gwei,
+            GweiNewtype(32_000_000_000)
+        );
+        let serialized = serde_json::to_value(&supply_parts).unwrap();
+        assert_eq!(serialized["beacon_balances_sum_gwei"], "32000000000");
+        assert_eq!(
+            supply_parts.beacon_deposits_sum_gwei,
+            GweiNewtype(64_000_000_000)
+        );
+        assert_eq!(
+            serialized["execution_balances_sum_estimate_wei"],
+            String::from(
+                MERGE_SLOT_SUPPLY - WeiNewtype::from_eth(32)
+                    + WeiNewtype::from_eth(64)
+            )
+        );
+    }
+}
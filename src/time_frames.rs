@@ -12,6 +12,7 @@ use thiserror::Error;
 
 use GrowingTimeFrame::*;
 use LimitedTimeFrame::*;
+use crate::beacon_chain::{Slot, SlotRange, FIRST_POST_LONDON_SLOT, FIRST_POST_MERGE_SLOT};
 use crate::execution_chain;
 use crate::execution_chain::{BlockNumber, LONDON_HARD_FORK_BLOCK_NUMBER, MERGE_BLOCK_NUMBER};
 
@@ -154,6 +155,13 @@ impl GrowingTimeFrame {
         }
     }
 
+    pub fn start_slot(&self) -> Slot {
+        match self {
+            SinceBurn => FIRST_POST_LONDON_SLOT,
+            SinceMerge => FIRST_POST_MERGE_SLOT,
+        }
+    }
+
     pub fn duration(&self) -> Duration {
         self.into()
     }
@@ -198,6 +206,33 @@ impl TimeFrame {
         self.duration().num_minutes() as f64 / MINUTES_PER_HOUR / HOURS_PER_DAY / DAYS_PER_YEAR
     }
 
+    // the window a time frame covers looking back from `now`, growing time frames start at
+    // their fork, limited ones one duration before `now`
+    pub fn to_date_time_range(
+        &self,
+        now: DateTime<Utc>,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start = match self {
+            TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_timestamp(),
+            TimeFrame::Limited(limited_time_frame) => now - limited_time_frame.duration(),
+        };
+        (start, now)
+    }
+
+    // the slots a time frame covers up to and including `head`, limited time frames span their
+    // slot count, but never reach before genesis. panics when `head` is before a growing time
+    // frame's fork
+    pub fn to_slot_range(&self, head: Slot) -> SlotRange {
+        let start = match self {
+            TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_slot(),
+            TimeFrame::Limited(limited_time_frame) => {
+                let slot_count = limited_time_frame.slot_count() as i32;
+                (head - (slot_count - 1)).max(Slot::GENESIS)
+            }
+        };
+        SlotRange::new(start, head)
+    }
+
     // pub fn start_timestamp(&self, block: &ExecutionNodeBlock) -> DateTime<Utc> {
     //     match self {
     //         TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_timestamp(),
@@ -279,4 +314,75 @@ mod tests {
         let limited_time_frame = "d30".parse::<TimeFrame>().unwrap();
         assert_eq!(limited_time_frame, TimeFrame::Limited(Day30))
     }
+
+    #[test]
+    fn display_parse_round_trip_test() {
+        for time_frame in all::<TimeFrame>() {
+            assert_eq!(time_frame.to_string().parse::<TimeFrame>().unwrap(), time_frame);
+        }
+    }
+
+    #[test]
+    fn growing_to_date_time_range_test() {
+        let now = "2024-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        assert_eq!(
+            TimeFrame::Growing(SinceBurn).to_date_time_range(now),
+            (*execution_chain::LONDON_HARD_FORK_TIMESTAMP, now)
+        );
+        assert_eq!(
+            TimeFrame::Growing(SinceMerge).to_date_time_range(now),
+            (*execution_chain::PARIS_HARD_FORK_TIMESTAMP, now)
+        );
+    }
+
+    #[test]
+    fn limited_to_date_time_range_test() {
+        let now = "2024-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let expected_starts = [
+            (Minute5, "2024-05-31T23:55:00Z"),
+            (Hour1, "2024-05-31T23:00:00Z"),
+            (Day1, "2024-05-31T00:00:00Z"),
+            (Day7, "2024-05-25T00:00:00Z"),
+            (Day30, "2024-05-02T00:00:00Z"),
+        ];
+
+        for (limited_time_frame, expected_start) in expected_starts {
+            assert_eq!(
+                TimeFrame::Limited(limited_time_frame).to_date_time_range(now),
+                (expected_start.parse().unwrap(), now),
+                "{limited_time_frame}"
+            );
+        }
+    }
+    #[test]
+    fn limited_to_slot_range_test() {
+        let head = Slot(8_000_000);
+
+        let minute_5 = TimeFrame::Limited(Minute5).to_slot_range(head);
+        assert_eq!(minute_5.less_than_or_equal(), head);
+        assert_eq!(minute_5.into_iter().count(), 25);
+
+        let day_1 = TimeFrame::Limited(Day1).to_slot_range(head);
+        assert_eq!(day_1.less_than_or_equal(), head);
+        assert_eq!(day_1.into_iter().count(), 7200);
+    }
+
+    #[test]
+    fn limited_to_slot_range_near_genesis_test() {
+        let slot_range = TimeFrame::Limited(Day1).to_slot_range(Slot(10));
+        assert_eq!(slot_range.greater_than_or_equal(), Slot::GENESIS);
+    }
+
+    #[test]
+    fn growing_to_slot_range_test() {
+        let head = Slot(8_000_000);
+
+        let since_merge = TimeFrame::Growing(SinceMerge).to_slot_range(head);
+        assert_eq!(since_merge.greater_than_or_equal(), FIRST_POST_MERGE_SLOT);
+        assert_eq!(since_merge.less_than_or_equal(), head);
+
+        let since_burn = TimeFrame::Growing(SinceBurn).to_slot_range(head);
+        assert_eq!(since_burn.greater_than_or_equal(), FIRST_POST_LONDON_SLOT);
+    }
 }
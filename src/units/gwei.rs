@@ -23,8 +23,50 @@ impl fmt::Display for GweiNewtype {
     }
 }
 
+// renders the amount in ETH with all nine gwei decimals, e.g. 32.000000000 ETH
+struct GweiAsEth(GweiNewtype);
+
+impl fmt::Display for GweiAsEth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let GweiAsEth(GweiNewtype(amount)) = self;
+        let sign = if *amount < 0 { "-" } else { "" };
+        let amount = amount.unsigned_abs();
+        let gwei_per_eth = EthNewtype::GWEI_PER_ETH as u64;
+        write!(
+            f,
+            "{sign}{}.{:09} ETH",
+            amount / gwei_per_eth,
+            amount % gwei_per_eth
+        )
+    }
+}
+
 impl GweiNewtype {
     pub const WEI_PER_GWEI: u32 = 1_000_000_000;
+
+    pub fn to_eth_f64(&self) -> f64 {
+        EthNewtype::from(*self).0
+    }
+
+    // Display renders the plain gwei amount so it parses back with FromStr, use this to
+    // show the amount in ETH, e.g. in logs
+    pub fn display_eth(&self) -> impl fmt::Display {
+        GweiAsEth(*self)
+    }
+
+    // the Add and Sub impls panic on overflow, use these where inconsistent data could
+    // push a result out of range and the caller should decide what to do
+    pub fn checked_add(self, GweiNewtype(rhs): Self) -> Option<Self> {
+        self.0.checked_add(rhs).map(GweiNewtype)
+    }
+
+    pub fn checked_sub(self, GweiNewtype(rhs): Self) -> Option<Self> {
+        self.0.checked_sub(rhs).map(GweiNewtype)
+    }
+
+    pub fn saturating_sub(self, GweiNewtype(rhs): Self) -> Self {
+        GweiNewtype(self.0.saturating_sub(rhs))
+    }
 }
 
 impl Add<GweiNewtype> for GweiNewtype {
@@ -181,4 +223,63 @@ mod tests {
     fn gwei_sub_test() {
         assert_eq!(GweiNewtype(1) - GweiNewtype(1), GweiNewtype(0));
     }
+
+    #[test]
+    fn gwei_display_test() {
+        let gwei = GweiNewtype(-1_500_000_001);
+        assert_eq!(gwei.to_string(), "-1500000001");
+        assert_eq!(gwei.to_string().parse::<GweiNewtype>(), Ok(gwei));
+    }
+
+    #[test]
+    fn gwei_display_eth_test() {
+        assert_eq!(
+            GweiNewtype(32_000_000_000).display_eth().to_string(),
+            "32.000000000 ETH"
+        );
+        assert_eq!(
+            GweiNewtype(1_500_000_001).display_eth().to_string(),
+            "1.500000001 ETH"
+        );
+        assert_eq!(
+            GweiNewtype(-2_500_000).display_eth().to_string(),
+            "-0.002500000 ETH"
+        );
+    }
+
+    #[test]
+    fn gwei_to_eth_f64_test() {
+        assert_eq!(GweiNewtype(32_000_000_000).to_eth_f64(), 32.0);
+        assert_eq!(GweiNewtype(-500_000_000).to_eth_f64(), -0.5);
+    }
+
+    #[test]
+    fn gwei_checked_add_test() {
+        assert_eq!(
+            GweiNewtype(1).checked_add(GweiNewtype(1)),
+            Some(GweiNewtype(2))
+        );
+        assert_eq!(GweiNewtype(i64::MAX).checked_add(GweiNewtype(1)), None);
+    }
+
+    #[test]
+    fn gwei_checked_sub_test() {
+        assert_eq!(
+            GweiNewtype(1).checked_sub(GweiNewtype(2)),
+            Some(GweiNewtype(-1))
+        );
+        assert_eq!(GweiNewtype(i64::MIN).checked_sub(GweiNewtype(1)), None);
+    }
+
+    #[test]
+    fn gwei_saturating_sub_test() {
+        assert_eq!(
+            GweiNewtype(3).saturating_sub(GweiNewtype(1)),
+            GweiNewtype(2)
+        );
+        assert_eq!(
+            GweiNewtype(i64::MIN).saturating_sub(GweiNewtype(1)),
+            GweiNewtype(i64::MIN)
+        );
+    }
 }
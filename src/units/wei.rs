@@ -25,6 +25,33 @@ impl WeiNewtype {
     pub fn from_eth(eth: i128) -> Self {
         Self(eth * WEI_PER_ETH)
     }
+
+    pub fn to_eth_f64(&self) -> f64 {
+        EthNewtype::from(*self).0
+    }
+
+    // Display renders the plain wei amount so it parses back with FromStr, use this to
+    // show the amount in ETH, e.g. in logs
+    pub fn display_eth(&self) -> impl Display {
+        WeiAsEth(*self)
+    }
+
+    // converting to GweiNewtype with `into` truncates, this rounds to the nearest gwei
+    // instead, with halves rounded away from zero.
+    pub fn to_gwei_rounded(&self) -> GweiNewtype {
+        let wei_per_gwei = GweiNewtype::WEI_PER_GWEI as i128;
+        let WeiNewtype(amount) = *self;
+        let gwei = amount / wei_per_gwei;
+        let remainder = amount % wei_per_gwei;
+        let gwei = if remainder.abs() * 2 >= wei_per_gwei {
+            gwei + remainder.signum()
+        } else {
+            gwei
+        };
+        GweiNewtype(
+            i64::try_from(gwei).expect("expect wei amount to fit in gwei"),
+        )
+    }
 }
 
 impl Add<WeiNewtype> for WeiNewtype {
@@ -58,6 +85,24 @@ impl Display for WeiNewtype {
     }
 }
 
+// renders the amount in ETH with all eighteen wei decimals, e.g. 1.500000000000000000 ETH
+struct WeiAsEth(WeiNewtype);
+
+impl Display for WeiAsEth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let WeiAsEth(WeiNewtype(amount)) = self;
+        let sign = if *amount < 0 { "-" } else { "" };
+        let amount = amount.unsigned_abs();
+        let wei_per_eth = WEI_PER_ETH as u128;
+        write!(
+            f,
+            "{sign}{}.{:018} ETH",
+            amount / wei_per_eth,
+            amount % wei_per_eth
+        )
+    }
+}
+
 impl From<WeiNewtype> for String {
     fn from(WeiNewtype(amount): WeiNewtype) -> Self {
         amount.to_string()
@@ -105,3 +150,56 @@ impl From<EthNewtype> for WeiNewtype {
 }
 
 pub type Wei = i128;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wei_from_gwei_test() {
+        assert_eq!(
+            WeiNewtype::from(GweiNewtype(32_000_000_000)),
+            WeiNewtype::from_eth(32)
+        );
+    }
+
+    #[test]
+    fn wei_display_test() {
+        let wei = WeiNewtype(-1_500_000_000_000_000_001);
+        assert_eq!(wei.to_string(), "-1500000000000000001");
+        assert_eq!(wei.to_string().parse::<WeiNewtype>(), Ok(wei));
+    }
+
+    #[test]
+    fn wei_display_eth_test() {
+        assert_eq!(
+            WeiNewtype::from_eth(32).display_eth().to_string(),
+            "32.000000000000000000 ETH"
+        );
+        assert_eq!(
+            WeiNewtype(1_500_000_000_000_000_001).display_eth().to_string(),
+            "1.500000000000000001 ETH"
+        );
+        assert_eq!(
+            WeiNewtype(-2_500_000_000_000_000).display_eth().to_string(),
+            "-0.002500000000000000 ETH"
+        );
+    }
+
+    #[test]
+    fn wei_to_eth_f64_test() {
+        assert_eq!(WeiNewtype::from_eth(32).to_eth_f64(), 32.0);
+        assert_eq!(WeiNewtype(-500_000_000_000_000_000).to_eth_f64(), -0.5);
+    }
+
+    #[test]
+    fn to_gwei_rounded_test() {
+        assert_eq!(WeiNewtype(1_499_999_999).to_gwei_rounded(), GweiNewtype(1));
+        assert_eq!(WeiNewtype(1_500_000_000).to_gwei_rounded(), GweiNewtype(2));
+        assert_eq!(
+            WeiNewtype(-1_500_000_000).to_gwei_rounded(),
+            GweiNewtype(-2)
+        );
+        assert_eq!(WeiNewtype(499_999_999).to_gwei_rounded(), GweiNewtype(0));
+    }
+}